@@ -0,0 +1,31 @@
+//! Reads the header of a remote archive with the `fetch` backend.
+//!
+//! Build for the browser with
+//! `cargo build --example fetch_header --target wasm32-unknown-unknown --features fetch-async`
+//! and load the resulting module with `wasm-bindgen`. The archive must be served with CORS
+//! headers allowing the `Range` request header.
+
+use pmtiles::async_reader::AsyncPmTilesReader;
+use wasm_bindgen_futures::spawn_local;
+
+const URL: &str = "https://protomaps.github.io/PMTiles/protomaps(vector)ODbL_firenze.pmtiles";
+
+fn main() {
+    spawn_local(async {
+        match AsyncPmTilesReader::new_with_fetch_url(URL).await {
+            Ok(reader) => {
+                let header = reader.get_header();
+                web_sys::console::log_1(
+                    &format!(
+                        "{:?} tiles, zoom {}..={}",
+                        header.tile_type(),
+                        header.min_zoom(),
+                        header.max_zoom()
+                    )
+                    .into(),
+                );
+            }
+            Err(e) => web_sys::console::error_1(&e.to_string().into()),
+        }
+    });
+}
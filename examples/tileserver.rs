@@ -0,0 +1,147 @@
+//! Serves the tiles of a local archive at `http://<addr>/{z}/{x}/{y}`.
+//!
+//! Run with `cargo run --example tileserver --features mmap-async-tokio -- <archive> [addr]`.
+//! The address defaults to `127.0.0.1:8080`. Tiles are sent as stored, with a
+//! `Content-Encoding` matching the tile compression of the archive.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use pmtiles::async_reader::{AsyncPmTilesReader, PmTilesReaderBuilder};
+use pmtiles::cache::LruDirCache;
+use pmtiles::tile_id::tile_id;
+use pmtiles::{Header, MmapBackend, PmtResult};
+use tokio::io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Tiles up to this zoom level are preloaded into memory, as most clients request them.
+const PRELOAD_MAX_ZOOM: u8 = 3;
+
+struct Server {
+    reader: AsyncPmTilesReader<MmapBackend, LruDirCache>,
+    preloaded: HashMap<u64, Bytes>,
+}
+
+#[tokio::main]
+async fn main() -> PmtResult<()> {
+    let mut args = std::env::args().skip(1);
+    let Some(path) = args.next() else {
+        eprintln!("usage: tileserver <archive> [addr]");
+        std::process::exit(2);
+    };
+    let addr = args.next().unwrap_or_else(|| "127.0.0.1:8080".to_string());
+
+    let reader = PmTilesReaderBuilder::new()
+        .backend(MmapBackend::try_from(&path).await?)
+        .cache(LruDirCache::new(64 * 1024 * 1024))
+        .build()
+        .await?;
+    // Read all leaf directories concurrently, so that no request waits for them
+    reader.prefetch_directories().await?;
+
+    // Fetch the low zoom tiles at once, adjacent tiles are read together
+    let header = reader.get_header();
+    let max_zoom = header.max_zoom().min(PRELOAD_MAX_ZOOM);
+    let ids: Vec<u64> = (0..tile_id(max_zoom + 1, 0, 0)).collect();
+    let mut preloaded = HashMap::new();
+    for (id, tile) in ids.iter().zip(reader.get_tiles(&ids).await) {
+        if let Some(tile) = tile? {
+            preloaded.insert(*id, tile);
+        }
+    }
+    println!(
+        "Serving {path} ({:?}, zoom {}..={}) at http://{addr}/{{z}}/{{x}}/{{y}}, {} tiles preloaded",
+        header.tile_type(),
+        header.min_zoom(),
+        header.max_zoom(),
+        preloaded.len()
+    );
+
+    let server = Arc::new(Server { reader, preloaded });
+    let listener = TcpListener::bind(&addr).await?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let server = Arc::clone(&server);
+        tokio::spawn(async move {
+            if let Err(e) = server.handle(stream).await {
+                eprintln!("{e}");
+            }
+        });
+    }
+}
+
+impl Server {
+    async fn handle(&self, mut stream: TcpStream) -> PmtResult<()> {
+        let mut request_line = String::new();
+        let mut reader = BufReader::new(&mut stream);
+        reader.read_line(&mut request_line).await?;
+        // Skip the request headers
+        let mut line = String::new();
+        while reader.read_line(&mut line).await? > 2 {
+            line.clear();
+        }
+
+        let ["GET", path, _] = request_line.split_whitespace().collect::<Vec<_>>()[..] else {
+            return respond(&mut stream, "405 Method Not Allowed", &[], &[]).await;
+        };
+        let Some((z, x, y)) = parse_tile_path(path) else {
+            return respond(&mut stream, "404 Not Found", &[], &[]).await;
+        };
+
+        let id = tile_id(z, x, y);
+        let tile = match self.preloaded.get(&id) {
+            Some(tile) => Some(tile.clone()),
+            None => self.reader.get_tile(z, x, y).await?,
+        };
+        match tile {
+            Some(tile) => {
+                let headers = tile_headers(self.reader.get_header());
+                respond(&mut stream, "200 OK", &headers, &tile).await
+            }
+            None => respond(&mut stream, "204 No Content", &[], &[]).await,
+        }
+    }
+}
+
+/// Parses `/{z}/{x}/{y}`, optionally followed by a file extension.
+fn parse_tile_path(path: &str) -> Option<(u8, u64, u64)> {
+    let mut parts = path.strip_prefix('/')?.split('/');
+    let z = parts.next()?.parse().ok()?;
+    let x = parts.next()?.parse().ok()?;
+    let y = parts.next()?;
+    let y = y.split_once('.').map_or(y, |(y, _)| y).parse().ok()?;
+    if parts.next().is_some() || z > 31 || x >> z != 0 || y >> z != 0 {
+        return None;
+    }
+    Some((z, x, y))
+}
+
+fn tile_headers(header: &Header) -> Vec<(&'static str, &'static str)> {
+    let mut headers = vec![("Content-Type", header.tile_type().content_type())];
+    if let Some(encoding) = header.tile_compression().content_encoding() {
+        headers.push(("Content-Encoding", encoding));
+    }
+    headers
+}
+
+async fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> PmtResult<()> {
+    let mut head = format!(
+        "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        body.len()
+    );
+    for (name, value) in headers {
+        // Writing to a String cannot fail
+        let _ = write!(head, "{name}: {value}\r\n");
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    Ok(())
+}
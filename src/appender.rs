@@ -0,0 +1,380 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::num::NonZeroU64;
+use std::path::Path;
+
+use bytes::Bytes;
+
+use crate::directory::{entries_clustered, DirEntry, Directory};
+use crate::error::{PmtError, PmtResult};
+use crate::header::{HEADER_SIZE, MAX_INITIAL_BYTES};
+use crate::tile_id::tile_coord;
+use crate::writer::{build_directories, compress, decompress, into_u32, tile_corner};
+use crate::{Header, WriteTo as _};
+
+/// Limit of nested leaf directories read from the existing archive.
+const MAX_LEAF_DEPTH: u8 = 4;
+
+/// Adds tiles to an existing `PMTiles` archive in place.
+///
+/// All directory entries of the archive are read into memory. New tile data is written after
+/// the existing tile data, and the directories and header are rewritten by [`Self::finalize`].
+/// This requires the tile data to be the last section of the archive apart from the leaf
+/// directories, as in archives written by [`crate::PmTilesWriter`] or the `pmtiles` CLI.
+///
+/// The archive is not readable from the first added tile until [`Self::finalize`] returns.
+///
+/// ```no_run
+/// # fn main() -> pmtiles::PmtResult<()> {
+/// let mut appender = pmtiles::PmTilesAppender::open("tiles.pmtiles")?;
+/// appender.add_tile(1_000, &[0x89, 0x50, 0x4E, 0x47])?;
+/// appender.finalize()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct PmTilesAppender {
+    file: File,
+    header: Header,
+    entries: Vec<DirEntry>,
+    /// Space available for the root directory at its offset
+    max_root_length: usize,
+    overwrite: bool,
+}
+
+impl PmTilesAppender {
+    /// Opens an archive for appending, reading its header and all directories.
+    ///
+    /// Fails with [`PmtError::UnsupportedAppendLayout`] if other sections than the
+    /// leaf directories follow the tile data.
+    pub fn open<P: AsRef<Path>>(path: P) -> PmtResult<Self> {
+        let mut file = File::options().read(true).write(true).open(path)?;
+        let mut header = vec![0; HEADER_SIZE];
+        file.read_exact(&mut header)?;
+        let header = Header::try_from_bytes(Bytes::from(header))?;
+        let max_root_length = root_space(&header)?;
+
+        let mut entries = Vec::new();
+        let (offset, length) = (header.root_offset, header.root_length);
+        read_entries(&mut file, &header, offset, length, 0, &mut entries)?;
+
+        Ok(Self {
+            file,
+            header,
+            entries,
+            max_root_length,
+            overwrite: false,
+        })
+    }
+
+    /// Replace tiles already present in the archive, instead of failing with
+    /// [`PmtError::DuplicateTile`]. Disabled by default.
+    ///
+    /// The data of replaced tiles stays in the archive, unreferenced.
+    #[must_use]
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Add a tile to the archive, compressed with the archive's tile compression.
+    ///
+    /// Tiles may be added in any order. Empty tiles are skipped. The zoom range and bounds
+    /// of the header are extended to include the tile, the center is kept.
+    pub fn add_tile(&mut self, tile_id: u64, data: &[u8]) -> PmtResult<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        // Index of the entry the tile is inserted before
+        let idx = self.entries.partition_point(|e| e.tile_id <= tile_id);
+        let existing = idx
+            .checked_sub(1)
+            .filter(|&i| tile_id - self.entries[i].tile_id < u64::from(self.entries[i].run_length));
+        if existing.is_some() && !self.overwrite {
+            return Err(PmtError::DuplicateTile(tile_id));
+        }
+
+        let data = compress(self.header.tile_compression, data)?;
+        let length = into_u32(data.len())?;
+        let offset = self.header.data_length;
+        self.file
+            .seek(SeekFrom::Start(self.header.data_offset + offset))?;
+        self.file.write_all(&data)?;
+        self.header.data_length += u64::from(length);
+
+        let entry = DirEntry {
+            tile_id,
+            offset,
+            length,
+            run_length: 1,
+        };
+        if let Some(i) = existing {
+            // Split the run of the replaced tile around it
+            let run = self.entries[i].clone();
+            // Lies within the run, so it is smaller than its u32 length
+            #[allow(clippy::cast_possible_truncation)]
+            let before = (tile_id - run.tile_id) as u32;
+            let after = run.run_length - before - 1;
+            let mut replacement = Vec::with_capacity(3);
+            if before > 0 {
+                replacement.push(DirEntry {
+                    run_length: before,
+                    ..run.clone()
+                });
+            }
+            replacement.push(entry);
+            if after > 0 {
+                replacement.push(DirEntry {
+                    tile_id: tile_id + 1,
+                    run_length: after,
+                    ..run
+                });
+            }
+            self.entries.splice(i..=i, replacement);
+        } else {
+            self.entries.insert(idx, entry);
+        }
+
+        self.extend_header(tile_id);
+        Ok(())
+    }
+
+    /// Extend the zoom range and bounds of the header to include a tile.
+    #[allow(clippy::cast_possible_truncation)]
+    fn extend_header(&mut self, tile_id: u64) {
+        let (z, x, y) = tile_coord(tile_id);
+        let (west, north) = tile_corner(z, x, y);
+        let (east, south) = tile_corner(z, x + 1, y + 1);
+        let h = &mut self.header;
+        h.min_zoom = h.min_zoom.min(z);
+        h.max_zoom = h.max_zoom.max(z);
+        h.min_longitude = h.min_longitude.min(west as f32);
+        h.min_latitude = h.min_latitude.min(south as f32);
+        h.max_longitude = h.max_longitude.max(east as f32);
+        h.max_latitude = h.max_latitude.max(north as f32);
+    }
+
+    /// Write the directories and the header, completing the archive.
+    pub fn finalize(mut self) -> PmtResult<()> {
+        // Merge consecutive tiles sharing the same data into runs
+        let mut entries: Vec<DirEntry> = Vec::with_capacity(self.entries.len());
+        for entry in self.entries {
+            match entries.last_mut() {
+                Some(last)
+                    if entry.tile_id == last.tile_id + u64::from(last.run_length)
+                        && (entry.offset, entry.length) == (last.offset, last.length) =>
+                {
+                    last.run_length += entry.run_length;
+                }
+                _ => entries.push(entry),
+            }
+        }
+
+        let (root_dir, leaf_dirs) = build_directories(
+            &entries,
+            self.header.internal_compression,
+            self.max_root_length,
+        )
+        .map_err(|e| match e {
+            PmtError::RootDirectoryTooLarge(_) => PmtError::UnsupportedAppendLayout,
+            e => e,
+        })?;
+
+        let h = &mut self.header;
+        let contents: HashSet<_> = entries.iter().map(|e| (e.offset, e.length)).collect();
+        let addressed = entries.iter().map(|e| u64::from(e.run_length)).sum();
+        h.n_addressed_tiles = NonZeroU64::new(addressed);
+        h.n_tile_entries = NonZeroU64::new(entries.len() as u64);
+        h.n_tile_contents = NonZeroU64::new(contents.len() as u64);
+        h.clustered = entries_clustered(&entries)?;
+
+        // Leaf directories are written after the tile data
+        h.leaf_offset = h.data_offset + h.data_length;
+        h.leaf_length = leaf_dirs.len() as u64;
+        h.root_length = root_dir.len() as u64;
+        self.file.seek(SeekFrom::Start(h.leaf_offset))?;
+        self.file.write_all(&leaf_dirs)?;
+        self.file.set_len(h.leaf_offset + h.leaf_length)?;
+
+        self.file.seek(SeekFrom::Start(0))?;
+        self.header.write_to(&mut self.file)?;
+        self.file.seek(SeekFrom::Start(self.header.root_offset))?;
+        self.file.write_all(&root_dir)?;
+        self.file.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Checks that tiles can be appended to the archive, returning the space available
+/// for the root directory, up to the next section or the end of the first 16 KiB.
+fn root_space(h: &Header) -> PmtResult<usize> {
+    let data_end = h.data_offset + h.data_length;
+    let before_data = |offset: u64, length: u64| offset + length <= h.data_offset;
+    if h.root_offset < HEADER_SIZE as u64
+        || !before_data(h.root_offset, h.root_length)
+        || !before_data(h.metadata_offset, h.metadata_length)
+        || (h.leaf_length > 0
+            && !before_data(h.leaf_offset, h.leaf_length)
+            && h.leaf_offset < data_end)
+    {
+        return Err(PmtError::UnsupportedAppendLayout);
+    }
+
+    let root_end = h.root_offset + h.root_length;
+    let next_section = [
+        (h.metadata_offset, h.metadata_length),
+        (h.leaf_offset, h.leaf_length),
+    ]
+    .into_iter()
+    .filter(|&(offset, length)| length > 0 && offset >= root_end)
+    .map(|(offset, _)| offset)
+    .fold(h.data_offset.min(MAX_INITIAL_BYTES as u64), u64::min);
+    if next_section < root_end {
+        return Err(PmtError::UnsupportedAppendLayout);
+    }
+    usize::try_from(next_section - h.root_offset).map_err(|_| PmtError::UnsupportedAppendLayout)
+}
+
+/// Reads the tile entries of a directory and its leaf directories, in tile ID order.
+fn read_entries(
+    file: &mut File,
+    header: &Header,
+    offset: u64,
+    length: u64,
+    depth: u8,
+    entries: &mut Vec<DirEntry>,
+) -> PmtResult<()> {
+    let mut data = vec![0; usize::try_from(length).map_err(|_| PmtError::InvalidEntry)?];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut data)?;
+    let directory =
+        Directory::try_from(decompress(header.internal_compression, Bytes::from(data))?)?;
+
+    for entry in directory.iter() {
+        if entry.is_leaf() {
+            if depth >= MAX_LEAF_DEPTH {
+                return Err(PmtError::LeafRecursionLimit);
+            }
+            let offset = header.leaf_offset + entry.offset;
+            read_entries(
+                file,
+                header,
+                offset,
+                entry.length.into(),
+                depth + 1,
+                entries,
+            )?;
+        } else {
+            entries.push(entry.clone());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[cfg(feature = "mmap-async-tokio")]
+mod tests {
+    use std::fs::File;
+    use std::num::NonZeroU64;
+    use std::path::Path;
+
+    use super::PmTilesAppender;
+    use crate::async_reader::AsyncPmTilesReader;
+    use crate::tests::get_temp_file_path;
+    use crate::tile_id::tile_coord;
+    use crate::{MmapBackend, PmTilesWriter, PmtError, TileType};
+
+    async fn read_tiles(path: &Path, ids: &[u64]) -> Vec<Option<Vec<u8>>> {
+        let backend = MmapBackend::try_from(path).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let mut result = Vec::new();
+        for &id in ids {
+            let (z, x, y) = tile_coord(id);
+            let tile = tiles.get_tile(z, x, y).await.unwrap();
+            result.push(tile.map(|t| t.to_vec()));
+        }
+        result
+    }
+
+    fn write_archive(path: &Path, tiles: &[(u64, &[u8])]) {
+        let file = File::create(path).unwrap();
+        let mut writer = PmTilesWriter::new(TileType::Png).create(file).unwrap();
+        for (id, data) in tiles {
+            writer.add_tile(*id, data).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[tokio::test]
+    async fn append_tiles() {
+        let path = get_temp_file_path("appender.pmtiles");
+        write_archive(&path, &[(0, b"a"), (1, b"b"), (2, b"b")]);
+
+        let mut appender = PmTilesAppender::open(&path).unwrap();
+        assert!(matches!(
+            appender.add_tile(2, b"c"),
+            Err(PmtError::DuplicateTile(2))
+        ));
+        appender.add_tile(30, b"d").unwrap();
+        appender.add_tile(3, b"b").unwrap();
+        appender.finalize().unwrap();
+
+        let tiles = read_tiles(&path, &[0, 1, 2, 3, 4, 30]).await;
+        let expected: [Option<&[u8]>; 6] = [
+            Some(b"a"),
+            Some(b"b"),
+            Some(b"b"),
+            Some(b"b"),
+            None,
+            Some(b"d"),
+        ];
+        assert_eq!(tiles, expected.map(|t| t.map(<[u8]>::to_vec)));
+
+        let backend = MmapBackend::try_from(&path).await.unwrap();
+        let reader = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let header = reader.get_header();
+        assert_eq!(header.n_addressed_tiles, NonZeroU64::new(5));
+        assert_eq!(header.n_tile_contents, NonZeroU64::new(4));
+        // Tile 30 is at zoom 3
+        assert_eq!((header.min_zoom, header.max_zoom), (0, 3));
+        // The data of tile 3 follows the data of tile 30
+        assert!(!header.clustered);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn overwrite_tiles() {
+        let path = get_temp_file_path("appender-overwrite.pmtiles");
+        write_archive(&path, &[(0, b"a"), (1, b"b"), (2, b"b"), (3, b"b")]);
+
+        let mut appender = PmTilesAppender::open(&path).unwrap().overwrite(true);
+        appender.add_tile(2, b"c").unwrap();
+        appender.add_tile(0, b"d").unwrap();
+        appender.finalize().unwrap();
+
+        let tiles = read_tiles(&path, &[0, 1, 2, 3]).await;
+        let expected: [&[u8]; 4] = [b"d", b"b", b"c", b"b"];
+        assert_eq!(tiles, expected.map(|t| Some(t.to_vec())));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn append_to_leaf_directories() {
+        let path = get_temp_file_path("appender-leaves.pmtiles");
+        std::fs::copy("fixtures/leaf.pmtiles", &path).unwrap();
+        let id = crate::tile_id::tile_id(20, 0, 0);
+
+        let mut appender = PmTilesAppender::open(&path).unwrap();
+        appender.add_tile(id, b"new").unwrap();
+        appender.finalize().unwrap();
+
+        let tiles = read_tiles(&path, &[crate::tile_id::tile_id(1, 1, 0), id]).await;
+        assert_eq!(tiles, [Some(b"4".to_vec()), Some(b"new".to_vec())]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -2,9 +2,15 @@
 //        so any file larger than 4GB, or an untrusted file with bad data may crash.
 #![allow(clippy::cast_possible_truncation)]
 
+use std::collections::{BTreeMap, HashMap};
 use std::future::Future;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
 
 use bytes::Bytes;
+use futures_util::stream::{self, FuturesUnordered, Stream, StreamExt as _, TryStreamExt as _};
 #[cfg(feature = "__async")]
 use tokio::io::AsyncReadExt;
 
@@ -14,15 +20,368 @@ use crate::cache::{DirectoryCache, NoCache};
 use crate::directory::{DirEntry, Directory};
 use crate::error::{PmtError, PmtResult};
 use crate::header::{HEADER_SIZE, MAX_INITIAL_BYTES};
-use crate::tile::tile_id;
+use crate::tile_id::{lon_lat_to_tile, tile_coord, tile_id, tile_ids_for_zoom};
+use crate::validation::{
+    is_usual_tile_compression, Section, ValidationIssue, ValidationReport, ValidationWarning,
+};
+#[cfg(feature = "write")]
+use crate::PmTilesWriter;
 use crate::PmtError::UnsupportedCompression;
-use crate::{Compression, Header};
+#[cfg(feature = "metadata")]
+use crate::TileType;
+use crate::{ArchiveInfo, Compression, Header};
+
+/// Maximum number of leaf directories fetched at once by
+/// [`AsyncPmTilesReader::prefetch_directories`].
+pub const PREFETCH_CONCURRENCY: usize = 16;
+
+/// Default maximum number of tile reads in flight for [`AsyncPmTilesReader::get_tiles`],
+/// see [`PmTilesReaderBuilder::max_concurrency`].
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 16;
+
+/// Maximum number of bytes of adjacent tiles fetched with a single read by
+/// [`AsyncPmTilesReader::stream_all_tiles`] and [`AsyncPmTilesReader::get_tiles`].
+const MAX_COALESCED_READ: u64 = 1 << 20;
+
+/// Default limit of nested leaf directories followed by a reader, see
+/// [`AsyncPmTilesReader::with_max_leaf_depth`].
+pub const DEFAULT_MAX_LEAF_DEPTH: u8 = 4;
+
+/// Directory entry counts of an archive, as returned by [`AsyncPmTilesReader::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveStats {
+    /// Number of entries in the root directory, including leaf directory entries.
+    pub root_entries: usize,
+    /// Number of leaf directories, at any depth.
+    pub leaf_directories: usize,
+    /// Number of entries across all leaf directories.
+    pub leaf_entries: usize,
+}
+
+/// Tile IDs which differ between two archives, as returned by [`diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirDiff {
+    /// Tiles only present in the new archive, in tile ID order.
+    pub added: Vec<u64>,
+    /// Tiles only present in the old archive, in tile ID order.
+    pub removed: Vec<u64>,
+    /// Tiles present in both archives with different data, in tile ID order.
+    pub changed: Vec<u64>,
+}
+
+/// Counters of the directory lookups and backend reads of a reader since its creation,
+/// as returned by [`AsyncPmTilesReader::stats_snapshot`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReaderStats {
+    /// Number of leaf directory lookups answered by the cache.
+    pub directory_cache_hits: u64,
+    /// Number of leaf directory lookups which had to read the directory from the backend.
+    pub directory_cache_misses: u64,
+    /// Number of reads from the backend, including the header, directories, metadata and tiles.
+    pub backend_reads: u64,
+    /// Number of bytes returned by the backend.
+    pub bytes_read: u64,
+}
+
+#[derive(Default)]
+struct ReaderCounters {
+    directory_cache_hits: AtomicU64,
+    directory_cache_misses: AtomicU64,
+    backend_reads: AtomicU64,
+    bytes_read: AtomicU64,
+}
+
+impl ReaderCounters {
+    fn record_read(&self, data: &Bytes) {
+        self.backend_reads.fetch_add(1, Ordering::Relaxed);
+        self.bytes_read
+            .fetch_add(data.len() as u64, Ordering::Relaxed);
+    }
+
+    fn record_lookup(&self, result: &DirCacheResult) {
+        let counter = match result {
+            DirCacheResult::NotCached => &self.directory_cache_misses,
+            DirCacheResult::NotFound | DirCacheResult::Found(_) => &self.directory_cache_hits,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Decompression of data stored with a [`Compression`], registered with
+/// [`PmTilesReaderBuilder::decompressor`].
+///
+/// Implemented for closures taking and returning the data, e.g. to support a proprietary
+/// compression stored with [`Compression::Unknown`]:
+///
+/// ```no_run
+/// # async fn run() -> pmtiles::PmtResult<()> {
+/// use bytes::Bytes;
+/// use pmtiles::async_reader::PmTilesReaderBuilder;
+/// use pmtiles::{Compression, MemoryBackend};
+///
+/// let data = std::fs::read("tiles.pmtiles")?;
+/// let reader = PmTilesReaderBuilder::new()
+///     .backend(MemoryBackend::new(data.into()))
+///     .decompressor(Compression::Unknown, |data: Bytes| {
+///         Ok(data.iter().rev().copied().collect::<Vec<u8>>().into())
+///     })
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Decompressor: Send + Sync {
+    /// Decompresses `data`.
+    fn decompress(&self, data: Bytes) -> PmtResult<Bytes>;
+}
+
+impl<F: Fn(Bytes) -> PmtResult<Bytes> + Send + Sync> Decompressor for F {
+    fn decompress(&self, data: Bytes) -> PmtResult<Bytes> {
+        self(data)
+    }
+}
+
+/// Decompressors registered by compression, falling back to the built-in ones.
+#[derive(Default)]
+struct Decompressors(Vec<(Compression, Arc<dyn Decompressor>)>);
+
+impl Decompressors {
+    fn register(&mut self, compression: Compression, decompressor: Arc<dyn Decompressor>) {
+        self.0.retain(|(c, _)| *c != compression);
+        self.0.push((compression, decompressor));
+    }
+
+    async fn decompress(&self, compression: Compression, bytes: Bytes) -> PmtResult<Bytes> {
+        match self.0.iter().find(|(c, _)| *c == compression) {
+            Some((_, decompressor)) => decompressor.decompress(bytes),
+            None => decompress_builtin(compression, bytes).await,
+        }
+    }
+
+    async fn read_directory(&self, compression: Compression, bytes: Bytes) -> PmtResult<Directory> {
+        let decompressed_bytes = self.decompress(compression, bytes).await?;
+        Directory::try_from(decompressed_bytes)
+    }
+}
 
 pub struct AsyncPmTilesReader<B, C = NoCache> {
     backend: B,
     cache: C,
     header: Header,
     root_directory: Directory,
+    max_in_flight: usize,
+    max_leaf_depth: u8,
+    coalesce_gap: u64,
+    max_inflight_bytes: u64,
+    auto_detect_compression: bool,
+    /// Tile compression detected from the first decompressed tile
+    detected_compression: OnceLock<Compression>,
+    /// Whether unreadable leaf directories are treated as having no tiles
+    lenient_leaves: bool,
+    /// Offset of the archive in the backend, added to every read
+    base_offset: usize,
+    counters: ReaderCounters,
+    decompressors: Decompressors,
+}
+
+/// Configures and creates an [`AsyncPmTilesReader`].
+///
+/// ```no_run
+/// # async fn run() -> pmtiles::PmtResult<()> {
+/// use pmtiles::async_reader::PmTilesReaderBuilder;
+/// use pmtiles::cache::HashMapCache;
+/// use pmtiles::MemoryBackend;
+///
+/// let data = std::fs::read("tiles.pmtiles")?;
+/// let reader = PmTilesReaderBuilder::new()
+///     .backend(MemoryBackend::new(data.into()))
+///     .cache(HashMapCache::default())
+///     .max_concurrency(4)
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct PmTilesReaderBuilder<B = (), C = NoCache> {
+    backend: B,
+    cache: C,
+    max_concurrency: usize,
+    max_leaf_depth: u8,
+    coalesce_gap: u64,
+    max_inflight_bytes: u64,
+    auto_detect_compression: bool,
+    lenient_leaves: bool,
+    base_offset: u64,
+    decompressors: Decompressors,
+}
+
+impl PmTilesReaderBuilder {
+    /// Creates a builder without a backend, which must be set with [`Self::backend`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            backend: (),
+            cache: NoCache,
+            max_concurrency: DEFAULT_MAX_IN_FLIGHT,
+            max_leaf_depth: DEFAULT_MAX_LEAF_DEPTH,
+            coalesce_gap: 0,
+            max_inflight_bytes: u64::MAX,
+            auto_detect_compression: false,
+            lenient_leaves: false,
+            base_offset: 0,
+            decompressors: Decompressors::default(),
+        }
+    }
+}
+
+impl Default for PmTilesReaderBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B, C> PmTilesReaderBuilder<B, C> {
+    /// Sets the backend the archive is read from.
+    pub fn backend<B2: AsyncBackend + Sync + Send>(
+        self,
+        backend: B2,
+    ) -> PmTilesReaderBuilder<B2, C> {
+        PmTilesReaderBuilder {
+            backend,
+            cache: self.cache,
+            max_concurrency: self.max_concurrency,
+            max_leaf_depth: self.max_leaf_depth,
+            coalesce_gap: self.coalesce_gap,
+            max_inflight_bytes: self.max_inflight_bytes,
+            auto_detect_compression: self.auto_detect_compression,
+            lenient_leaves: self.lenient_leaves,
+            base_offset: self.base_offset,
+            decompressors: self.decompressors,
+        }
+    }
+
+    /// Sets the leaf directory cache. Defaults to [`NoCache`].
+    pub fn cache<C2: DirectoryCache + Sync + Send>(self, cache: C2) -> PmTilesReaderBuilder<B, C2> {
+        PmTilesReaderBuilder {
+            backend: self.backend,
+            cache,
+            max_concurrency: self.max_concurrency,
+            max_leaf_depth: self.max_leaf_depth,
+            coalesce_gap: self.coalesce_gap,
+            max_inflight_bytes: self.max_inflight_bytes,
+            auto_detect_compression: self.auto_detect_compression,
+            lenient_leaves: self.lenient_leaves,
+            base_offset: self.base_offset,
+            decompressors: self.decompressors,
+        }
+    }
+
+    /// Sets the maximum number of concurrent tile reads of [`AsyncPmTilesReader::get_tiles`].
+    /// Defaults to [`DEFAULT_MAX_IN_FLIGHT`].
+    #[must_use]
+    pub fn max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self
+    }
+
+    /// See [`AsyncPmTilesReader::with_max_leaf_depth`].
+    #[must_use]
+    pub fn max_leaf_depth(mut self, max_leaf_depth: u8) -> Self {
+        self.max_leaf_depth = max_leaf_depth;
+        self
+    }
+
+    /// Sets the largest gap in bytes between the data of two tiles which are still fetched with
+    /// a single read by [`AsyncPmTilesReader::stream_all_tiles`] and [`AsyncPmTilesReader::get_tiles`].
+    ///
+    /// Defaults to 0, i.e. only tiles stored back to back are read at once. Larger gaps reduce
+    /// the number of requests, e.g. for object stores, at the cost of reading unused bytes.
+    #[must_use]
+    pub fn coalesce_gap(mut self, gap: usize) -> Self {
+        self.coalesce_gap = gap as u64;
+        self
+    }
+
+    /// Limits the sum of the lengths of the tile reads in flight in
+    /// [`AsyncPmTilesReader::get_tiles`] to `max_bytes`, in addition to [`Self::max_concurrency`].
+    /// Reads of adjacent tiles are also limited to this size, including those of
+    /// [`AsyncPmTilesReader::stream_all_tiles`].
+    ///
+    /// The lengths are taken from the directory entries before fetching. A single tile larger
+    /// than the budget is still read, but only if nothing else is in flight.
+    /// Unlimited by default.
+    #[must_use]
+    pub fn max_inflight_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_inflight_bytes = (max_bytes as u64).max(1);
+        self
+    }
+
+    /// Detect the tile compression from the tile data if the header declares
+    /// [`Compression::Unknown`]. Disabled by default.
+    ///
+    /// Gzip and zstd are recognized by their magic bytes. Brotli has none, so other data is
+    /// treated as brotli if it decodes as such, and as uncompressed otherwise. The compression
+    /// detected for the first tile is used for all further tiles.
+    #[must_use]
+    pub fn auto_detect_compression(mut self, auto_detect: bool) -> Self {
+        self.auto_detect_compression = auto_detect;
+        self
+    }
+
+    /// Treat tiles in leaf directories which cannot be read or decoded as missing, instead of
+    /// failing the lookup. Disabled by default.
+    ///
+    /// With a corrupt or partially downloaded archive, a tile server can then respond with a missing
+    /// tile instead of an error. The leaf directory is read again on the next lookup.
+    #[must_use]
+    pub fn lenient_leaves(mut self, lenient: bool) -> Self {
+        self.lenient_leaves = lenient;
+        self
+    }
+
+    /// Reads an archive embedded in a larger file, starting `offset` bytes into the backend.
+    ///
+    /// All offsets of the archive are relative to its start, so the offset is added to every
+    /// read of the backend. Defaults to 0.
+    ///
+    /// [`Self::build`] fails with [`PmtError::InvalidBaseOffset`] if the offset is not
+    /// addressable on the target platform.
+    #[must_use]
+    pub fn with_base_offset(mut self, offset: u64) -> Self {
+        self.base_offset = offset;
+        self
+    }
+
+    /// Decompress directories, metadata and tiles stored with `compression` using
+    /// `decompressor`, replacing the built-in decompression or a decompressor registered before.
+    #[must_use]
+    pub fn decompressor<D: Decompressor + 'static>(
+        mut self,
+        compression: Compression,
+        decompressor: D,
+    ) -> Self {
+        self.decompressors
+            .register(compression, Arc::new(decompressor));
+        self
+    }
+}
+
+impl<B: AsyncBackend + Sync + Send, C: DirectoryCache + Sync + Send> PmTilesReaderBuilder<B, C> {
+    /// Creates the reader, reading the header and root directory from the backend.
+    pub async fn build(self) -> PmtResult<AsyncPmTilesReader<B, C>> {
+        let base_offset = usize::try_from(self.base_offset)
+            .map_err(|_| PmtError::InvalidBaseOffset(self.base_offset))?;
+        let mut reader =
+            AsyncPmTilesReader::open(self.backend, self.cache, self.decompressors, base_offset)
+                .await?;
+        reader.max_in_flight = self.max_concurrency;
+        reader.max_leaf_depth = self.max_leaf_depth;
+        reader.coalesce_gap = self.coalesce_gap;
+        reader.max_inflight_bytes = self.max_inflight_bytes;
+        reader.auto_detect_compression = self.auto_detect_compression;
+        reader.lenient_leaves = self.lenient_leaves;
+        Ok(reader)
+    }
 }
 
 impl<B: AsyncBackend + Sync + Send> AsyncPmTilesReader<B, NoCache> {
@@ -39,269 +398,2706 @@ impl<B: AsyncBackend + Sync + Send, C: DirectoryCache + Sync + Send> AsyncPmTile
     ///
     /// Note: Prefer using `new_with_*` methods.
     pub async fn try_from_cached_source(backend: B, cache: C) -> PmtResult<Self> {
-        // Read the first 127 and up to 16,384 bytes to ensure we can initialize the header and root directory.
-        let mut initial_bytes = backend.read(0, MAX_INITIAL_BYTES).await?;
+        Self::open(backend, cache, Decompressors::default(), 0).await
+    }
+
+    async fn open(
+        backend: B,
+        cache: C,
+        decompressors: Decompressors,
+        base_offset: usize,
+    ) -> PmtResult<Self> {
+        // Read the first 127 and up to 16,384 bytes, which usually include both the header and root directory.
+        let counters = ReaderCounters::default();
+        let initial_read_size = backend.initial_read_size().max(HEADER_SIZE);
+        let initial_bytes = backend.read(base_offset, initial_read_size).await?;
+        counters.record_read(&initial_bytes);
         if initial_bytes.len() < HEADER_SIZE {
             return Err(PmtError::InvalidHeader);
         }
 
-        let header = Header::try_from_bytes(initial_bytes.split_to(HEADER_SIZE))?;
+        let header = Header::try_from_bytes(initial_bytes.slice(..HEADER_SIZE))?;
+        if let Some(size) = backend.size().await? {
+            header.check_archive_size(size.saturating_sub(base_offset as u64))?;
+        }
 
-        let directory_bytes = initial_bytes
-            .split_off((header.root_offset as usize) - HEADER_SIZE)
-            .split_to(header.root_length as _);
+        let root_start = header.root_offset as usize;
+        let root_end = root_start + header.root_length as usize;
+        let directory_bytes = if root_start >= HEADER_SIZE && root_end <= initial_bytes.len() {
+            initial_bytes.slice(root_start..root_end)
+        } else {
+            // The root directory is not part of the initial window, fetch it separately.
+            let data = backend
+                .read_exact(
+                    backend_offset(base_offset, root_start)?,
+                    header.root_length as _,
+                )
+                .await?;
+            counters.record_read(&data);
+            data
+        };
 
-        let root_directory =
-            Self::read_compressed_directory(header.internal_compression, directory_bytes).await?;
+        let root_directory = decompressors
+            .read_directory(header.internal_compression, directory_bytes)
+            .await?;
 
         Ok(Self {
             backend,
             cache,
             header,
             root_directory,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            max_leaf_depth: DEFAULT_MAX_LEAF_DEPTH,
+            coalesce_gap: 0,
+            max_inflight_bytes: u64::MAX,
+            auto_detect_compression: false,
+            detected_compression: OnceLock::new(),
+            lenient_leaves: false,
+            base_offset,
+            counters,
+            decompressors,
         })
     }
 
     /// Fetches tile bytes from the archive.
+    ///
+    /// Zoom levels above 31 have no tiles.
     pub async fn get_tile(&self, z: u8, x: u64, y: u64) -> PmtResult<Option<Bytes>> {
-        let tile_id = tile_id(z, x, y);
-        let Some(entry) = self.find_tile_entry(tile_id).await? else {
+        if z > 31 {
             return Ok(None);
-        };
-
-        let offset = (self.header.data_offset + entry.offset) as _;
-        let length = entry.length as _;
-
-        Ok(Some(self.backend.read_exact(offset, length).await?))
+        }
+        self.read_tile(tile_id(z, x, y)).await
     }
 
-    /// Access header information.
-    pub fn get_header(&self) -> &Header {
-        &self.header
+    /// Fetches the stored tile bytes by tile ID without decompressing them,
+    /// together with the tile compression declared in the header, or the detected one
+    /// if enabled with [`PmTilesReaderBuilder::auto_detect_compression`].
+    ///
+    /// This is useful to pass tiles on as is, e.g. serving gzip-compressed vector tiles
+    /// with a matching `Content-Encoding`.
+    pub async fn get_tile_compressed(
+        &self,
+        tile_id: u64,
+    ) -> PmtResult<Option<(Bytes, Compression)>> {
+        let Some(data) = self.read_tile(tile_id).await? else {
+            return Ok(None);
+        };
+        let compression = self.tile_compression_of(&data).await;
+        Ok(Some((data, compression)))
     }
 
-    /// Gets metadata from the archive.
+    /// Fetches tile bytes by tile ID, together with the directory entry they are stored in.
     ///
-    /// Note: by spec, this should be valid JSON. This method currently returns a [String].
-    /// This may change in the future.
-    pub async fn get_metadata(&self) -> PmtResult<String> {
-        let offset = self.header.metadata_offset as _;
-        let length = self.header.metadata_length as _;
-        let metadata = self.backend.read_exact(offset, length).await?;
-
-        let decompressed_metadata =
-            Self::decompress(self.header.internal_compression, metadata).await?;
-
-        Ok(String::from_utf8(decompressed_metadata.to_vec())?)
+    /// A run-length entry serves all IDs from [`DirEntry::tile_id`] for [`DirEntry::run_length`]
+    /// tiles with the same bytes, so they can e.g. be cached once under the first ID.
+    pub async fn get_tile_with_entry(&self, tile_id: u64) -> PmtResult<Option<(Bytes, DirEntry)>> {
+        let Some(entry) = self.find_tile_entry(tile_id).await? else {
+            return Ok(None);
+        };
+        let data = self.read_entry_data(&entry).await?;
+        Ok(Some((data, entry)))
     }
 
-    #[cfg(feature = "tilejson")]
-    pub async fn parse_tilejson(&self, sources: Vec<String>) -> PmtResult<tilejson::TileJSON> {
-        use serde_json::Value;
+    /// Checks if a tile is present in the archive, without fetching its data.
+    ///
+    /// Only the directories on the way to the tile are read, i.e. leaf directories not yet cached.
+    pub async fn has_tile(&self, tile_id: u64) -> PmtResult<bool> {
+        Ok(self.find_tile_entry(tile_id).await?.is_some())
+    }
 
-        let meta = self.get_metadata().await?;
-        let meta: Value = serde_json::from_str(&meta).map_err(|_| PmtError::InvalidMetadata)?;
-        let Value::Object(meta) = meta else {
-            return Err(PmtError::InvalidMetadata);
+    /// Fetches a tile from the archive and decompresses it according to the header's tile compression.
+    ///
+    /// Returns `Ok(None)` if the tile is not present in the archive.
+    pub async fn get_tile_zxy(&self, z: u8, x: u64, y: u64) -> PmtResult<Option<Bytes>> {
+        let Some(data) = self.get_tile(z, x, y).await? else {
+            return Ok(None);
         };
 
-        let mut tj = self.header.get_tilejson(sources);
-        for (key, value) in meta {
-            if let Value::String(v) = value {
-                if key == "description" {
-                    tj.description = Some(v);
-                } else if key == "attribution" {
-                    tj.attribution = Some(v);
-                } else if key == "legend" {
-                    tj.legend = Some(v);
-                } else if key == "name" {
-                    tj.name = Some(v);
-                } else if key == "version" {
-                    tj.version = Some(v);
-                } else if key == "minzoom" || key == "maxzoom" {
-                    // We already have the correct values from the header, so just drop these
-                    // attributes from the metadata silently, don't overwrite known-good values.
-                } else {
-                    tj.other.insert(key, Value::String(v));
-                }
-            } else if key == "vector_layers" {
-                if let Ok(v) = serde_json::from_value::<Vec<tilejson::VectorLayer>>(value) {
-                    tj.vector_layers = Some(v);
-                } else {
-                    return Err(PmtError::InvalidMetadata);
-                }
-            } else {
-                tj.other.insert(key, value);
-            }
+        let compression = self.tile_compression_of(&data).await;
+        Ok(Some(
+            self.decompressors.decompress(compression, data).await?,
+        ))
+    }
+
+    /// The compression of tile data, detected from the first tile if the header does not
+    /// declare it and [`PmTilesReaderBuilder::auto_detect_compression`] is enabled.
+    async fn tile_compression_of(&self, data: &Bytes) -> Compression {
+        let compression = self.header.tile_compression;
+        if compression != Compression::Unknown || !self.auto_detect_compression {
+            return compression;
         }
-        Ok(tj)
+        if let Some(detected) = self.detected_compression.get() {
+            return *detected;
+        }
+        let detected = Self::detect_compression(data).await;
+        *self.detected_compression.get_or_init(|| detected)
     }
 
-    /// Recursively locates a tile in the archive.
-    async fn find_tile_entry(&self, tile_id: u64) -> PmtResult<Option<DirEntry>> {
-        let entry = self.root_directory.find_tile_id(tile_id);
-        if let Some(entry) = entry {
-            if entry.is_leaf() {
-                return self.find_entry_rec(tile_id, entry, 0).await;
-            }
+    #[cfg_attr(not(feature = "brotli"), allow(clippy::unused_async))]
+    async fn detect_compression(data: &Bytes) -> Compression {
+        if data.starts_with(&[0x1f, 0x8b]) {
+            return Compression::Gzip;
         }
+        if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            return Compression::Zstd;
+        }
+        #[cfg(feature = "brotli")]
+        if decompress_builtin(Compression::Brotli, data.clone())
+            .await
+            .is_ok()
+        {
+            return Compression::Brotli;
+        }
+        Compression::None
+    }
 
-        Ok(entry.cloned())
+    /// Fetches multiple tiles by tile ID, returning the results in the order of `ids`.
+    ///
+    /// All directory lookups are resolved first, reading each required leaf directory once,
+    /// then up to [`DEFAULT_MAX_IN_FLIGHT`] reads (or as configured with
+    /// [`PmTilesReaderBuilder::max_concurrency`]) are issued concurrently. Tiles stored
+    /// close to each other are fetched with a single read, see [`PmTilesReaderBuilder::coalesce_gap`].
+    /// Tiles are returned as stored, see [`Self::get_tile`].
+    pub async fn get_tiles(&self, ids: &[u64]) -> Vec<PmtResult<Option<Bytes>>> {
+        self.get_tiles_with_max_in_flight(ids, self.max_in_flight)
+            .await
     }
 
-    async fn find_entry_rec(
+    /// Same as [`Self::get_tiles`], with up to `max_in_flight` concurrent tile reads.
+    pub async fn get_tiles_with_max_in_flight(
         &self,
-        tile_id: u64,
-        entry: &DirEntry,
-        depth: u8,
-    ) -> PmtResult<Option<DirEntry>> {
-        // the recursion is done as two functions because it is a bit cleaner,
-        // and it allows directory to be cached later without cloning it first.
-        let offset = (self.header.leaf_offset + entry.offset) as _;
+        ids: &[u64],
+        max_in_flight: usize,
+    ) -> Vec<PmtResult<Option<Bytes>>> {
+        let max_in_flight = max_in_flight.max(1);
+        let mut sorted_ids = ids.to_vec();
+        sorted_ids.sort_unstable();
+        sorted_ids.dedup();
 
-        let entry = match self.cache.get_dir_entry(offset, tile_id).await {
-            DirCacheResult::NotCached => {
-                // Cache miss - read from backend
-                let length = entry.length as _;
-                let dir = self.read_directory(offset, length).await?;
-                let entry = dir.find_tile_id(tile_id).cloned();
-                self.cache.insert_dir(offset, dir).await;
-                entry
-            }
-            DirCacheResult::NotFound => None,
-            DirCacheResult::Found(entry) => Some(entry),
+        let Ok(entries) = self.find_tile_entries(&sorted_ids).await else {
+            // Resolve the tiles one by one, so that every tile gets its own error
+            return stream::iter(ids)
+                .map(|&id| self.read_tile(id))
+                .buffered(max_in_flight)
+                .collect()
+                .await;
         };
+        let mut by_offset: Vec<DirEntry> = entries.iter().map(|(_, e)| e.clone()).collect();
+        by_offset.sort_by_key(|e| (e.offset, e.length));
+        by_offset.dedup();
+        let groups = self.coalesce(by_offset);
 
-        if let Some(ref entry) = entry {
-            if entry.is_leaf() {
-                return if depth <= 4 {
-                    Box::pin(self.find_entry_rec(tile_id, entry, depth + 1)).await
-                } else {
-                    Ok(None)
-                };
+        // Data of every fetched entry, by offset and length
+        let mut data: HashMap<(u64, u32), Bytes> = HashMap::new();
+        let mut pending = groups.iter().peekable();
+        let mut reads = FuturesUnordered::new();
+        let mut inflight_bytes = 0;
+        loop {
+            // Start reads while both the concurrency and the byte budget allow it
+            while let Some(group) = pending.next_if(|g| {
+                reads.len() < max_in_flight
+                    && (reads.is_empty() || inflight_bytes + g.len() <= self.max_inflight_bytes)
+            }) {
+                inflight_bytes += group.len();
+                reads.push(async move { (group, self.read_group_data(group).await) });
+            }
+            let Some((group, group_data)) = reads.next().await else {
+                break;
+            };
+            inflight_bytes -= group.len();
+            // Tiles of failed reads are fetched again below, reporting their own errors
+            let Ok(group_data) = group_data else { continue };
+            for e in &group.entries {
+                let start = (e.offset - group.start) as usize;
+                let tile = group_data.slice(start..start + e.length as usize);
+                data.insert((e.offset, e.length), tile);
             }
         }
 
-        Ok(entry)
+        let entries: HashMap<u64, DirEntry> = entries.into_iter().collect();
+        stream::iter(ids)
+            .map(|id| {
+                let entry = entries.get(id);
+                let tile = entry.and_then(|e| data.get(&(e.offset, e.length)));
+                async move {
+                    match (entry, tile) {
+                        (_, Some(tile)) => Ok(Some(tile.clone())),
+                        (Some(entry), None) if entry.length > 0 => {
+                            self.read_entry_data(entry).await.map(Some)
+                        }
+                        (Some(_), None) => Ok(Some(Bytes::new())),
+                        (None, _) => Ok(None),
+                    }
+                }
+            })
+            .buffered(max_in_flight)
+            .collect()
+            .await
     }
 
-    async fn read_directory(&self, offset: usize, length: usize) -> PmtResult<Directory> {
-        let data = self.backend.read_exact(offset, length).await?;
-        Self::read_compressed_directory(self.header.internal_compression, data).await
+    /// Fetches all tiles of zoom level `z` covering a WGS84 bounding box.
+    ///
+    /// Tiles are yielded in tile ID order as `(tile_id, bytes)` pairs, missing tiles are skipped.
+    /// All leaf directories covering the box are read first, concurrently and only once each,
+    /// then the tiles are fetched as with [`Self::get_tiles`].
+    pub fn tiles_in_bbox(
+        &self,
+        z: u8,
+        min_lon: f64,
+        min_lat: f64,
+        max_lon: f64,
+        max_lat: f64,
+    ) -> impl Stream<Item = PmtResult<(u64, Bytes)>> + '_ {
+        // Tile rows grow from north to south
+        let (min_x, min_y) = lon_lat_to_tile(z, min_lon, max_lat);
+        let (max_x, max_y) = lon_lat_to_tile(z, max_lon, min_lat);
+        // Zoom levels above 31 have no tiles
+        let mut tile_ids: Vec<u64> = (min_x..=max_x)
+            .filter(|_| z <= 31)
+            .flat_map(|x| (min_y..=max_y).map(move |y| tile_id(z, x, y)))
+            .collect();
+        tile_ids.sort_unstable();
+
+        stream::once(async move {
+            let tiles = self.get_tiles(&tile_ids).await;
+            let found = tile_ids
+                .into_iter()
+                .zip(tiles)
+                .filter_map(|(tile_id, tile)| {
+                    tile.transpose()
+                        .map(|tile| tile.map(|tile| (tile_id, tile)))
+                });
+            stream::iter(found)
+        })
+        .flatten()
     }
 
-    async fn read_compressed_directory(
-        compression: Compression,
-        bytes: Bytes,
-    ) -> PmtResult<Directory> {
-        let decompressed_bytes = Self::decompress(compression, bytes).await?;
-        Directory::try_from(decompressed_bytes)
+    /// Streams all tiles of the archive as decompressed `(tile_id, bytes)` pairs, in tile ID order.
+    ///
+    /// Tiles stored next to each other are fetched with a single backend read of up to 1 MiB,
+    /// see [`PmTilesReaderBuilder::coalesce_gap`].
+    /// Every tile ID covered by a run-length encoded entry is yielded, sharing the same bytes.
+    /// Empty tiles are skipped.
+    ///
+    /// Note: this reads all leaf directories of the archive before yielding the first tile.
+    pub fn stream_all_tiles(&self) -> impl Stream<Item = PmtResult<(u64, Bytes)>> + '_ {
+        stream::once(async move { self.collect_tile_entries().await })
+            .map_ok(|entries| stream::iter(self.coalesce(entries)).map(Ok))
+            .try_flatten()
+            .and_then(move |group| async move { self.read_entry_group(group).await })
+            .map_ok(|tiles| {
+                stream::iter(tiles.into_iter().flat_map(|(entry, data)| {
+                    (entry.tile_id..entry.tile_id + u64::from(entry.run_length))
+                        .map(move |tile_id| Ok((tile_id, data.clone())))
+                }))
+            })
+            .try_flatten()
     }
 
-    async fn decompress(compression: Compression, bytes: Bytes) -> PmtResult<Bytes> {
-        let mut decompressed_bytes = Vec::with_capacity(bytes.len() * 2);
-        match compression {
-            Compression::Gzip => {
-                async_compression::tokio::bufread::GzipDecoder::new(&bytes[..])
-                    .read_to_end(&mut decompressed_bytes)
-                    .await?;
-            }
-            v => Err(UnsupportedCompression(v))?,
+    /// Reads the data of a group of entries with a single backend read,
+    /// returning the decompressed data of each entry.
+    async fn read_entry_group(&self, group: EntryGroup) -> PmtResult<Vec<(DirEntry, Bytes)>> {
+        let data = self.read_group_data(&group).await?;
+
+        let mut tiles = Vec::with_capacity(group.entries.len());
+        for entry in group.entries {
+            let start = (entry.offset - group.start) as usize;
+            let tile = data.slice(start..start + entry.length as usize);
+            let compression = self.tile_compression_of(&tile).await;
+            let tile = self.decompressors.decompress(compression, tile).await?;
+            tiles.push((entry, tile));
         }
+        Ok(tiles)
+    }
 
-        Ok(Bytes::from(decompressed_bytes))
+    fn coalesce(&self, entries: Vec<DirEntry>) -> Vec<EntryGroup> {
+        let max_read = MAX_COALESCED_READ.min(self.max_inflight_bytes);
+        coalesce_entries(entries, self.coalesce_gap, max_read)
     }
-}
 
-pub trait AsyncBackend {
-    /// Reads exactly `length` bytes starting at `offset`
-    fn read_exact(
-        &self,
-        offset: usize,
-        length: usize,
-    ) -> impl Future<Output = PmtResult<Bytes>> + Send
-    where
-        Self: Sync,
-    {
-        async move {
-            let data = self.read(offset, length).await?;
+    async fn read_group_data(&self, group: &EntryGroup) -> PmtResult<Bytes> {
+        let offset = (self.header.data_offset + group.start) as _;
+        let length = (group.end - group.start) as _;
+        self.read_backend(offset, length).await
+    }
 
-            if data.len() == length {
-                Ok(data)
-            } else {
-                Err(PmtError::UnexpectedNumberOfBytesReturned(
-                    length,
-                    data.len(),
-                ))
+    /// Writes all tiles of the archive to `root/{z}/{x}/{y}.{ext}`, returning the number of written tiles.
+    ///
+    /// The extension is derived from the header's tile type. Tiles are written decompressed,
+    /// empty tiles are skipped.
+    ///
+    /// Note: this reads all leaf directories of the archive. Not available on WebAssembly.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn export_to_dir(&self, root: &Path) -> PmtResult<usize> {
+        let extension = self.header.tile_type.extension();
+        let mut count = 0;
+
+        for entry in self.collect_tile_entries().await? {
+            if entry.length == 0 {
+                continue;
+            }
+            let data = self.read_entry_data(&entry).await?;
+            let compression = self.tile_compression_of(&data).await;
+            let data = self.decompressors.decompress(compression, data).await?;
+
+            for tile_id in entry.tile_id..entry.tile_id + u64::from(entry.run_length) {
+                let (z, x, y) = tile_coord(tile_id);
+                let dir = root.join(z.to_string()).join(x.to_string());
+                tokio::fs::create_dir_all(&dir).await?;
+                tokio::fs::write(dir.join(format!("{y}.{extension}")), &data).await?;
+                count += 1;
             }
         }
+
+        Ok(count)
     }
 
-    /// Reads up to `length` bytes starting at `offset`.
-    fn read(&self, offset: usize, length: usize) -> impl Future<Output = PmtResult<Bytes>> + Send;
-}
+    /// Copies the tiles of zoom levels `min_zoom..=max_zoom` into a new archive written to `out`.
+    ///
+    /// Tile data is copied without recompressing it, identical tiles are stored only once, and
+    /// the output is clustered. The header keeps the source's compressions, while the zoom range,
+    /// bounds and center are computed from the copied tiles.
+    ///
+    /// Returns [`PmtError::InvalidZoomRange`] if the range is inverted or does not overlap the
+    /// zoom range of the archive.
+    ///
+    /// Note: this reads all leaf directories of the archive.
+    #[cfg(feature = "write")]
+    pub async fn extract_zoom_range<W: std::io::Write + std::io::Seek>(
+        &self,
+        min_zoom: u8,
+        max_zoom: u8,
+        out: W,
+    ) -> PmtResult<()> {
+        if min_zoom > max_zoom || min_zoom > self.header.max_zoom || max_zoom < self.header.min_zoom
+        {
+            return Err(PmtError::InvalidZoomRange(min_zoom, max_zoom));
+        }
+        let min_zoom = min_zoom.max(self.header.min_zoom);
+        let max_zoom = max_zoom.min(self.header.max_zoom);
+        let first_id = tile_id(min_zoom, 0, 0);
+        let end_id = if max_zoom < 31 {
+            tile_id(max_zoom + 1, 0, 0)
+        } else {
+            u64::MAX
+        };
 
-#[cfg(test)]
-#[cfg(feature = "mmap-async-tokio")]
-mod tests {
-    use super::AsyncPmTilesReader;
-    use crate::tests::{RASTER_FILE, VECTOR_FILE};
-    use crate::MmapBackend;
+        let mut writer = PmTilesWriter::new(self.header.tile_type)
+            .with_internal_compression(self.header.internal_compression)
+            .with_tile_compression(self.header.tile_compression)
+            .with_raw_metadata(&self.get_metadata().await?)
+            .dedup(true)
+            .create(out)?;
 
-    #[tokio::test]
-    async fn open_sanity_check() {
-        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
-        AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        for entry in self.collect_tile_entries().await? {
+            let start = entry.tile_id.max(first_id);
+            let end = entry
+                .tile_id
+                .checked_add(u64::from(entry.run_length))
+                .ok_or(PmtError::InvalidEntry)?
+                .min(end_id);
+            if start >= end {
+                continue;
+            }
+            let data = self.read_entry_data(&entry).await?;
+            let compression = self.tile_compression_of(&data).await;
+            for tile_id in start..end {
+                writer.add_tile_precompressed(tile_id, &data, compression)?;
+            }
+        }
+
+        writer.finalize()
     }
 
-    async fn compare_tiles(z: u8, x: u64, y: u64, fixture_bytes: &[u8]) {
-        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
-        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
-        let tile = tiles.get_tile(z, x, y).await.unwrap().unwrap();
+    /// Sets how deep leaf directories may be nested before lookups fail with
+    /// [`PmtError::LeafRecursionLimit`]. Defaults to [`DEFAULT_MAX_LEAF_DEPTH`].
+    ///
+    /// This protects against corrupt archives, e.g. a leaf directory referencing itself.
+    #[must_use]
+    pub fn with_max_leaf_depth(mut self, max_leaf_depth: u8) -> Self {
+        self.max_leaf_depth = max_leaf_depth;
+        self
+    }
 
+    /// Access header information.
+    pub fn get_header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Access the root directory, which is read and decompressed once when opening the archive.
+    ///
+    /// Only leaf directories are read on demand and go through the directory cache.
+    pub fn root_directory(&self) -> &Directory {
+        &self.root_directory
+    }
+
+    /// Summarizes the archive, e.g. for a `pmtiles info` command.
+    pub fn info(&self) -> ArchiveInfo {
+        ArchiveInfo::from(&self.header)
+    }
+
+    /// Access the backend the archive is read from.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Returns the directory cache and backend read counters accumulated since the reader
+    /// was created. The counters are updated with relaxed atomics, so a snapshot taken while
+    /// other requests are running may not be consistent across fields.
+    pub fn stats_snapshot(&self) -> ReaderStats {
+        let c = &self.counters;
+        ReaderStats {
+            directory_cache_hits: c.directory_cache_hits.load(Ordering::Relaxed),
+            directory_cache_misses: c.directory_cache_misses.load(Ordering::Relaxed),
+            backend_reads: c.backend_reads.load(Ordering::Relaxed),
+            bytes_read: c.bytes_read.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Reads `length` raw bytes at `offset` from the start of the archive.
+    ///
+    /// Fails with [`PmtError::UnexpectedNumberOfBytesReturned`] if the archive ends before.
+    pub async fn read_range(&self, offset: u64, length: u64) -> PmtResult<Bytes> {
+        self.read_backend(offset as _, length as _).await
+    }
+
+    /// Computes a 64-bit identifier of the archive from the header and the stored root
+    /// directory, e.g. for cache keys. Only the root directory is read from the backend.
+    ///
+    /// The value is stable across versions of this crate, and changes whenever the directories
+    /// change. It is based on a fast non-cryptographic hash, so different archives may collide
+    /// and it must not be relied upon to detect tampering.
+    pub async fn archive_hash(&self) -> PmtResult<u64> {
+        let root = self
+            .read_range(self.header.root_offset, self.header.root_length)
+            .await?;
+        let hash = fnv1a(FNV_OFFSET_BASIS, &self.header.to_bytes());
+        Ok(fnv1a(hash, &root))
+    }
+
+    /// Checks whether the tile data of the whole archive is clustered, i.e. stored in tile ID
+    /// order without gaps. See [`Directory::is_clustered`] for details.
+    ///
+    /// Note: this reads all leaf directories of the archive.
+    pub async fn is_clustered(&self) -> PmtResult<bool> {
+        let entries = self.collect_tile_entries().await?;
+        Ok(Directory::from_entries(entries).is_clustered())
+    }
+
+    /// Returns the smallest and the largest tile ID present in the archive.
+    ///
+    /// Only the leaf directories on the way to the first and the last tile entry are read.
+    /// Fails with [`PmtError::EmptyArchive`] if the archive has no tiles.
+    pub async fn tile_id_bounds(&self) -> PmtResult<(u64, u64)> {
+        let first = self.edge_entry(false).await?;
+        let last = self.edge_entry(true).await?;
+        match (first, last) {
+            (Some(first), Some(last)) => Ok((first.tile_id, last.next_tile_id() - 1)),
+            _ => Err(PmtError::EmptyArchive),
+        }
+    }
+
+    /// Counts the tiles of every zoom level present in the archive.
+    ///
+    /// Every tile ID covered by a run-length encoded entry is counted, also if the run
+    /// continues into the next zoom level. E.g. a gap in the keys reveals missing zoom levels.
+    ///
+    /// Note: this reads all leaf directories of the archive.
+    pub async fn tile_count_by_zoom(&self) -> PmtResult<BTreeMap<u8, u64>> {
+        let mut counts = BTreeMap::new();
+        for entry in self.collect_tile_entries().await? {
+            let mut tile_id = entry.tile_id;
+            let mut remaining = u64::from(entry.run_length);
+            while remaining > 0 {
+                let (z, _, _) = tile_coord(tile_id);
+                // IDs past zoom level 31 are invalid, count them as part of it
+                let count = remaining.min(
+                    tile_ids_for_zoom(z)
+                        .end
+                        .checked_sub(tile_id)
+                        .filter(|&n| n > 0)
+                        .unwrap_or(remaining),
+                );
+                *counts.entry(z).or_insert(0) += count;
+                tile_id = tile_id.saturating_add(count);
+                remaining -= count;
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Finds the first or the last tile entry of the archive, descending into leaf directories.
+    async fn edge_entry(&self, last: bool) -> PmtResult<Option<DirEntry>> {
+        let edge = |dir: &Directory| {
+            if last {
+                dir.iter().last().cloned()
+            } else {
+                dir.iter().next().cloned()
+            }
+        };
+        let mut entry = edge(&self.root_directory);
+        let mut depth = 0;
+        while let Some(leaf) = entry.as_ref().filter(|e| e.is_leaf()) {
+            depth += 1;
+            if depth > self.max_leaf_depth {
+                return Err(PmtError::LeafRecursionLimit);
+            }
+            let offset = (self.header.leaf_offset + leaf.offset) as _;
+            let dir = self.read_directory(offset, leaf.length as _).await?;
+            entry = edge(&dir);
+        }
+        Ok(entry)
+    }
+
+    /// Reads all leaf directories of the archive and inserts them into the cache.
+    ///
+    /// Up to [`PREFETCH_CONCURRENCY`] directories are fetched at once. With a cache that keeps
+    /// all directories, later tile lookups only read tile data from the backend.
+    pub async fn prefetch_directories(&self) -> PmtResult<()> {
+        let mut leaves = Self::leaf_entries(&self.root_directory);
+
+        let mut depth = 0;
+        while !leaves.is_empty() {
+            depth += 1;
+            if depth > self.max_leaf_depth {
+                return Err(PmtError::LeafRecursionLimit);
+            }
+
+            let dirs = self.read_leaf_directories(leaves).await?;
+            leaves = Vec::new();
+            for (offset, dir) in dirs {
+                leaves.extend(Self::leaf_entries(&dir));
+                self.cache.insert_dir(offset, dir).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Counts the entries of the root directory and of all leaf directories.
+    ///
+    /// Note: this reads all leaf directories of the archive, bypassing the cache.
+    pub async fn stats(&self) -> PmtResult<ArchiveStats> {
+        let mut stats = ArchiveStats {
+            root_entries: self.root_directory.iter().count(),
+            leaf_directories: 0,
+            leaf_entries: 0,
+        };
+        let mut leaves = Self::leaf_entries(&self.root_directory);
+
+        let mut depth = 0;
+        while !leaves.is_empty() {
+            depth += 1;
+            if depth > self.max_leaf_depth {
+                return Err(PmtError::LeafRecursionLimit);
+            }
+
+            let dirs = self.read_leaf_directories(leaves).await?;
+            leaves = Vec::new();
+            for (_, dir) in dirs {
+                stats.leaf_directories += 1;
+                stats.leaf_entries += dir.iter().count();
+                leaves.extend(Self::leaf_entries(&dir));
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Checks the structure of the whole archive, e.g. before serving a file from an untrusted source.
+    ///
+    /// The header fields are already verified when the reader is created. This checks
+    /// that all sections lie inside the file, that tile IDs of every directory are strictly
+    /// increasing, and that leaf directories and tiles lie inside their sections.
+    /// Unlike other methods, this does not stop at the first problem: all problems are listed
+    /// in the returned [`ValidationReport`].
+    ///
+    /// It also warns about a tile compression that is unusual for the tile type, and with the
+    /// `metadata` feature, about a tile type that does not match the `format` of the metadata.
+    ///
+    /// Note: this reads all leaf directories of the archive.
+    pub async fn validate(&self) -> PmtResult<ValidationReport> {
+        Ok(self.validate_structure().await?.0)
+    }
+
+    /// Same as [`Self::validate`], and then reads the data of every tile to check that it is
+    /// available, with up to [`PmTilesReaderBuilder::max_concurrency`] concurrent reads.
+    /// If `check_decompress` is set, the tiles must also decompress with the tile compression.
+    ///
+    /// Tiles that fail are listed in the returned [`ValidationReport`] with their tile ID.
+    /// This catches corrupted tile data, which the structure checks cannot, but reads the whole archive.
+    pub async fn deep_validate(&self, check_decompress: bool) -> PmtResult<ValidationReport> {
+        let (mut report, entries) = self.validate_structure().await?;
+        let issues: Vec<_> = stream::iter(entries)
+            .map(|entry| self.validate_tile(entry, check_decompress))
+            .buffered(self.max_in_flight)
+            .filter_map(|issue| async { issue })
+            .collect()
+            .await;
+        report.issues.extend(issues);
+        Ok(report)
+    }
+
+    async fn validate_tile(
+        &self,
+        entry: DirEntry,
+        check_decompress: bool,
+    ) -> Option<ValidationIssue> {
+        let data = match self.read_entry_data(&entry).await {
+            Ok(data) => data,
+            Err(e) => {
+                return Some(ValidationIssue::UnreadableTile {
+                    tile_id: entry.tile_id,
+                    reason: e.to_string(),
+                })
+            }
+        };
+        if !check_decompress {
+            return None;
+        }
+        let compression = self.tile_compression_of(&data).await;
+        if compression == Compression::Unknown {
+            return None;
+        }
+        let result = self.decompressors.decompress(compression, data).await;
+        result
+            .err()
+            .map(|e| ValidationIssue::TileDecompressionFailed {
+                tile_id: entry.tile_id,
+                reason: e.to_string(),
+            })
+    }
+
+    /// Checks the structure of the archive, returning the tile entries which are inside the tile data section.
+    async fn validate_structure(&self) -> PmtResult<(ValidationReport, Vec<DirEntry>)> {
+        let mut report = ValidationReport::default();
+        let header = &self.header;
+
+        for (section, offset, length) in [
+            (
+                Section::RootDirectory,
+                header.root_offset,
+                header.root_length,
+            ),
+            (
+                Section::Metadata,
+                header.metadata_offset,
+                header.metadata_length,
+            ),
+            (
+                Section::LeafDirectories,
+                header.leaf_offset,
+                header.leaf_length,
+            ),
+            (Section::TileData, header.data_offset, header.data_length),
+        ] {
+            // Probe the last byte of the section, not all backends expose the file size
+            let last_byte = offset
+                .checked_add(length)
+                .and_then(|end| end.checked_sub(1))
+                .and_then(|last| usize::try_from(last).ok())
+                .and_then(|last| backend_offset(self.base_offset, last).ok());
+            let readable = match last_byte {
+                Some(last) => self.backend.read(last, 1).await.is_ok_and(|b| b.len() == 1),
+                None => false,
+            };
+            if length > 0 && !readable {
+                report.push(ValidationIssue::SectionOutOfBounds(section));
+            }
+        }
+
+        if !is_usual_tile_compression(header.tile_type, header.tile_compression) {
+            report
+                .warnings
+                .push(ValidationWarning::UnusualTileCompression {
+                    tile_type: header.tile_type,
+                    compression: header.tile_compression,
+                });
+        }
+        // Metadata which cannot be parsed has no format to compare with
+        #[cfg(feature = "metadata")]
+        if let Some(format) = self.get_metadata_typed().await.ok().and_then(|m| m.format) {
+            if TileType::from_format(&format).is_some_and(|t| t != header.tile_type) {
+                report.warnings.push(ValidationWarning::TileTypeMismatch {
+                    tile_type: header.tile_type,
+                    format,
+                });
+            }
+        }
+
+        report.n_directories = 1;
+        let mut previous = None;
+        let mut entries = Vec::new();
+        self.validate_directory(
+            &self.root_directory,
+            &mut report,
+            &mut entries,
+            &mut previous,
+            0,
+        )
+        .await;
+
+        Ok((report, entries))
+    }
+
+    async fn validate_directory(
+        &self,
+        dir: &Directory,
+        report: &mut ValidationReport,
+        entries: &mut Vec<DirEntry>,
+        previous: &mut Option<u64>,
+        depth: u8,
+    ) {
+        let mut previous_in_dir = None;
+        for entry in dir.iter() {
+            // The first entry is compared with the last tile of the preceding directory
+            if let Some(prev) = previous_in_dir
+                .or(*previous)
+                .filter(|&p| p >= entry.tile_id)
+            {
+                report.push(ValidationIssue::TileIdNotIncreasing {
+                    tile_id: entry.tile_id,
+                    previous: prev,
+                });
+            }
+            previous_in_dir = Some(entry.tile_id);
+
+            if !entry.is_leaf() {
+                report.n_tile_entries += 1;
+                *previous = Some(entry.tile_id);
+
+                if entry
+                    .offset
+                    .checked_add(u64::from(entry.length))
+                    .map_or(true, |end| end > self.header.data_length)
+                {
+                    report.push(ValidationIssue::TileOutOfBounds {
+                        tile_id: entry.tile_id,
+                        offset: entry.offset,
+                        length: entry.length,
+                    });
+                } else {
+                    entries.push(entry.clone());
+                }
+                continue;
+            }
+
+            if entry
+                .offset
+                .checked_add(u64::from(entry.length))
+                .map_or(true, |end| end > self.header.leaf_length)
+            {
+                report.push(ValidationIssue::LeafOutOfBounds {
+                    offset: entry.offset,
+                    length: entry.length,
+                });
+                continue;
+            }
+            if depth >= self.max_leaf_depth {
+                report.push(ValidationIssue::DirectoryTooDeep(entry.offset));
+                continue;
+            }
+
+            let offset = self.header.leaf_offset + entry.offset;
+            match self.read_directory(offset as _, entry.length as _).await {
+                Ok(leaf) => {
+                    report.n_directories += 1;
+                    Box::pin(self.validate_directory(&leaf, report, entries, previous, depth + 1))
+                        .await;
+                }
+                Err(e) => report.push(ValidationIssue::UnreadableDirectory {
+                    offset,
+                    reason: e.to_string(),
+                }),
+            }
+        }
+    }
+
+    /// Gets metadata from the archive.
+    ///
+    /// Note: by spec, this should be valid JSON. This method currently returns a [String].
+    /// This may change in the future.
+    pub async fn get_metadata(&self) -> PmtResult<String> {
+        let metadata = self.get_metadata_raw().await?;
+        let decompressed_metadata = self
+            .decompressors
+            .decompress(self.header.internal_compression, metadata)
+            .await?;
+
+        Ok(String::from_utf8(decompressed_metadata.to_vec())?)
+    }
+
+    /// Gets the metadata as stored in the archive, without decompressing or parsing it.
+    ///
+    /// Useful to inspect metadata that [`Self::get_metadata`] rejects.
+    pub async fn get_metadata_raw(&self) -> PmtResult<Bytes> {
+        let offset = self.header.metadata_offset as _;
+        let length = self.header.metadata_length as _;
+        self.read_backend(offset, length).await
+    }
+
+    /// Gets metadata from the archive, parsed into the standard `TileJSON` fields.
+    ///
+    /// Use [`Self::get_metadata`] to access metadata not following the `TileJSON` structure.
+    #[cfg(feature = "metadata")]
+    pub async fn get_metadata_typed(&self) -> PmtResult<crate::Metadata> {
+        let meta = self.get_metadata().await?;
+        serde_json::from_str(&meta).map_err(|_| PmtError::InvalidMetadata)
+    }
+
+    #[cfg(feature = "tilejson")]
+    pub async fn parse_tilejson(&self, sources: Vec<String>) -> PmtResult<tilejson::TileJSON> {
+        use serde_json::Value;
+
+        let meta = self.get_metadata().await?;
+        let meta: Value = serde_json::from_str(&meta).map_err(|_| PmtError::InvalidMetadata)?;
+        let Value::Object(meta) = meta else {
+            return Err(PmtError::InvalidMetadata);
+        };
+
+        let mut tj = self.header.get_tilejson(sources);
+        for (key, value) in meta {
+            if let Value::String(v) = value {
+                if key == "description" {
+                    tj.description = Some(v);
+                } else if key == "attribution" {
+                    tj.attribution = Some(v);
+                } else if key == "legend" {
+                    tj.legend = Some(v);
+                } else if key == "name" {
+                    tj.name = Some(v);
+                } else if key == "version" {
+                    tj.version = Some(v);
+                } else if key == "minzoom" || key == "maxzoom" {
+                    // We already have the correct values from the header, so just drop these
+                    // attributes from the metadata silently, don't overwrite known-good values.
+                } else {
+                    tj.other.insert(key, Value::String(v));
+                }
+            } else if key == "vector_layers" {
+                if let Ok(v) = serde_json::from_value::<Vec<tilejson::VectorLayer>>(value) {
+                    tj.vector_layers = Some(v);
+                } else {
+                    return Err(PmtError::InvalidMetadata);
+                }
+            } else {
+                tj.other.insert(key, value);
+            }
+        }
+        Ok(tj)
+    }
+
+    /// Reads the stored bytes of a tile from the backend.
+    async fn read_tile(&self, tile_id: u64) -> PmtResult<Option<Bytes>> {
+        let Some(entry) = self.find_tile_entry(tile_id).await? else {
+            return Ok(None);
+        };
+
+        Ok(Some(self.read_entry_data(&entry).await?))
+    }
+
+    /// Reads the tile data referenced by a directory entry.
+    async fn read_entry_data(&self, entry: &DirEntry) -> PmtResult<Bytes> {
+        let offset = (self.header.data_offset + entry.offset) as _;
+        let length = entry.length as _;
+
+        self.read_backend(offset, length).await
+    }
+
+    /// Locates multiple tiles in the archive, reading each leaf directory at most once.
+    ///
+    /// `tile_ids` must be sorted. Returns the entries of all tiles present in the archive.
+    async fn find_tile_entries(&self, tile_ids: &[u64]) -> PmtResult<Vec<(u64, DirEntry)>> {
+        let mut found = self
+            .find_tile_entries_rec(&self.root_directory, tile_ids, 0)
+            .await?;
+        found.sort_unstable_by_key(|(tile_id, _)| *tile_id);
+        Ok(found)
+    }
+
+    /// Locates the tiles in `dir`, which is nested `depth` levels below the root.
+    ///
+    /// The leaf directories covering the tiles are determined first, and then all read concurrently.
+    async fn find_tile_entries_rec(
+        &self,
+        dir: &Directory,
+        tile_ids: &[u64],
+        depth: u8,
+    ) -> PmtResult<Vec<(u64, DirEntry)>> {
+        let mut found = Vec::new();
+        let mut leaves = Vec::new();
+        let mut remaining = tile_ids;
+        while let Some(&tile_id) = remaining.first() {
+            let Some(entry) = dir.find_tile_id(tile_id) else {
+                remaining = &remaining[1..];
+                continue;
+            };
+            if !entry.is_leaf() {
+                found.push((tile_id, entry.clone()));
+                remaining = &remaining[1..];
+                continue;
+            }
+
+            // All following tiles located in the same leaf directory
+            let group_len = remaining
+                .iter()
+                .take_while(|&&id| dir.find_tile_id(id).is_some_and(|e| std::ptr::eq(e, entry)))
+                .count();
+            let (group, rest) = remaining.split_at(group_len);
+            remaining = rest;
+            leaves.push((entry, group));
+        }
+
+        let mut leaf_entries = stream::iter(leaves)
+            .map(|(entry, group)| self.find_leaf_entries(entry, group, depth))
+            .buffered(self.max_in_flight);
+        while let Some(entries) = leaf_entries.next().await {
+            found.extend(entries?);
+        }
+        Ok(found)
+    }
+
+    /// Locates the tiles in the leaf directory of `entry`, which is listed in a directory
+    /// nested `depth` levels below the root.
+    async fn find_leaf_entries(
+        &self,
+        entry: &DirEntry,
+        tile_ids: &[u64],
+        depth: u8,
+    ) -> PmtResult<Vec<(u64, DirEntry)>> {
+        let offset = (self.header.leaf_offset + entry.offset) as _;
+        // Only a miss is recorded here, hits are recorded by the lookup of each tile below
+        let probe = self.cache.get_dir_entry(offset, tile_ids[0]).await;
+        if let DirCacheResult::NotCached = probe {
+            self.counters.record_lookup(&probe);
+            if depth >= self.max_leaf_depth {
+                return Err(PmtError::LeafRecursionLimit);
+            }
+            let leaf = match self.read_directory(offset, entry.length as _).await {
+                Ok(leaf) => leaf,
+                Err(_) if self.lenient_leaves => return Ok(Vec::new()),
+                Err(e) => return Err(e),
+            };
+            let found = Box::pin(self.find_tile_entries_rec(&leaf, tile_ids, depth + 1)).await?;
+            self.cache.insert_dir(offset, leaf).await;
+            Ok(found)
+        } else {
+            let mut found = Vec::new();
+            for &id in tile_ids {
+                if let Some(entry) = self.find_entry_rec(id, entry, depth + 1).await? {
+                    found.push((id, entry));
+                }
+            }
+            Ok(found)
+        }
+    }
+
+    /// Recursively locates a tile in the archive.
+    async fn find_tile_entry(&self, tile_id: u64) -> PmtResult<Option<DirEntry>> {
+        let entry = self.root_directory.find_tile_id(tile_id);
+        if let Some(entry) = entry {
+            if entry.is_leaf() {
+                return self.find_entry_rec(tile_id, entry, 1).await;
+            }
+        }
+
+        Ok(entry.cloned())
+    }
+
+    /// Looks up a tile in the leaf directory of `entry`, which is nested `depth` levels below the root.
+    async fn find_entry_rec(
+        &self,
+        tile_id: u64,
+        entry: &DirEntry,
+        depth: u8,
+    ) -> PmtResult<Option<DirEntry>> {
+        if depth > self.max_leaf_depth {
+            return Err(PmtError::LeafRecursionLimit);
+        }
+
+        // the recursion is done as two functions because it is a bit cleaner,
+        // and it allows directory to be cached later without cloning it first.
+        let offset = (self.header.leaf_offset + entry.offset) as _;
+
+        let entry = match self.cached_dir_entry(offset, tile_id).await {
+            DirCacheResult::NotCached => {
+                // Cache miss - read from backend
+                let length = entry.length as _;
+                let dir = match self.read_directory(offset, length).await {
+                    Ok(dir) => dir,
+                    Err(_) if self.lenient_leaves => return Ok(None),
+                    Err(e) => return Err(e),
+                };
+                let entry = dir.find_tile_id(tile_id).cloned();
+                self.cache.insert_dir(offset, dir).await;
+                entry
+            }
+            DirCacheResult::NotFound => None,
+            DirCacheResult::Found(entry) => Some(entry),
+        };
+
+        if let Some(ref entry) = entry {
+            if entry.is_leaf() {
+                return Box::pin(self.find_entry_rec(tile_id, entry, depth + 1)).await;
+            }
+        }
+
+        Ok(entry)
+    }
+
+    /// Collects all tile entries of the archive in tile ID order, descending into leaf directories.
+    async fn collect_tile_entries(&self) -> PmtResult<Vec<DirEntry>> {
+        let mut entries = Vec::new();
+        self.collect_tile_entries_rec(&self.root_directory, &mut entries, 0)
+            .await?;
+        Ok(entries)
+    }
+
+    async fn collect_tile_entries_rec(
+        &self,
+        dir: &Directory,
+        entries: &mut Vec<DirEntry>,
+        depth: u8,
+    ) -> PmtResult<()> {
+        for entry in dir.iter() {
+            if entry.is_leaf() {
+                if depth >= self.max_leaf_depth {
+                    return Err(PmtError::LeafRecursionLimit);
+                }
+                let offset = (self.header.leaf_offset + entry.offset) as _;
+                let leaf = self.read_directory(offset, entry.length as _).await?;
+                Box::pin(self.collect_tile_entries_rec(&leaf, entries, depth + 1)).await?;
+            } else {
+                entries.push(entry.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn leaf_entries(dir: &Directory) -> Vec<DirEntry> {
+        dir.iter().filter(|e| e.is_leaf()).cloned().collect()
+    }
+
+    /// Reads the given leaf directories, up to [`PREFETCH_CONCURRENCY`] at once.
+    ///
+    /// Returns each directory with its offset in the file, in no particular order.
+    async fn read_leaf_directories(
+        &self,
+        leaves: Vec<DirEntry>,
+    ) -> PmtResult<Vec<(usize, Directory)>> {
+        stream::iter(leaves)
+            .map(|entry| async move {
+                let offset = (self.header.leaf_offset + entry.offset) as _;
+                let dir = self.read_directory(offset, entry.length as _).await?;
+                Ok::<_, PmtError>((offset, dir))
+            })
+            .buffer_unordered(PREFETCH_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
+    async fn read_backend(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        let data = self
+            .backend
+            .read_exact(backend_offset(self.base_offset, offset)?, length)
+            .await?;
+        self.counters.record_read(&data);
+        Ok(data)
+    }
+
+    /// Looks up a tile in a cached leaf directory, counting cache hits and misses.
+    async fn cached_dir_entry(&self, offset: usize, tile_id: u64) -> DirCacheResult {
+        let result = self.cache.get_dir_entry(offset, tile_id).await;
+        self.counters.record_lookup(&result);
+        result
+    }
+
+    async fn read_directory(&self, offset: usize, length: usize) -> PmtResult<Directory> {
+        let data = self.read_backend(offset, length).await?;
+        self.decompressors
+            .read_directory(self.header.internal_compression, data)
+            .await
+    }
+}
+
+/// The offset within the backend of `offset` within an archive starting at `base_offset`.
+fn backend_offset(base_offset: usize, offset: usize) -> PmtResult<usize> {
+    base_offset
+        .checked_add(offset)
+        .ok_or(PmtError::InvalidBaseOffset(base_offset as u64))
+}
+
+async fn decompress_builtin(compression: Compression, bytes: Bytes) -> PmtResult<Bytes> {
+    if compression == Compression::None {
+        return Ok(bytes);
+    }
+
+    let mut decompressed_bytes = Vec::with_capacity(bytes.len() * 2);
+    match compression {
+        Compression::Gzip => {
+            // Some writers store the data as several concatenated gzip members
+            let mut decoder = async_compression::tokio::bufread::GzipDecoder::new(&bytes[..]);
+            decoder.multiple_members(true);
+            decoder.read_to_end(&mut decompressed_bytes).await?;
+        }
+        #[cfg(feature = "brotli")]
+        Compression::Brotli => {
+            async_compression::tokio::bufread::BrotliDecoder::new(&bytes[..])
+                .read_to_end(&mut decompressed_bytes)
+                .await?;
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => {
+            async_compression::tokio::bufread::ZstdDecoder::new(&bytes[..])
+                .read_to_end(&mut decompressed_bytes)
+                .await?;
+        }
+        v => Err(UnsupportedCompression(v))?,
+    }
+
+    Ok(Bytes::from(decompressed_bytes))
+}
+
+/// Compares the tiles of two archives, e.g. to only publish the tiles which changed.
+///
+/// Tiles are considered changed if the length of their stored data differs. With
+/// `compare_data`, the data of tiles with the same length is fetched and compared as well,
+/// once per pair of directory entries. Each run of a run-length encoded entry is compared
+/// as a whole, but reported tile by tile.
+///
+/// Note: this reads all leaf directories of both archives.
+pub async fn diff<B1, C1, B2, C2>(
+    old: &AsyncPmTilesReader<B1, C1>,
+    new: &AsyncPmTilesReader<B2, C2>,
+    compare_data: bool,
+) -> PmtResult<DirDiff>
+where
+    B1: AsyncBackend + Sync + Send,
+    C1: DirectoryCache + Sync + Send,
+    B2: AsyncBackend + Sync + Send,
+    C2: DirectoryCache + Sync + Send,
+{
+    let old_entries = old.collect_tile_entries().await?;
+    let new_entries = new.collect_tile_entries().await?;
+    let run_end = |e: &DirEntry| e.tile_id + u64::from(e.run_length);
+
+    // Within the ranges between these tile IDs, both archives store the same entry
+    let mut boundaries: Vec<u64> = old_entries
+        .iter()
+        .chain(&new_entries)
+        .flat_map(|e| [e.tile_id, run_end(e)])
+        .collect();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut result = DirDiff::default();
+    let (mut old_idx, mut new_idx) = (0, 0);
+    // Whether the data of entries differs, by their offsets and length
+    let mut compared: HashMap<(u64, u64, u32), bool> = HashMap::new();
+    for range in boundaries.windows(2) {
+        let (start, end) = (range[0], range[1]);
+        let covering = |entries: &[DirEntry], idx: &mut usize| {
+            while entries.get(*idx).is_some_and(|e| run_end(e) <= start) {
+                *idx += 1;
+            }
+            entries.get(*idx).filter(|e| e.tile_id <= start).cloned()
+        };
+        let target = match (
+            covering(&old_entries, &mut old_idx),
+            covering(&new_entries, &mut new_idx),
+        ) {
+            (None, Some(_)) => &mut result.added,
+            (Some(_), None) => &mut result.removed,
+            (Some(o), Some(n)) if o.length != n.length => &mut result.changed,
+            (Some(o), Some(n)) if compare_data => {
+                let key = (o.offset, n.offset, o.length);
+                let differs = if let Some(&differs) = compared.get(&key) {
+                    differs
+                } else {
+                    let differs = old.read_entry_data(&o).await? != new.read_entry_data(&n).await?;
+                    compared.insert(key, differs);
+                    differs
+                };
+                if !differs {
+                    continue;
+                }
+                &mut result.changed
+            }
+            (None, None) | (Some(_), Some(_)) => continue,
+        };
+        target.extend(start..end);
+    }
+
+    Ok(result)
+}
+
+/// Tile entries whose data lies in one contiguous byte range of the tile data section.
+struct EntryGroup {
+    start: u64,
+    end: u64,
+    entries: Vec<DirEntry>,
+}
+
+impl EntryGroup {
+    fn len(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Continues a 64-bit FNV-1a hash over `data`.
+fn fnv1a(hash: u64, data: &[u8]) -> u64 {
+    data.iter().fold(hash, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Groups non-empty tile entries, in their original order, into contiguous byte ranges.
+///
+/// An entry joins the previous group if its data lies inside of the group's range,
+/// e.g. for deduplicated tiles, or starts at most `gap` bytes after its end,
+/// as long as the group does not exceed `max_read` bytes.
+fn coalesce_entries(entries: Vec<DirEntry>, gap: u64, max_read: u64) -> Vec<EntryGroup> {
+    let mut groups: Vec<EntryGroup> = Vec::new();
+    for entry in entries.into_iter().filter(|e| e.length > 0) {
+        let entry_end = entry.offset + u64::from(entry.length);
+        match groups.last_mut() {
+            Some(g) if entry.offset >= g.start && entry_end <= g.end => g.entries.push(entry),
+            Some(g)
+                if entry.offset >= g.start
+                    && entry.offset <= g.end.saturating_add(gap)
+                    && entry_end - g.start <= max_read =>
+            {
+                g.end = g.end.max(entry_end);
+                g.entries.push(entry);
+            }
+            _ => groups.push(EntryGroup {
+                start: entry.offset,
+                end: entry_end,
+                entries: vec![entry],
+            }),
+        }
+    }
+    groups
+}
+
+/// Random access to the bytes of an archive.
+///
+/// Reads extending past the end of the archive are normalized across backends:
+/// [`AsyncBackend::read`] returns only the available bytes, which may be none at all, and
+/// [`AsyncBackend::read_exact`] fails with [`PmtError::UnexpectedNumberOfBytesReturned`],
+/// holding the requested and the available number of bytes.
+pub trait AsyncBackend {
+    /// Reads exactly `length` bytes starting at `offset`
+    fn read_exact(
+        &self,
+        offset: usize,
+        length: usize,
+    ) -> impl Future<Output = PmtResult<Bytes>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let data = self.read(offset, length).await?;
+
+            if data.len() == length {
+                Ok(data)
+            } else {
+                Err(PmtError::UnexpectedNumberOfBytesReturned(
+                    length,
+                    data.len(),
+                ))
+            }
+        }
+    }
+
+    /// Reads up to `length` bytes starting at `offset`, fewer if the archive ends before.
+    fn read(&self, offset: usize, length: usize) -> impl Future<Output = PmtResult<Bytes>> + Send;
+
+    /// Total size of the archive in bytes, if the backend can tell it without a request.
+    ///
+    /// Readers check it against the header when opening the archive, failing early with
+    /// [`PmtError::TruncatedArchive`]. Defaults to `None`, skipping the check.
+    fn size(&self) -> impl Future<Output = PmtResult<Option<u64>>> + Send {
+        async { Ok(None) }
+    }
+
+    /// Number of bytes read at once when opening the archive, for the header and root directory.
+    ///
+    /// If the root directory does not fit, it is read with a second request. Values below the
+    /// header size are rounded up to it. Defaults to 16,384 bytes, the maximum size of the
+    /// header and root directory that the spec recommends.
+    fn initial_read_size(&self) -> usize {
+        MAX_INITIAL_BYTES
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mmap-async-tokio")]
+mod tests {
+    use std::io::Read;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use bytes::Bytes;
+    use futures_util::TryStreamExt as _;
+
+    use super::{AsyncBackend, AsyncPmTilesReader, PmTilesReaderBuilder};
+    use crate::cache::HashMapCache;
+    use crate::directory::{DirEntry, Directory};
+    use crate::error::{PmtError, PmtResult};
+    use crate::tests::{
+        archive_from_parts, get_temp_file_path, self_referential_leaf_archive, RASTER_FILE,
+        VECTOR_FILE,
+    };
+    use crate::tile_id::{tile_coord, tile_id};
+    use crate::{
+        Compression, MemoryBackend, MmapBackend, Section, TileType, ValidationIssue,
+        ValidationWarning,
+    };
+
+    #[tokio::test]
+    async fn open_sanity_check() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+    }
+
+    async fn compare_tiles(z: u8, x: u64, y: u64, fixture_bytes: &[u8]) {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let tile = tiles.get_tile(z, x, y).await.unwrap().unwrap();
+
+        assert_eq!(
+            tile.len(),
+            fixture_bytes.len(),
+            "Expected tile length to match."
+        );
+        assert_eq!(tile, fixture_bytes, "Expected tile to match fixture.");
+    }
+
+    #[tokio::test]
+    async fn get_first_tile() {
+        let fixture_tile = include_bytes!("../fixtures/0_0_0.png");
+        compare_tiles(0, 0, 0, fixture_tile).await;
+    }
+
+    #[tokio::test]
+    async fn get_another_tile() {
+        let fixture_tile = include_bytes!("../fixtures/2_2_2.png");
+        compare_tiles(2, 2, 2, fixture_tile).await;
+    }
+
+    #[tokio::test]
+    async fn get_yet_another_tile() {
+        let fixture_tile = include_bytes!("../fixtures/3_4_5.png");
+        compare_tiles(3, 4, 5, fixture_tile).await;
+    }
+
+    #[tokio::test]
+    async fn get_tile_zxy_raster() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let tile = tiles.get_tile_zxy(3, 4, 5).await.unwrap().unwrap();
+        assert_eq!(tile, &include_bytes!("../fixtures/3_4_5.png")[..]);
+
+        // Zoom 4 is not part of the archive
+        assert!(tiles.get_tile_zxy(4, 0, 0).await.unwrap().is_none());
+        assert!(tiles.get_tile_zxy(u8::MAX, 0, 0).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn get_tile_zxy_vector() {
+        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let compressed = tiles.get_tile(12, 2174, 1492).await.unwrap().unwrap();
+        let mut expected = Vec::new();
+        flate2::read::GzDecoder::new(&compressed[..])
+            .read_to_end(&mut expected)
+            .unwrap();
+
+        let tile = tiles.get_tile_zxy(12, 2174, 1492).await.unwrap().unwrap();
+        assert_eq!(tile, expected);
+    }
+
+    #[tokio::test]
+    async fn get_tile_compressed() {
+        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let (tile, compression) = tiles
+            .get_tile_compressed(tile_id(12, 2174, 1492))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(compression, Compression::Gzip);
+        assert_eq!(tile, tiles.get_tile(12, 2174, 1492).await.unwrap().unwrap());
+
+        assert!(tiles
+            .get_tile_compressed(tile_id(6, 31, 23))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn tiles_in_bbox() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let found: Vec<_> = tiles
+            .tiles_in_bbox(3, -180.0, -85.0, 180.0, 85.0)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 64);
+        assert!(found.windows(2).all(|w| w[0].0 < w[1].0));
+        let (_, tile) = found
+            .iter()
+            .find(|(id, _)| *id == tile_id(3, 4, 5))
+            .unwrap();
+        assert_eq!(tile, &include_bytes!("../fixtures/3_4_5.png")[..]);
+
+        let above_max_zoom: Vec<_> = tiles
+            .tiles_in_bbox(32, 11.2, 43.75, 11.3, 43.8)
+            .try_collect()
+            .await
+            .unwrap();
+        assert!(above_max_zoom.is_empty());
+    }
+
+    #[tokio::test]
+    async fn tiles_in_bbox_leaf() {
+        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        // Florence, located in leaf directories at zoom 12
+        let found: Vec<_> = tiles
+            .tiles_in_bbox(12, 11.2, 43.75, 11.3, 43.8)
+            .try_collect()
+            .await
+            .unwrap();
+        assert!(!found.is_empty());
+        for (id, tile) in found {
+            let (z, x, y) = tile_coord(id);
+            assert_eq!(tiles.get_tile(z, x, y).await.unwrap().unwrap(), tile);
+        }
+    }
+
+    /// Backend tracking the highest number of reads in progress at the same time.
+    struct ConcurrencyBackend<B> {
+        backend: B,
+        in_progress: AtomicUsize,
+        max_in_progress: AtomicUsize,
+    }
+
+    impl<B: AsyncBackend + Sync + Send> AsyncBackend for ConcurrencyBackend<B> {
+        async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+            let in_progress = self.in_progress.fetch_add(1, Ordering::Relaxed) + 1;
+            self.max_in_progress
+                .fetch_max(in_progress, Ordering::Relaxed);
+            // Give other reads the chance to start
+            tokio::task::yield_now().await;
+            let data = self.backend.read(offset, length).await;
+            self.in_progress.fetch_sub(1, Ordering::Relaxed);
+            data
+        }
+    }
+
+    #[tokio::test]
+    async fn tiles_in_bbox_concurrent_leaves() {
+        use crate::writer::WriteTo as _;
+
+        // Three leaf directories, each with a single tile
+        let ids = [0, 10, 20];
+        let mut leaves = Vec::new();
+        let mut root_entries = Vec::new();
+        for (offset, id) in (0..).zip(ids) {
+            let leaf = Directory::from_entries(vec![DirEntry::new(id, offset, 1, 1)]);
+            let mut leaf_bytes = Vec::new();
+            leaf.write_to(&mut leaf_bytes).unwrap();
+            let length = u32::try_from(leaf_bytes.len()).unwrap();
+            root_entries.push(DirEntry::new(id, leaves.len() as u64, length, 0));
+            leaves.extend_from_slice(&leaf_bytes);
+        }
+        let root = Directory::from_entries(root_entries);
+        let archive = archive_from_parts(&root, &leaves, b"abc");
+
+        let backend = ConcurrencyBackend {
+            backend: MemoryBackend::new(archive.into()),
+            in_progress: AtomicUsize::new(0),
+            max_in_progress: AtomicUsize::new(0),
+        };
+        let tiles = PmTilesReaderBuilder::new()
+            .backend(backend)
+            .cache(HashMapCache::default())
+            .build()
+            .await
+            .unwrap();
+
+        let reads = tiles.stats_snapshot().backend_reads;
+        let entries = tiles.find_tile_entries(&ids).await.unwrap();
+        assert_eq!(tiles.stats_snapshot().backend_reads - reads, 3);
+        assert_eq!(tiles.backend.max_in_progress.load(Ordering::Relaxed), 3);
+        let found: Vec<_> = entries.iter().map(|(id, e)| (*id, e.offset)).collect();
+        assert_eq!(found, [(0, 0), (10, 1), (20, 2)]);
+
+        // All leaf directories are cached now, only the tile is read
+        let reads = tiles.stats_snapshot().backend_reads;
+        let found: Vec<_> = tiles
+            .tiles_in_bbox(0, -180.0, -85.0, 180.0, 85.0)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(found, [(0, Bytes::from_static(b"a"))]);
+        assert_eq!(tiles.stats_snapshot().backend_reads - reads, 1);
+    }
+
+    #[tokio::test]
+    async fn test_auto_detect_compression() {
+        let mut data = std::fs::read(VECTOR_FILE).unwrap();
+        // Declare the tile compression as unknown
+        data[98] = 0;
+        let reader = |auto_detect| {
+            PmTilesReaderBuilder::new()
+                .backend(MemoryBackend::new(data.clone().into()))
+                .auto_detect_compression(auto_detect)
+                .build()
+        };
+
+        let tiles = reader(false).await.unwrap();
+        assert_eq!(tiles.get_header().tile_compression, Compression::Unknown);
+        assert!(matches!(
+            tiles.get_tile_zxy(12, 2174, 1492).await,
+            Err(PmtError::UnsupportedCompression(Compression::Unknown))
+        ));
+
+        let tiles = reader(true).await.unwrap();
+        let tile = tiles.get_tile_zxy(12, 2174, 1492).await.unwrap().unwrap();
+        let expected =
+            AsyncPmTilesReader::try_from_source(MmapBackend::try_from(VECTOR_FILE).await.unwrap())
+                .await
+                .unwrap()
+                .get_tile_zxy(12, 2174, 1492)
+                .await
+                .unwrap();
+        assert_eq!(Some(tile), expected);
+        let (_, compression) = tiles
+            .get_tile_compressed(tile_id(12, 2174, 1492))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(compression, Compression::Gzip);
+
+        // Streaming and exporting all tiles decompress them as well
+        let all: Vec<_> = tiles.stream_all_tiles().try_collect().await.unwrap();
+        let (id, tile) = all
+            .iter()
+            .find(|(id, _)| *id == tile_id(12, 2174, 1492))
+            .unwrap();
+        assert_eq!(Some(tile.clone()), expected);
+        let root = get_temp_file_path("auto-detect-export");
+        let _ = std::fs::remove_dir_all(&root);
+        assert_eq!(tiles.export_to_dir(&root).await.unwrap(), all.len());
+        let (z, x, y) = tile_coord(*id);
+        let exported = std::fs::read(root.join(format!("{z}/{x}/{y}.mvt"))).unwrap();
+        assert_eq!(Some(Bytes::from(exported)), expected);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_archive_hash() {
+        let hash = |data: Vec<u8>| async move {
+            let backend = MemoryBackend::new(data.into());
+            let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+            tiles.archive_hash().await.unwrap()
+        };
+        let data = std::fs::read(RASTER_FILE).unwrap();
+        let raster_hash = hash(data.clone()).await;
+        // The hash must not change between versions
+        assert_eq!(raster_hash, 11_521_253_193_426_488_658);
+        assert_ne!(raster_hash, hash(std::fs::read(VECTOR_FILE).unwrap()).await);
+
+        // Changing the tile data keeps the identity, changing the directories does not
+        let mut changed = data.clone();
+        *changed.last_mut().unwrap() ^= 1;
+        assert_eq!(hash(changed).await, raster_hash);
+        let mut changed = data;
+        changed[127 + 8] ^= 1;
+        assert_ne!(hash(changed).await, raster_hash);
+    }
+
+    #[tokio::test]
+    async fn test_custom_decompressor() {
+        let root = Directory::from_entries(vec![DirEntry {
+            tile_id: 0,
+            offset: 0,
+            length: 3,
+            run_length: 1,
+        }]);
+        let mut archive = archive_from_parts(&root, &[], b"cba");
+        // Declare the tile compression as unknown
+        archive[98] = 0;
+        let archive = Bytes::from(archive);
+
+        let tiles = AsyncPmTilesReader::try_from_source(MemoryBackend::new(archive.clone()))
+            .await
+            .unwrap();
+        assert!(matches!(
+            tiles.get_tile_zxy(0, 0, 0).await,
+            Err(PmtError::UnsupportedCompression(Compression::Unknown))
+        ));
+
+        let tiles = PmTilesReaderBuilder::new()
+            .backend(MemoryBackend::new(archive))
+            .decompressor(Compression::Unknown, |data: Bytes| {
+                Ok(data.iter().rev().copied().collect::<Vec<u8>>().into())
+            })
+            .build()
+            .await
+            .unwrap();
+        let tile = tiles.get_tile_zxy(0, 0, 0).await.unwrap().unwrap();
+        assert_eq!(tile, "abc");
+    }
+
+    #[tokio::test]
+    async fn test_diff() {
+        let entry = |tile_id, offset, length, run_length| DirEntry {
+            tile_id,
+            offset,
+            length,
+            run_length,
+        };
+        let archive = |entries, data: &[u8]| {
+            let archive = archive_from_parts(&Directory::from_entries(entries), &[], data);
+            AsyncPmTilesReader::from_bytes(archive.into())
+        };
+        let old = archive(
+            vec![entry(0, 0, 1, 1), entry(1, 1, 1, 2), entry(3, 2, 1, 1)],
+            b"abc",
+        )
+        .await
+        .unwrap();
+        // Tile 2 changes to data of the same length, tile 3 to longer data
+        let new = archive(
+            vec![
+                entry(1, 0, 1, 1),
+                entry(2, 1, 1, 1),
+                entry(3, 2, 2, 1),
+                entry(4, 4, 1, 1),
+            ],
+            b"bxccd",
+        )
+        .await
+        .unwrap();
+
+        let result = super::diff(&old, &new, false).await.unwrap();
+        assert_eq!(result.added, [4]);
+        assert_eq!(result.removed, [0]);
+        assert_eq!(result.changed, [3]);
+
+        let result = super::diff(&old, &new, true).await.unwrap();
+        assert_eq!(result.changed, [2, 3]);
+
+        let result = super::diff(&old, &old, true).await.unwrap();
+        assert_eq!(result, super::DirDiff::default());
+    }
+
+    #[tokio::test]
+    async fn test_missing_tile() {
+        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let tile = tiles.get_tile(6, 31, 23).await;
+        assert!(tile.is_ok());
+        assert!(tile.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_leaf_tile() {
+        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let tile = tiles.get_tile(12, 2174, 1492).await;
+        assert!(tile.is_ok_and(|t| t.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_is_clustered() {
+        // Fixtures with deduplicated tiles are clustered as far as their header flag is concerned,
+        // but reference tile data written before
+        for (file, clustered) in [
+            (RASTER_FILE, false),
+            (VECTOR_FILE, false),
+            ("fixtures/leaf.pmtiles", true),
+            ("fixtures/uncompressed_dirs.pmtiles", true),
+        ] {
+            let backend = MmapBackend::try_from(file).await.unwrap();
+            let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+            assert!(tiles.header.clustered, "{file}");
+            assert_eq!(tiles.is_clustered().await.unwrap(), clustered, "{file}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let stats = tiles.stats().await.unwrap();
+        assert_eq!(stats.root_entries, 84);
+        assert_eq!(stats.leaf_directories, 0);
+        assert_eq!(stats.leaf_entries, 0);
+
+        let backend = MmapBackend::try_from("fixtures/leaf.pmtiles")
+            .await
+            .unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let stats = tiles.stats().await.unwrap();
+        assert_eq!(stats.leaf_directories, stats.root_entries);
+        assert_eq!(
+            stats.leaf_entries as u64,
+            tiles.get_header().n_tile_entries.unwrap().get()
+        );
+    }
+
+    /// Counts the reads of the wrapped backend.
+    struct CountingBackend<B>(B, AtomicUsize);
+
+    impl<B: AsyncBackend + Sync + Send> AsyncBackend for CountingBackend<B> {
+        async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+            self.1.fetch_add(1, Ordering::Relaxed);
+            self.0.read(offset, length).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_root_directory_resident() {
+        let data = std::fs::read(RASTER_FILE).unwrap();
+        let backend = CountingBackend(MemoryBackend::new(data.into()), AtomicUsize::new(0));
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let reads = tiles.backend.1.load(Ordering::Relaxed);
+
+        // All tiles are listed in the root directory, so lookups don't read from the backend
+        for id in 0..85 {
+            let entry = tiles.find_tile_entry(id).await.unwrap().unwrap();
+            assert_eq!(tiles.root_directory().find_tile_id(id), Some(&entry));
+        }
+        assert_eq!(tiles.backend.1.load(Ordering::Relaxed), reads);
+    }
+
+    #[tokio::test]
+    async fn test_root_directory_outside_initial_window() {
+        let data = std::fs::read("fixtures/leaf.pmtiles").unwrap();
+        let backend = CountingBackend(MemoryBackend::new(data.clone().into()), AtomicUsize::new(0));
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        // Header and root directory are read at once
+        assert_eq!(tiles.backend.1.load(Ordering::Relaxed), 1);
+
+        // Move the root directory past the first 16 KiB
+        let header = tiles.get_header();
+        let root = &data[header.root_offset as usize..][..header.root_length as usize];
+        let mut moved = data.clone();
+        moved.resize(20_000, 0);
+        moved.extend_from_slice(root);
+        moved[8..16].copy_from_slice(&20_000_u64.to_le_bytes());
+
+        let backend = CountingBackend(MemoryBackend::new(moved.into()), AtomicUsize::new(0));
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        assert_eq!(tiles.backend.1.load(Ordering::Relaxed), 2);
+        assert_eq!(tiles.get_tile(1, 1, 0).await.unwrap().unwrap(), &b"4"[..]);
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_directories() {
+        let backend = CountingBackend(
+            MmapBackend::try_from("fixtures/leaf.pmtiles")
+                .await
+                .unwrap(),
+            AtomicUsize::new(0),
+        );
+        let tiles = AsyncPmTilesReader::try_from_cached_source(backend, HashMapCache::default())
+            .await
+            .unwrap();
+        tiles.prefetch_directories().await.unwrap();
+        assert!(!tiles.cache.cache.read().unwrap().is_empty());
+
+        let reads = tiles.backend.1.load(Ordering::Relaxed);
+        let tile = tiles.get_tile(1, 1, 0).await.unwrap().unwrap();
+        assert_eq!(tile, &b"4"[..]);
+        // Only the tile data itself is read
+        assert_eq!(tiles.backend.1.load(Ordering::Relaxed), reads + 1);
+    }
+
+    async fn modified_leaf_file(
+        modify: impl FnOnce(&mut Vec<u8>),
+    ) -> AsyncPmTilesReader<MemoryBackend> {
+        let mut data = std::fs::read("fixtures/leaf.pmtiles").unwrap();
+        modify(&mut data);
+        AsyncPmTilesReader::from_bytes(data.into()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_lenient_leaves() {
+        let mut data = std::fs::read("fixtures/leaf.pmtiles").unwrap();
+        // Zero the leaf directory, which is no valid gzip stream
+        data[233..260].fill(0);
+        let id = tile_id(1, 1, 0);
+
+        let tiles = AsyncPmTilesReader::from_bytes(data.clone().into())
+            .await
+            .unwrap();
+        assert!(tiles.get_tile(1, 1, 0).await.is_err());
+        assert!(tiles.get_tiles(&[id]).await[0].is_err());
+
+        let tiles = PmTilesReaderBuilder::new()
+            .backend(MemoryBackend::new(data.into()))
+            .lenient_leaves(true)
+            .build()
+            .await
+            .unwrap();
+        assert!(tiles.get_tile(1, 1, 0).await.unwrap().is_none());
+        assert!(tiles.get_tiles(&[id]).await[0].as_ref().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_uncompressed_directories() {
+        let backend = MmapBackend::try_from("fixtures/uncompressed_dirs.pmtiles")
+            .await
+            .unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        assert_eq!(tiles.header.internal_compression, Compression::None);
+
+        let tile = tiles.get_tile(2, 1, 3).await.unwrap().unwrap();
+        assert_eq!(tile, &b"2/1/3"[..]);
+        assert!(tiles.get_tile(3, 0, 0).await.unwrap().is_none());
+
+        let metadata = tiles.get_metadata().await.unwrap();
+        assert_eq!(metadata, r#"{"name":"uncompressed directories"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_multi_member_gzip() {
+        let backend = MmapBackend::try_from("fixtures/multi_member_gzip.pmtiles")
+            .await
+            .unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        assert_eq!(tiles.header.internal_compression, Compression::Gzip);
+
+        // The last tiles are only listed in the second gzip member of the root directory
+        assert_eq!(tiles.root_directory.iter().count(), 21);
+        let tile = tiles.get_tile(2, 3, 3).await.unwrap().unwrap();
+        assert_eq!(tile, &b"2/3/3"[..]);
+
+        let metadata = tiles.get_metadata().await.unwrap();
+        assert_eq!(metadata, r#"{"name":"multi-member gzip"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_get_tile_with_entry() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        // The fixture has a single entry with a run length of 2
+        let runs: Vec<_> = tiles
+            .root_directory
+            .iter()
+            .filter(|e| e.run_length() > 1)
+            .collect();
+        let [run] = runs[..] else {
+            panic!("expected a single run, found {runs:?}");
+        };
+        for id in run.tile_id()..run.tile_id() + u64::from(run.run_length()) {
+            let (data, entry) = tiles.get_tile_with_entry(id).await.unwrap().unwrap();
+            assert_eq!(&entry, run);
+            assert_eq!(data, tiles.read_entry_data(run).await.unwrap());
+        }
+
+        let (_, entry) = tiles.get_tile_with_entry(0).await.unwrap().unwrap();
+        assert_eq!((entry.tile_id(), entry.run_length()), (0, 1));
+        assert!(tiles.get_tile_with_entry(85).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_metadata_raw() {
+        let mut data = std::fs::read(RASTER_FILE).unwrap();
+        let tiles = AsyncPmTilesReader::from_bytes(Bytes::from(data.clone()))
+            .await
+            .unwrap();
+        let start = tiles.header.metadata_offset as usize;
+        let metadata = start..start + tiles.header.metadata_length as usize;
+        data[metadata.clone()].fill(0xff);
+        let tiles = AsyncPmTilesReader::from_bytes(Bytes::from(data))
+            .await
+            .unwrap();
+
+        // The metadata is not valid gzip, but is still returned as stored
+        assert!(tiles.get_metadata().await.is_err());
+        let raw = tiles.get_metadata_raw().await.unwrap();
+        assert_eq!(raw.len(), metadata.len());
+        assert!(raw.iter().all(|&b| b == 0xff));
+    }
+
+    #[tokio::test]
+    async fn test_uncompressed_metadata() {
+        let backend = MmapBackend::try_from("fixtures/uncompressed_dirs.pmtiles")
+            .await
+            .unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        // The archive stores the metadata as plain JSON
+        let header = &tiles.header;
+        let stored = tiles
+            .backend
+            .read_exact(header.metadata_offset as _, header.metadata_length as _)
+            .await
+            .unwrap();
+        assert_eq!(stored, tiles.get_metadata().await.unwrap().as_bytes());
+        assert_eq!(stored, tiles.get_metadata_raw().await.unwrap());
+
+        #[cfg(feature = "metadata")]
+        {
+            let metadata = tiles.get_metadata_typed().await.unwrap();
+            assert_eq!(metadata.name.as_deref(), Some("uncompressed directories"));
+        }
+        #[cfg(feature = "tilejson")]
+        {
+            let tj = tiles.parse_tilejson(Vec::new()).await.unwrap();
+            assert_eq!(tj.name.as_deref(), Some("uncompressed directories"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_all_tiles() {
+        let backend = CountingBackend(
+            MmapBackend::try_from(RASTER_FILE).await.unwrap(),
+            AtomicUsize::new(0),
+        );
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let all: Vec<(u64, Bytes)> = tiles.stream_all_tiles().try_collect().await.unwrap();
+
+        let n_addressed = tiles.header.n_addressed_tiles.unwrap().get();
+        assert_eq!(all.len() as u64, n_addressed);
+        assert!(all.windows(2).all(|w| w[0].0 < w[1].0));
+        let (_, tile) = all.iter().find(|(id, _)| *id == tile_id(3, 4, 5)).unwrap();
+        assert_eq!(tile, &include_bytes!("../fixtures/3_4_5.png")[..]);
+        // The header and root directory, then all of the clustered tile data at once
+        assert_eq!(tiles.backend.1.load(Ordering::Relaxed), 2);
+
+        // Run-length encoded entries yield every tile
+        let root = Directory::from_entries(vec![
+            DirEntry {
+                tile_id: 0,
+                offset: 0,
+                length: 1,
+                run_length: 1,
+            },
+            DirEntry {
+                tile_id: 1,
+                offset: 1,
+                length: 1,
+                run_length: 2,
+            },
+        ]);
+        let archive = archive_from_parts(&root, &[], b"ab");
+        let tiles = AsyncPmTilesReader::from_bytes(archive.into())
+            .await
+            .unwrap();
+        let all: Vec<(u64, Bytes)> = tiles.stream_all_tiles().try_collect().await.unwrap();
         assert_eq!(
-            tile.len(),
-            fixture_bytes.len(),
-            "Expected tile length to match."
+            all,
+            vec![(0, Bytes::from("a")), (1, "b".into()), (2, "b".into())]
         );
-        assert_eq!(tile, fixture_bytes, "Expected tile to match fixture.");
     }
 
     #[tokio::test]
-    async fn get_first_tile() {
-        let fixture_tile = include_bytes!("../fixtures/0_0_0.png");
-        compare_tiles(0, 0, 0, fixture_tile).await;
+    async fn test_coalesce_gap() {
+        // Three tiles with a single unused byte between each of them
+        let entries = (0..3)
+            .map(|i| DirEntry {
+                tile_id: i,
+                offset: i * 2,
+                length: 1,
+                run_length: 1,
+            })
+            .collect();
+        let archive = Bytes::from(archive_from_parts(
+            &Directory::from_entries(entries),
+            &[],
+            b"a_b_c",
+        ));
+
+        for (gap, expected_reads) in [(0, 3), (1, 1)] {
+            let tiles = PmTilesReaderBuilder::new()
+                .backend(CountingBackend(
+                    MemoryBackend::new(archive.clone()),
+                    AtomicUsize::new(0),
+                ))
+                .coalesce_gap(gap)
+                .build()
+                .await
+                .unwrap();
+            let initial_reads = tiles.backend.1.load(Ordering::Relaxed);
+
+            let results = tiles.get_tiles(&[2, 0, 1, 2]).await;
+            let results: Vec<_> = results.into_iter().map(|r| r.unwrap().unwrap()).collect();
+            assert_eq!(results, ["c", "a", "b", "c"]);
+            let reads = tiles.backend.1.load(Ordering::Relaxed) - initial_reads;
+            assert_eq!(reads, expected_reads, "gap {gap}");
+
+            let all: Vec<(u64, Bytes)> = tiles.stream_all_tiles().try_collect().await.unwrap();
+            assert_eq!(all, vec![(0, "a".into()), (1, "b".into()), (2, "c".into())]);
+        }
+    }
+
+    /// Tracks the largest sum of lengths of concurrent reads of the wrapped backend.
+    struct InflightBackend<B> {
+        backend: B,
+        current: AtomicUsize,
+        max: AtomicUsize,
+    }
+
+    impl<B: AsyncBackend + Sync + Send> AsyncBackend for InflightBackend<B> {
+        async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+            let current = self.current.fetch_add(length, Ordering::Relaxed) + length;
+            self.max.fetch_max(current, Ordering::Relaxed);
+            tokio::task::yield_now().await;
+            let data = self.backend.read(offset, length).await;
+            self.current.fetch_sub(length, Ordering::Relaxed);
+            data
+        }
     }
 
     #[tokio::test]
-    async fn get_another_tile() {
-        let fixture_tile = include_bytes!("../fixtures/2_2_2.png");
-        compare_tiles(2, 2, 2, fixture_tile).await;
+    async fn test_max_inflight_bytes() {
+        // Tiles of 4, 4, 8 and 4 bytes, separated by a single unused byte
+        let mut data = Vec::new();
+        let mut entries = Vec::new();
+        for (tile_id, length) in [4, 4, 8, 4].into_iter().enumerate() {
+            entries.push(DirEntry {
+                tile_id: tile_id as u64,
+                offset: data.len() as u64,
+                length,
+                run_length: 1,
+            });
+            data.extend(std::iter::repeat(b'a' + tile_id as u8).take(length as usize + 1));
+        }
+        let archive = Bytes::from(archive_from_parts(
+            &Directory::from_entries(entries),
+            &[],
+            &data,
+        ));
+
+        // Without a budget, all tiles are fetched with a single read of 23 bytes.
+        // A budget smaller than a tile still allows reading it on its own.
+        for (budget, expected_max) in [(None, 23), (Some(8), 8), (Some(1), 8)] {
+            let builder = PmTilesReaderBuilder::new().backend(InflightBackend {
+                backend: MemoryBackend::new(archive.clone()),
+                current: AtomicUsize::new(0),
+                max: AtomicUsize::new(0),
+            });
+            let builder = match budget {
+                Some(budget) => builder.max_inflight_bytes(budget),
+                None => builder,
+            };
+            let tiles = builder.coalesce_gap(1).build().await.unwrap();
+            tiles.backend.max.store(0, Ordering::Relaxed);
+
+            let results = tiles.get_tiles(&[0, 1, 2, 3]).await;
+            let lengths: Vec<_> = results
+                .into_iter()
+                .map(|r| r.unwrap().unwrap().len())
+                .collect();
+            assert_eq!(lengths, [4, 4, 8, 4]);
+            assert_eq!(
+                tiles.backend.max.load(Ordering::Relaxed),
+                expected_max,
+                "{budget:?}"
+            );
+        }
     }
 
     #[tokio::test]
-    async fn get_yet_another_tile() {
-        let fixture_tile = include_bytes!("../fixtures/3_4_5.png");
-        compare_tiles(3, 4, 5, fixture_tile).await;
+    async fn test_mmap_read_past_end() {
+        let backend = MmapBackend::try_from("fixtures/leaf.pmtiles")
+            .await
+            .unwrap();
+        let len = std::fs::read("fixtures/leaf.pmtiles").unwrap().len();
+
+        assert_eq!(backend.read(len - 2, 10).await.unwrap().len(), 2);
+        assert!(backend.read(len + 5, 10).await.unwrap().is_empty());
+        assert!(matches!(
+            backend.read_exact(len - 2, 10).await,
+            Err(PmtError::UnexpectedNumberOfBytesReturned(10, 2))
+        ));
+        assert!(matches!(
+            backend.read_exact(len + 5, 10).await,
+            Err(PmtError::UnexpectedNumberOfBytesReturned(10, 0))
+        ));
     }
 
     #[tokio::test]
-    async fn test_missing_tile() {
-        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+    async fn test_truncated_archive() {
+        let data = std::fs::read(RASTER_FILE).unwrap();
+        let path = get_temp_file_path("truncated.pmtiles");
+        std::fs::write(&path, &data[..data.len() - 10]).unwrap();
+
+        let result = AsyncPmTilesReader::new_with_path(&path).await;
+        let (expected, actual) = (data.len() as u64, data.len() as u64 - 10);
+        assert!(matches!(
+            result,
+            Err(PmtError::TruncatedArchive { expected: e, actual: a }) if (e, a) == (expected, actual)
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mmap_zero_copy() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
         let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let whole = tiles.backend().read(0, usize::MAX).await.unwrap();
 
-        let tile = tiles.get_tile(6, 31, 23).await;
-        assert!(tile.is_ok());
-        assert!(tile.unwrap().is_none());
+        // Uncompressed raster tiles point into the mapping
+        let tile = tiles.get_tile_zxy(0, 0, 0).await.unwrap().unwrap();
+        assert!(whole.as_ptr_range().contains(&tile.as_ptr()));
+        drop(tiles);
+        assert_eq!(tile, &include_bytes!("../fixtures/0_0_0.png")[..]);
     }
 
     #[tokio::test]
-    async fn test_leaf_tile() {
+    async fn test_read_range() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        assert_eq!(tiles.read_range(0, 7).await.unwrap(), &b"PMTiles"[..]);
+        let header = tiles.get_header();
+        let data = tiles
+            .read_range(header.data_offset, header.data_length)
+            .await
+            .unwrap();
+        assert_eq!(data.len() as u64, header.data_length);
+        assert!(tiles
+            .read_range(header.data_offset, u64::MAX >> 8)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_has_tile() {
+        let backend = CountingBackend(
+            MmapBackend::try_from("fixtures/leaf.pmtiles")
+                .await
+                .unwrap(),
+            AtomicUsize::new(0),
+        );
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let reads = || tiles.backend.1.load(Ordering::Relaxed);
+
+        let initial_reads = reads();
+        assert!(tiles.has_tile(tile_id(1, 1, 0)).await.unwrap());
+        // Only the leaf directory holding the tile is read
+        assert_eq!(reads() - initial_reads, 1);
+        assert!(tiles.get_tile(1, 1, 0).await.unwrap().is_some());
+        assert_eq!(reads() - initial_reads, 3);
+
+        assert!(!tiles.has_tile(tile_id(20, 0, 0)).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_stats_snapshot() {
+        let backend = CountingBackend(
+            MmapBackend::try_from("fixtures/leaf.pmtiles")
+                .await
+                .unwrap(),
+            AtomicUsize::new(0),
+        );
+        let tiles = AsyncPmTilesReader::try_from_cached_source(backend, HashMapCache::default())
+            .await
+            .unwrap();
+        let initial = tiles.stats_snapshot();
+        assert_eq!(
+            initial.backend_reads,
+            tiles.backend.1.load(Ordering::Relaxed) as u64
+        );
+        assert_eq!(
+            initial.directory_cache_hits + initial.directory_cache_misses,
+            0
+        );
+
+        // The leaf directory is read on the first lookup only
+        let tile_len = tiles.get_tile(1, 1, 0).await.unwrap().unwrap().len() as u64;
+        let first = tiles.stats_snapshot();
+        assert_eq!(first.directory_cache_misses, 1);
+        assert_eq!(first.backend_reads, initial.backend_reads + 2);
+        tiles.get_tile(1, 1, 0).await.unwrap();
+        let second = tiles.stats_snapshot();
+        assert_eq!(second.directory_cache_hits, 1);
+        assert_eq!(second.directory_cache_misses, 1);
+        assert_eq!(second.backend_reads, first.backend_reads + 1);
+        assert_eq!(second.bytes_read, first.bytes_read + tile_len);
+    }
+
+    #[tokio::test]
+    async fn test_stats_batched_lookup() {
+        let backend = MmapBackend::try_from("fixtures/leaf.pmtiles")
+            .await
+            .unwrap();
+        let tiles = AsyncPmTilesReader::try_from_cached_source(backend, HashMapCache::default())
+            .await
+            .unwrap();
+        // All tiles are in the single leaf directory of the archive
+        assert_eq!(tiles.root_directory.iter().count(), 1);
+        let ids = [tile_id(0, 0, 0), tile_id(1, 0, 0), tile_id(1, 1, 0)];
+
+        // A batch within one leaf directory reads it once
+        tiles.get_tiles(&ids).await;
+        let first = tiles.stats_snapshot();
+        assert_eq!(first.directory_cache_misses, 1);
+        assert_eq!(first.directory_cache_hits, 0);
+
+        // Then every tile of the batch is looked up in the cached directory once
+        tiles.get_tiles(&ids).await;
+        let second = tiles.stats_snapshot();
+        assert_eq!(second.directory_cache_misses, 1);
+        assert_eq!(second.directory_cache_hits, 3);
+    }
+
+    #[tokio::test]
+    async fn test_tile_id_bounds() {
+        for file in [RASTER_FILE, VECTOR_FILE, "fixtures/leaf.pmtiles"] {
+            let backend = MmapBackend::try_from(file).await.unwrap();
+            let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+            let entries = tiles.collect_tile_entries().await.unwrap();
+            let first = entries.first().unwrap();
+            let last = entries.last().unwrap();
+            let expected = (first.tile_id, last.tile_id + u64::from(last.run_length) - 1);
+            assert_eq!(tiles.tile_id_bounds().await.unwrap(), expected, "{file}");
+        }
+
+        let archive = archive_from_parts(&Directory::from_entries(Vec::new()), &[], &[]);
+        let tiles = AsyncPmTilesReader::try_from_source(MemoryBackend::new(archive.into()))
+            .await
+            .unwrap();
+        assert!(matches!(
+            tiles.tile_id_bounds().await,
+            Err(PmtError::EmptyArchive)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tile_count_by_zoom() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let counts = tiles.tile_count_by_zoom().await.unwrap();
+        assert_eq!(
+            counts.into_iter().collect::<Vec<_>>(),
+            [(0, 1), (1, 4), (2, 16), (3, 64)]
+        );
+
+        // Tiles 3 and 4 are of zoom 1, tiles 5 and 6 of zoom 2
+        let root = Directory::from_entries(vec![DirEntry::new(3, 0, 1, 4)]);
+        let archive = archive_from_parts(&root, &[], b"x");
+        let tiles = AsyncPmTilesReader::from_bytes(archive.into())
+            .await
+            .unwrap();
+        let counts = tiles.tile_count_by_zoom().await.unwrap();
+        assert_eq!(counts.into_iter().collect::<Vec<_>>(), [(1, 2), (2, 2)]);
+    }
+
+    #[tokio::test]
+    async fn test_builder() {
+        let tiles = PmTilesReaderBuilder::new()
+            .backend(MmapBackend::try_from(RASTER_FILE).await.unwrap())
+            .cache(HashMapCache::default())
+            .max_concurrency(2)
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(tiles.max_in_flight, 2);
+        let results = tiles.get_tiles(&[tile_id(0, 0, 0), tile_id(3, 4, 5)]).await;
+        assert!(results.iter().all(|r| r.as_ref().unwrap().is_some()));
+
+        let result = PmTilesReaderBuilder::default()
+            .backend(MemoryBackend::new(self_referential_leaf_archive().into()))
+            .max_leaf_depth(1)
+            .build()
+            .await
+            .unwrap()
+            .get_tile(0, 0, 0)
+            .await;
+        assert!(matches!(result, Err(PmtError::LeafRecursionLimit)));
+    }
+
+    #[tokio::test]
+    async fn test_leaf_recursion_limit() {
+        let data = Bytes::from(self_referential_leaf_archive());
+        let tiles = AsyncPmTilesReader::from_bytes(data.clone()).await.unwrap();
+        assert!(matches!(
+            tiles.get_tile(0, 0, 0).await,
+            Err(PmtError::LeafRecursionLimit)
+        ));
+        assert!(matches!(
+            tiles.prefetch_directories().await,
+            Err(PmtError::LeafRecursionLimit)
+        ));
+        assert!(matches!(
+            tiles.find_tile_entries(&[0]).await,
+            Err(PmtError::LeafRecursionLimit)
+        ));
+
+        let tiles = AsyncPmTilesReader::from_bytes(data)
+            .await
+            .unwrap()
+            .with_max_leaf_depth(0);
+        assert!(matches!(
+            tiles.is_clustered().await,
+            Err(PmtError::LeafRecursionLimit)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_compression() {
+        let backend = |compression: u8| {
+            let mut data = std::fs::read("fixtures/leaf.pmtiles").unwrap();
+            // internal_compression
+            data[97] = compression;
+            MemoryBackend::new(data.into())
+        };
+
+        let result = AsyncPmTilesReader::try_from_source(backend(0)).await;
+        assert!(matches!(
+            result,
+            Err(PmtError::UnsupportedCompression(Compression::Unknown))
+        ));
+        #[cfg(not(feature = "brotli"))]
+        assert!(matches!(
+            AsyncPmTilesReader::try_from_source(backend(3)).await,
+            Err(PmtError::UnsupportedCompression(Compression::Brotli))
+        ));
+        #[cfg(not(feature = "zstd"))]
+        assert!(matches!(
+            AsyncPmTilesReader::try_from_source(backend(4)).await,
+            Err(PmtError::UnsupportedCompression(Compression::Zstd))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate() {
+        for file in [
+            RASTER_FILE,
+            VECTOR_FILE,
+            "fixtures/leaf.pmtiles",
+            "fixtures/uncompressed_dirs.pmtiles",
+        ] {
+            let backend = MmapBackend::try_from(file).await.unwrap();
+            let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+            let report = tiles.validate().await.unwrap();
+            assert!(report.is_valid(), "{file}: {:?}", report.issues());
+            assert!(
+                report.warnings().is_empty(),
+                "{file}: {:?}",
+                report.warnings()
+            );
+            assert!(report.n_tile_entries() > 0);
+        }
+
+        let tiles = modified_leaf_file(|_| {}).await;
+        let report = tiles.validate().await.unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.n_directories(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_validate_reports_all_issues() {
+        let tiles = modified_leaf_file(|data| {
+            // leaf_length
+            data[48..56].copy_from_slice(&10_u64.to_le_bytes());
+        })
+        .await;
+        let report = tiles.validate().await.unwrap();
+        let issues = report.issues();
+        assert!(issues
+            .iter()
+            .any(|i| matches!(i, ValidationIssue::LeafOutOfBounds { .. })));
+
+        // The five tiles are one byte each
+        let tiles = modified_leaf_file(|data| {
+            data[64..72].copy_from_slice(&3_u64.to_le_bytes());
+        })
+        .await;
+        let report = tiles.validate().await.unwrap();
+        assert_eq!(report.issues().len(), 2);
+        assert!(report
+            .issues()
+            .iter()
+            .all(|i| matches!(i, ValidationIssue::TileOutOfBounds { .. })));
+
+        let mut data = std::fs::read("fixtures/leaf.pmtiles").unwrap();
+        data[64..72].copy_from_slice(&300_u64.to_le_bytes());
+        // Backends knowing the archive size refuse to open it
+        assert!(matches!(
+            AsyncPmTilesReader::from_bytes(data.clone().into()).await,
+            Err(PmtError::TruncatedArchive { .. })
+        ));
+        let backend = CountingBackend(MemoryBackend::new(data.into()), AtomicUsize::new(0));
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let report = tiles.validate().await.unwrap();
+        assert_eq!(
+            report.issues(),
+            [ValidationIssue::SectionOutOfBounds(Section::TileData)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_warnings() {
+        let root = Directory::from_entries(vec![DirEntry::new(0, 0, 1, 1)]);
+        // A raster archive with the given tile compression and metadata
+        let archive = |compression: Compression, metadata: &str| {
+            let mut data = archive_from_parts(&root, &[], b"x");
+            data[98] = compression.into();
+            let metadata_offset = data.len() as u64;
+            data[24..32].copy_from_slice(&metadata_offset.to_le_bytes());
+            data[32..40].copy_from_slice(&(metadata.len() as u64).to_le_bytes());
+            data.extend_from_slice(metadata.as_bytes());
+            data
+        };
+
+        let tiles = AsyncPmTilesReader::from_bytes(archive(Compression::Gzip, "{}").into())
+            .await
+            .unwrap();
+        let report = tiles.validate().await.unwrap();
+        assert!(report.is_valid());
+        assert_eq!(
+            report.warnings(),
+            [ValidationWarning::UnusualTileCompression {
+                tile_type: TileType::Png,
+                compression: Compression::Gzip
+            }]
+        );
+
+        let metadata = r#"{"format":"pbf"}"#;
+        let tiles = AsyncPmTilesReader::from_bytes(archive(Compression::None, metadata).into())
+            .await
+            .unwrap();
+        let warnings = tiles.validate().await.unwrap().warnings;
+        #[cfg(feature = "metadata")]
+        assert_eq!(
+            warnings,
+            [ValidationWarning::TileTypeMismatch {
+                tile_type: TileType::Png,
+                format: "pbf".to_string()
+            }]
+        );
+        #[cfg(not(feature = "metadata"))]
+        assert!(warnings.is_empty());
+
+        let metadata = r#"{"format":"png"}"#;
+        let tiles = AsyncPmTilesReader::from_bytes(archive(Compression::None, metadata).into())
+            .await
+            .unwrap();
+        assert!(tiles.validate().await.unwrap().warnings().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_base_offset() {
+        let archive = std::fs::read(RASTER_FILE).unwrap();
+        let mut container = vec![0xff; 1000];
+        container.extend_from_slice(&archive);
+        container.extend_from_slice(&[0xff; 100]);
+
+        let tiles = PmTilesReaderBuilder::new()
+            .backend(MemoryBackend::new(container.into()))
+            .with_base_offset(1000)
+            .build()
+            .await
+            .unwrap();
+        let expected = AsyncPmTilesReader::from_bytes(archive.into())
+            .await
+            .unwrap();
+        assert_eq!(tiles.get_header(), expected.get_header());
+        assert_eq!(
+            tiles.get_metadata().await.unwrap(),
+            expected.get_metadata().await.unwrap()
+        );
+        assert_eq!(
+            tiles.get_tile(3, 4, 5).await.unwrap(),
+            expected.get_tile(3, 4, 5).await.unwrap()
+        );
+        assert!(tiles.validate().await.unwrap().is_valid());
+    }
+
+    /// Backend serving the archive at a fixed offset of its addresses.
+    struct ShiftedBackend(MemoryBackend, usize);
+
+    impl AsyncBackend for ShiftedBackend {
+        async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+            self.0.read(offset.saturating_sub(self.1), length).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_base_offset_overflow() {
+        let base_offset = usize::MAX - 100;
+        let backend = ShiftedBackend(
+            MemoryBackend::new(std::fs::read(RASTER_FILE).unwrap().into()),
+            base_offset,
+        );
+        let tiles = PmTilesReaderBuilder::new()
+            .backend(backend)
+            .with_base_offset(base_offset as u64)
+            .build()
+            .await
+            .unwrap();
+        // The tile data lies beyond the addressable range of the backend
+        assert!(matches!(
+            tiles.get_tile(0, 0, 0).await,
+            Err(PmtError::InvalidBaseOffset(offset)) if offset == base_offset as u64
+        ));
+        let report = tiles.validate().await.unwrap();
+        assert!(report
+            .issues
+            .contains(&ValidationIssue::SectionOutOfBounds(Section::TileData)));
+    }
+
+    #[tokio::test]
+    async fn test_deep_validate() {
+        for file in [RASTER_FILE, VECTOR_FILE, "fixtures/leaf.pmtiles"] {
+            let backend = MmapBackend::try_from(file).await.unwrap();
+            let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+            let report = tiles.deep_validate(true).await.unwrap();
+            assert!(report.is_valid(), "{file}: {:?}", report.issues());
+        }
+
+        let entries = vec![DirEntry::new(0, 0, 1, 1), DirEntry::new(1, 1, 2, 1)];
+        let mut archive = archive_from_parts(&Directory::from_entries(entries), &[], b"abc");
+        // Declare the tiles as gzip compressed, and cut off the end of the second tile
+        archive[98] = Compression::Gzip.into();
+        archive.pop();
+        let backend = CountingBackend(MemoryBackend::new(archive.into()), AtomicUsize::new(0));
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let report = tiles.deep_validate(false).await.unwrap();
+        assert!(matches!(
+            report.issues(),
+            [
+                ValidationIssue::SectionOutOfBounds(Section::TileData),
+                ValidationIssue::UnreadableTile { tile_id: 1, .. }
+            ]
+        ));
+        let report = tiles.deep_validate(true).await.unwrap();
+        assert!(matches!(
+            report.issues(),
+            [
+                ValidationIssue::SectionOutOfBounds(Section::TileData),
+                ValidationIssue::TileDecompressionFailed { tile_id: 0, .. },
+                ValidationIssue::UnreadableTile { tile_id: 1, .. }
+            ]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_export_to_dir() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let root = get_temp_file_path("export");
+        let _ = std::fs::remove_dir_all(&root);
+        let count = tiles.export_to_dir(&root).await.unwrap();
+        assert_eq!(count, 85);
+
+        let tile = std::fs::read(root.join("3/4/5.png")).unwrap();
+        assert_eq!(tile, include_bytes!("../fixtures/3_4_5.png"));
+        assert_eq!(std::fs::read_dir(root.join("3")).unwrap().count(), 8);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        // Vector tiles are written decompressed
         let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
         let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let root = get_temp_file_path("export-vector");
+        let _ = std::fs::remove_dir_all(&root);
+        assert_eq!(tiles.export_to_dir(&root).await.unwrap(), 108);
+        let tile = std::fs::read(root.join("0/0/0.mvt")).unwrap();
+        assert_eq!(tile, tiles.get_tile_zxy(0, 0, 0).await.unwrap().unwrap());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 
-        let tile = tiles.get_tile(12, 2174, 1492).await;
-        assert!(tile.is_ok_and(|t| t.is_some()));
+    #[tokio::test]
+    #[cfg(feature = "write")]
+    async fn test_extract_zoom_range() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let path = get_temp_file_path("extract.pmtiles");
+        let file = std::fs::File::create(&path).unwrap();
+        tiles.extract_zoom_range(1, 2, file).await.unwrap();
+
+        let backend = MmapBackend::try_from(&path).await.unwrap();
+        let extracted = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let header = extracted.get_header();
+        assert_eq!((header.min_zoom, header.max_zoom), (1, 2));
+        // Bounds and center are computed from the tiles instead of copied from the source
+        assert!(header.max_latitude > tiles.get_header().max_latitude);
+        assert_eq!(header.center_zoom, 2);
+        assert_eq!(header.n_addressed_tiles.unwrap().get(), 4 + 16);
+        assert!(header.n_tile_entries < tiles.get_header().n_tile_entries);
+        assert!(header.clustered);
+        assert!(extracted.is_clustered().await.unwrap());
+        assert!(extracted.validate().await.unwrap().is_valid());
+
+        assert!(extracted.get_tile(0, 0, 0).await.unwrap().is_none());
+        assert!(extracted.get_tile(3, 4, 5).await.unwrap().is_none());
+        let tile = extracted.get_tile(2, 2, 2).await.unwrap().unwrap();
+        assert_eq!(tile, &include_bytes!("../fixtures/2_2_2.png")[..]);
+        assert_eq!(
+            extracted.get_metadata().await.unwrap(),
+            tiles.get_metadata().await.unwrap()
+        );
+
+        // Inverted ranges and ranges outside of the archive's zoom levels 0..=3
+        for (min_zoom, max_zoom) in [(2, 1), (5, 8)] {
+            let out = std::io::Cursor::new(Vec::new());
+            assert!(matches!(
+                tiles.extract_zoom_range(min_zoom, max_zoom, out).await,
+                Err(PmtError::InvalidZoomRange(min, max)) if (min, max) == (min_zoom, max_zoom)
+            ));
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_get_tiles() {
+        for file in [VECTOR_FILE, "fixtures/leaf.pmtiles"] {
+            let backend = MmapBackend::try_from(file).await.unwrap();
+            let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+            // Unsorted, with duplicates and tiles missing from the archive
+            let ids = [
+                tile_id(12, 2174, 1492),
+                tile_id(1, 1, 0),
+                tile_id(0, 0, 0),
+                tile_id(20, 0, 0),
+                tile_id(1, 1, 0),
+                tile_id(14, 8709, 5966),
+            ];
+            for max_in_flight in [0, 1, 4] {
+                let results = tiles
+                    .get_tiles_with_max_in_flight(&ids, max_in_flight)
+                    .await;
+                assert_eq!(results.len(), ids.len());
+                for (&id, result) in ids.iter().zip(results) {
+                    let (z, x, y) = tile_coord(id);
+                    let expected = tiles.get_tile(z, x, y).await.unwrap();
+                    assert_eq!(result.unwrap(), expected, "{file}: tile {id}");
+                }
+            }
+        }
+
+        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        assert!(tiles.get_tiles(&[]).await.is_empty());
+        let results = tiles.get_tiles(&[tile_id(0, 0, 0)]).await;
+        assert!(results[0].as_ref().unwrap().is_some());
     }
 
     #[tokio::test]
@@ -313,6 +3109,17 @@ mod tests {
         assert!(!metadata.is_empty());
     }
 
+    #[tokio::test]
+    #[cfg(feature = "metadata")]
+    async fn test_get_metadata_typed() {
+        let backend = MmapBackend::try_from(VECTOR_FILE).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let metadata = tiles.get_metadata_typed().await.unwrap();
+        assert!(metadata.attribution.unwrap().contains("OpenStreetMap"));
+        assert!(metadata.other.contains_key("tilestats"));
+    }
+
     #[tokio::test]
     #[cfg(feature = "tilejson")]
     async fn test_parse_tilejson() {
@@ -0,0 +1,102 @@
+use std::io::SeekFrom;
+
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncSeek, AsyncSeekExt as _};
+use tokio::sync::Mutex;
+
+use crate::async_reader::{AsyncBackend, AsyncPmTilesReader};
+use crate::cache::{DirectoryCache, NoCache};
+use crate::error::PmtResult;
+
+impl<R> AsyncPmTilesReader<AsyncReadBackend<R>, NoCache>
+where
+    R: AsyncRead + AsyncSeek + Unpin + Send,
+{
+    /// Creates a new `PMTiles` reader from any seekable async reader.
+    ///
+    /// Fails if the reader does not contain a valid archive.
+    pub async fn new_with_reader(reader: R) -> PmtResult<Self> {
+        Self::new_with_cached_reader(NoCache, reader).await
+    }
+}
+
+impl<R, C> AsyncPmTilesReader<AsyncReadBackend<R>, C>
+where
+    R: AsyncRead + AsyncSeek + Unpin + Send,
+    C: DirectoryCache + Sync + Send,
+{
+    /// Creates a new cached `PMTiles` reader from any seekable async reader.
+    ///
+    /// Fails if the reader does not contain a valid archive.
+    pub async fn new_with_cached_reader(cache: C, reader: R) -> PmtResult<Self> {
+        let backend = AsyncReadBackend::new(reader);
+
+        Self::try_from_cached_source(backend, cache).await
+    }
+}
+
+/// A backend for any [`AsyncRead`] + [`AsyncSeek`] source.
+///
+/// Every read seeks to the requested offset first, so the reader is kept behind
+/// an async mutex and concurrent reads are serialized. To share the backend
+/// between tasks, `R` must be [`Send`]; it does not need to be [`Sync`].
+pub struct AsyncReadBackend<R> {
+    reader: Mutex<R>,
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin + Send> AsyncReadBackend<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: Mutex::new(reader),
+        }
+    }
+
+    /// Returns the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin + Send> AsyncBackend for AsyncReadBackend<R> {
+    async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        let mut reader = self.reader.lock().await;
+        reader.seek(SeekFrom::Start(offset as u64)).await?;
+
+        let mut buf = Vec::with_capacity(length);
+        (&mut *reader)
+            .take(length as u64)
+            .read_to_end(&mut buf)
+            .await?;
+
+        Ok(Bytes::from(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::AsyncReadBackend;
+    use crate::async_reader::{AsyncBackend as _, AsyncPmTilesReader};
+    use crate::tests::RASTER_FILE;
+
+    #[tokio::test]
+    async fn read_from_cursor() {
+        let data = std::fs::read(RASTER_FILE).unwrap();
+        let tiles = AsyncPmTilesReader::new_with_reader(Cursor::new(data))
+            .await
+            .unwrap();
+
+        let tile = tiles.get_tile(3, 4, 5).await.unwrap().unwrap();
+        assert_eq!(tile, &include_bytes!("../fixtures/3_4_5.png")[..]);
+    }
+
+    #[tokio::test]
+    async fn read_past_end() {
+        let backend = AsyncReadBackend::new(Cursor::new(b"PMTiles".to_vec()));
+
+        assert_eq!(backend.read(3, 100).await.unwrap(), &b"iles"[..]);
+        assert_eq!(backend.read(10, 5).await.unwrap(), &b""[..]);
+        assert!(backend.read_exact(3, 100).await.is_err());
+    }
+}
@@ -61,17 +61,27 @@ impl AwsS3Backend {
 
 impl AsyncBackend for AwsS3Backend {
     async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        if length == 0 {
+            return Ok(Bytes::new());
+        }
         let range_end = offset + length - 1;
         let range = format!("bytes={offset}-{range_end}");
 
-        let obj = self
+        let obj = match self
             .client
             .get_object()
             .bucket(self.bucket.clone())
             .key(self.key.clone())
             .range(range)
             .send()
-            .await?;
+            .await
+        {
+            // The range starts at or past the end of the archive
+            Err(e) if e.raw_response().map(|r| r.status().as_u16()) == Some(416) => {
+                return Ok(Bytes::new());
+            }
+            result => result?,
+        };
 
         let response_bytes = obj
             .body
@@ -0,0 +1,107 @@
+use std::future::Future;
+
+use bytes::Bytes;
+use js_sys::{Promise, Uint8Array};
+use send_wrapper::SendWrapper;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::{JsCast as _, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Request, RequestInit, Response};
+
+use crate::async_reader::{AsyncBackend, AsyncPmTilesReader};
+use crate::cache::{DirectoryCache, NoCache};
+use crate::error::{PmtError, PmtResult};
+
+#[wasm_bindgen]
+extern "C" {
+    // The global `fetch`, available both in windows and in workers
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch_with_request(request: &Request) -> Promise;
+}
+
+impl AsyncPmTilesReader<FetchBackend, NoCache> {
+    /// Creates a new `PMTiles` reader from a URL using the browser `fetch` API.
+    ///
+    /// Fails if [url] does not exist or is an invalid archive. (Note: HTTP requests are made to validate it.)
+    pub async fn new_with_fetch_url(url: impl Into<String>) -> PmtResult<Self> {
+        Self::new_with_cached_fetch_url(NoCache, url).await
+    }
+}
+
+impl<C: DirectoryCache + Sync + Send> AsyncPmTilesReader<FetchBackend, C> {
+    /// Creates a new `PMTiles` reader with cache from a URL using the browser `fetch` API.
+    ///
+    /// Fails if [url] does not exist or is an invalid archive. (Note: HTTP requests are made to validate it.)
+    pub async fn new_with_cached_fetch_url(cache: C, url: impl Into<String>) -> PmtResult<Self> {
+        let backend = FetchBackend::new(url);
+
+        Self::try_from_cached_source(backend, cache).await
+    }
+}
+
+/// A backend reading byte ranges with the `fetch` API of browsers and other JavaScript runtimes.
+///
+/// This backend only works on `wasm32-unknown-unknown`. JavaScript values cannot be shared
+/// between threads, so the returned futures must be polled on the thread that created them,
+/// which is always the case in a single threaded WebAssembly environment.
+pub struct FetchBackend {
+    url: String,
+}
+
+impl FetchBackend {
+    #[must_use]
+    pub fn new(url: impl Into<String>) -> Self {
+        Self { url: url.into() }
+    }
+
+    async fn fetch(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        let init = RequestInit::new();
+        init.set_method("GET");
+        let request = Request::new_with_str_and_init(&self.url, &init).map_err(js_error)?;
+        let end = offset + length - 1;
+        request
+            .headers()
+            .set("Range", &format!("bytes={offset}-{end}"))
+            .map_err(js_error)?;
+
+        let response: Response = JsFuture::from(fetch_with_request(&request))
+            .await
+            .and_then(JsValue::dyn_into)
+            .map_err(js_error)?;
+        if response.status() == 416 {
+            // The range starts at or past the end of the archive
+            return Ok(Bytes::new());
+        }
+        if response.status() != 206 {
+            return Err(PmtError::RangeRequestsUnsupported);
+        }
+
+        let buffer = JsFuture::from(response.array_buffer().map_err(js_error)?)
+            .await
+            .map_err(js_error)?;
+        Ok(Bytes::from(Uint8Array::new(&buffer).to_vec()))
+    }
+}
+
+impl AsyncBackend for FetchBackend {
+    fn read(&self, offset: usize, length: usize) -> impl Future<Output = PmtResult<Bytes>> + Send {
+        SendWrapper::new(async move {
+            if length == 0 {
+                return Ok(Bytes::new());
+            }
+            let response_bytes = self.fetch(offset, length).await?;
+
+            if response_bytes.len() > length {
+                Err(PmtError::ResponseBodyTooLong(response_bytes.len(), length))
+            } else {
+                Ok(response_bytes)
+            }
+        })
+    }
+}
+
+// Taking the value allows passing this function to `map_err` directly
+#[allow(clippy::needless_pass_by_value)]
+fn js_error(error: JsValue) -> PmtError {
+    PmtError::Fetch(format!("{error:?}"))
+}
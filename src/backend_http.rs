@@ -1,10 +1,20 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher as _, Hasher as _};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
 use bytes::Bytes;
-use reqwest::header::{HeaderValue, RANGE};
-use reqwest::{Client, IntoUrl, Method, Request, StatusCode, Url};
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_RANGE, LAST_MODIFIED,
+    RANGE,
+};
+use reqwest::{Client, IntoUrl, Method, Request, Response, StatusCode, Url};
 
 use crate::async_reader::{AsyncBackend, AsyncPmTilesReader};
 use crate::cache::{DirectoryCache, NoCache};
 use crate::error::PmtResult;
+use crate::header::MAX_INITIAL_BYTES;
 use crate::PmtError;
 
 impl AsyncPmTilesReader<HttpBackend, NoCache> {
@@ -29,11 +39,54 @@ impl<C: DirectoryCache + Sync + Send> AsyncPmTilesReader<HttpBackend, C> {
 
         Self::try_from_cached_source(backend, cache).await
     }
+
+    /// The `ETag` of the archive, as returned by the server when the reader was created.
+    pub fn etag(&self) -> Option<&str> {
+        self.backend().etag()
+    }
+
+    /// The `Last-Modified` date of the archive, as returned by the server when the reader was created.
+    pub fn last_modified(&self) -> Option<&str> {
+        self.backend().last_modified()
+    }
 }
 
+/// Delay before the first retry of a failed request, doubled for every further retry.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+/// Upper limit of the delay between retries.
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(5);
+
 pub struct HttpBackend {
     client: Client,
     url: Url,
+    /// URLs of the subdomains requests are spread across, if any
+    shards: Vec<Url>,
+    next_shard: AtomicUsize,
+    /// Extra headers sent with every request
+    headers: HeaderMap,
+    timeout: Option<Duration>,
+    initial_read_size: usize,
+    max_retries: u32,
+    validators: OnceLock<Validators>,
+    /// The whole archive, if the server ignored a range request and sent all of it
+    full_body: OnceLock<Bytes>,
+}
+
+/// Cache validators of the archive, captured from the first response.
+#[derive(Debug)]
+struct Validators {
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+}
+
+impl Validators {
+    /// The value of the `If-Range` header, which requires a strong `ETag` or a date.
+    fn if_range(&self) -> Option<&HeaderValue> {
+        self.etag
+            .as_ref()
+            .filter(|etag| !etag.as_bytes().starts_with(b"W/"))
+            .or(self.last_modified.as_ref())
+    }
 }
 
 impl HttpBackend {
@@ -41,31 +94,389 @@ impl HttpBackend {
         Ok(HttpBackend {
             client,
             url: url.into_url()?,
+            shards: Vec::new(),
+            next_shard: AtomicUsize::new(0),
+            headers: HeaderMap::new(),
+            timeout: None,
+            initial_read_size: MAX_INITIAL_BYTES,
+            max_retries: 0,
+            validators: OnceLock::new(),
+            full_body: OnceLock::new(),
         })
     }
+
+    /// The `ETag` header of the first response, if any.
+    pub fn etag(&self) -> Option<&str> {
+        let etag = self.validators.get()?.etag.as_ref()?;
+        etag.to_str().ok()
+    }
+
+    /// The `Last-Modified` header of the first response, if any.
+    pub fn last_modified(&self) -> Option<&str> {
+        let last_modified = self.validators.get()?.last_modified.as_ref()?;
+        last_modified.to_str().ok()
+    }
+
+    /// Retries failed requests up to `max` times, with exponential backoff and jitter.
+    ///
+    /// Only network errors and `5xx` responses are retried, other errors like
+    /// `404 Not Found` or `416 Range Not Satisfiable` fail immediately. Defaults to no retries.
+    #[must_use]
+    pub fn with_retries(mut self, max: u32) -> Self {
+        self.max_retries = max;
+        self
+    }
+
+    /// Aborts requests which take longer than `timeout`, including reading the response body,
+    /// with [`PmtError::Timeout`]. With retries, the timeout applies to each attempt.
+    ///
+    /// Overrides the timeout of the [`Client`]. Defaults to no timeout.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the number of bytes requested for the header and root directory when opening the archive.
+    ///
+    /// A root directory which does not fit needs a second request, so archives with a large root
+    /// directory open faster with a bigger size. For tiny archives, a smaller size saves bandwidth,
+    /// as the response covers little more than the archive start. Defaults to 16,384 bytes.
+    #[must_use]
+    pub fn with_initial_read_size(mut self, size: usize) -> Self {
+        self.initial_read_size = size;
+        self
+    }
+
+    /// Sends the header with every request, e.g. an `Authorization` header for archives
+    /// behind an authenticated endpoint. Replaces a previous value of the same header.
+    ///
+    /// Headers which are the same for all archives can also be set as default headers of the [`Client`].
+    #[must_use]
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Spreads requests round-robin across hosts, replacing the `{s}` placeholder in the host
+    /// of the URL with each of `subdomains`, e.g. `https://{s}.example.com/tiles.pmtiles`.
+    ///
+    /// Some CDNs limit the connections per host, so this increases parallelism.
+    /// Fails with [`PmtError::NoSubdomainPlaceholder`] if the host has no placeholder,
+    /// or a subdomain does not result in a valid host.
+    /// An empty list reverts to requesting the URL as is.
+    pub fn with_subdomains(mut self, subdomains: &[&str]) -> PmtResult<Self> {
+        let host = self.url.host_str().unwrap_or_default();
+        if !host.contains("{s}") {
+            return Err(PmtError::NoSubdomainPlaceholder(self.url.to_string()));
+        }
+        self.shards = subdomains
+            .iter()
+            .map(|subdomain| {
+                let mut url = self.url.clone();
+                url.set_host(Some(&host.replace("{s}", subdomain))).ok()?;
+                Some(url)
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(|| PmtError::NoSubdomainPlaceholder(self.url.to_string()))?;
+        Ok(self)
+    }
+
+    /// The URL of the next request.
+    fn next_url(&self) -> &Url {
+        if self.shards.is_empty() {
+            return &self.url;
+        }
+        let shard = self.next_shard.fetch_add(1, Ordering::Relaxed);
+        &self.shards[shard % self.shards.len()]
+    }
+
+    /// Sends a GET request with the given `Range` header, retrying transient errors.
+    ///
+    /// Once the validators of the archive are known, the request is made conditional with
+    /// `If-Range`, and fails with [`PmtError::ArchiveChanged`] if the archive was replaced.
+    async fn get_range(&self, range: HeaderValue) -> PmtResult<Response> {
+        let if_range = self.validators.get().and_then(Validators::if_range);
+        let mut attempt = 0;
+        let response = loop {
+            let mut req = Request::new(Method::GET, self.next_url().clone());
+            req.headers_mut().clone_from(&self.headers);
+            *req.timeout_mut() = self.timeout;
+            req.headers_mut().insert(RANGE, range.clone());
+            if let Some(if_range) = if_range {
+                req.headers_mut().insert(IF_RANGE, if_range.clone());
+            }
+
+            match self
+                .client
+                .execute(req)
+                .await
+                .and_then(Response::error_for_status)
+            {
+                Err(e) if attempt < self.max_retries && is_retryable(&e) => {
+                    tokio::time::sleep(retry_delay(attempt)).await;
+                    attempt += 1;
+                }
+                result => break result.map_err(http_error)?,
+            }
+        };
+
+        let etag = response.headers().get(ETAG);
+        let validators = self.validators.get_or_init(|| Validators {
+            etag: etag.cloned(),
+            last_modified: response.headers().get(LAST_MODIFIED).cloned(),
+        });
+        // A full response to a conditional range request means the validator no longer matches
+        if (if_range.is_some() && response.status() == StatusCode::OK)
+            || (etag.is_some() && validators.etag.is_some() && etag != validators.etag.as_ref())
+        {
+            return Err(PmtError::ArchiveChanged);
+        }
+
+        Ok(response)
+    }
+
+    /// Keeps the body of a full `200 OK` response to a range request, to serve all further reads.
+    async fn buffer_full_body(&self, response: Response) -> PmtResult<&Bytes> {
+        let body = response.bytes().await.map_err(http_error)?;
+        log::warn!(
+            "{} does not support range requests, keeping the whole archive of {} bytes in memory",
+            self.url,
+            body.len()
+        );
+        Ok(self.full_body.get_or_init(|| body))
+    }
+}
+
+/// The `first-last` byte positions of a range request for `length > 0` bytes at `offset`.
+/// Positions past the end of the archive are limited by the server.
+fn byte_range(offset: usize, length: usize) -> String {
+    format!("{offset}-{}", offset.saturating_add(length - 1))
+}
+
+/// Up to `length` bytes of `body` starting at `offset`, like a range request.
+fn slice_body(body: &Bytes, offset: usize, length: usize) -> Bytes {
+    let start = offset.min(body.len());
+    let end = offset.saturating_add(length).min(body.len());
+    body.slice(start..end)
+}
+
+fn http_error(error: reqwest::Error) -> PmtError {
+    if error.is_timeout() {
+        PmtError::Timeout
+    } else {
+        PmtError::Http(error)
+    }
+}
+
+fn is_retryable(error: &reqwest::Error) -> bool {
+    match error.status() {
+        Some(status) => status.is_server_error(),
+        None => error.is_connect() || error.is_timeout() || error.is_request(),
+    }
+}
+
+/// Exponential backoff delay before retry number `attempt` (starting at 0), randomized
+/// between half and the full delay so that concurrent clients do not retry in lockstep.
+fn retry_delay(attempt: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY
+        .saturating_mul(2_u32.saturating_pow(attempt))
+        .min(RETRY_MAX_DELAY);
+    let jitter = RandomState::new().build_hasher().finish() % 1024;
+    let jitter = u32::try_from(jitter).unwrap_or_default();
+    delay / 2 + delay / 2 * jitter / 1023
+}
+
+impl HttpBackend {
+    /// Reads multiple byte ranges, given as `(offset, length)` pairs, with a single
+    /// `multipart/byteranges` request.
+    ///
+    /// If the server responds with the full content instead, it is kept in memory as with
+    /// [`AsyncBackend::read`]. Other responses without partial content fall back to one
+    /// request per range. The returned buffers are in the same order as `ranges`.
+    /// Empty ranges are not requested and return empty buffers.
+    pub async fn read_ranges(&self, ranges: &[(usize, usize)]) -> PmtResult<Vec<Bytes>> {
+        if ranges.iter().all(|&(_, length)| length == 0) {
+            return Ok(vec![Bytes::new(); ranges.len()]);
+        }
+        let slice_all = |body: &Bytes| {
+            ranges
+                .iter()
+                .map(|&(offset, length)| slice_body(body, offset, length))
+                .collect()
+        };
+        if let Some(body) = self.full_body.get() {
+            return Ok(slice_all(body));
+        }
+
+        let range = ranges
+            .iter()
+            .filter(|&&(_, length)| length > 0)
+            .map(|&(offset, length)| byte_range(offset, length))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let range = HeaderValue::try_from(format!("bytes={range}"))?;
+
+        let response = match self.get_range(range).await {
+            // All ranges start at or past the end of the archive, as for `read`
+            Err(PmtError::Http(e)) if e.status() == Some(StatusCode::RANGE_NOT_SATISFIABLE) => {
+                return Ok(vec![Bytes::new(); ranges.len()]);
+            }
+            result => result?,
+        };
+        if response.status() == StatusCode::OK {
+            return Ok(slice_all(self.buffer_full_body(response).await?));
+        }
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            let mut result = Vec::with_capacity(ranges.len());
+            for &(offset, length) in ranges {
+                result.push(self.read(offset, length).await?);
+            }
+            return Ok(result);
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let parts = if let Some(boundary) = multipart_boundary(content_type) {
+            let boundary = boundary.to_string();
+            let body = response.bytes().await.map_err(http_error)?;
+            parse_multipart_byteranges(&body, &boundary)?
+        } else {
+            // A single part response, e.g. if the server merged all ranges into one
+            let start = response
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(parse_content_range_start)
+                .ok_or(PmtError::InvalidMultipartResponse)?;
+            vec![(start, response.bytes().await.map_err(http_error)?)]
+        };
+
+        ranges
+            .iter()
+            .map(|&(offset, length)| {
+                if length == 0 {
+                    return Ok(Bytes::new());
+                }
+                parts
+                    .iter()
+                    .find_map(|(start, data)| {
+                        let from = offset.checked_sub(*start)?;
+                        (from + length <= data.len()).then(|| data.slice(from..from + length))
+                    })
+                    .ok_or(PmtError::InvalidMultipartResponse)
+            })
+            .collect()
+    }
+}
+
+/// Extracts the boundary of a `multipart/byteranges` content type.
+fn multipart_boundary(content_type: &str) -> Option<&str> {
+    let (mime, params) = content_type.split_once(';')?;
+    if !mime.trim().eq_ignore_ascii_case("multipart/byteranges") {
+        return None;
+    }
+    params.split(';').find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"'))
+    })
+}
+
+/// Parses the first byte position of a `Content-Range: bytes <start>-<end>/<size>` header value.
+fn parse_content_range_start(value: &str) -> Option<usize> {
+    let range = value.trim().strip_prefix("bytes")?.trim_start();
+    range.split_once('-')?.0.parse().ok()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Parses a `multipart/byteranges` body into its parts, as `(start offset, data)` pairs.
+fn parse_multipart_byteranges(body: &Bytes, boundary: &str) -> PmtResult<Vec<(usize, Bytes)>> {
+    let delimiter = format!("--{boundary}");
+    let delimiter = delimiter.as_bytes();
+    let mut pos = find(body, delimiter).ok_or(PmtError::InvalidMultipartResponse)?;
+    let mut parts = Vec::new();
+
+    loop {
+        pos += delimiter.len();
+        if body[pos..].starts_with(b"--") {
+            // Closing delimiter
+            break;
+        }
+
+        let headers_len =
+            find(&body[pos..], b"\r\n\r\n").ok_or(PmtError::InvalidMultipartResponse)?;
+        let headers = std::str::from_utf8(&body[pos..pos + headers_len])
+            .map_err(|_| PmtError::InvalidMultipartResponse)?;
+        let start = headers
+            .lines()
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.trim()
+                    .eq_ignore_ascii_case(CONTENT_RANGE.as_str())
+                    .then_some(value)
+            })
+            .and_then(parse_content_range_start)
+            .ok_or(PmtError::InvalidMultipartResponse)?;
+
+        let data_start = pos + headers_len + 4;
+        let data_len = find(&body[data_start..], &[b"\r\n", delimiter].concat())
+            .ok_or(PmtError::InvalidMultipartResponse)?;
+        parts.push((start, body.slice(data_start..data_start + data_len)));
+        pos = data_start + data_len + 2;
+    }
+
+    Ok(parts)
 }
 
 impl AsyncBackend for HttpBackend {
+    /// Reads a range of the archive with a range request.
+    ///
+    /// If the server does not support range requests and responds with the whole archive,
+    /// it is kept in memory to serve this and all further reads without requests, logging a warning.
+    /// This is only feasible for small archives.
     async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
-        let end = offset + length - 1;
-        let range = format!("bytes={offset}-{end}");
-        let range = HeaderValue::try_from(range)?;
-
-        let mut req = Request::new(Method::GET, self.url.clone());
-        req.headers_mut().insert(RANGE, range);
+        if length == 0 {
+            return Ok(Bytes::new());
+        }
+        if let Some(body) = self.full_body.get() {
+            return Ok(slice_body(body, offset, length));
+        }
+        let range = HeaderValue::try_from(format!("bytes={}", byte_range(offset, length)))?;
 
-        let response = self.client.execute(req).await?.error_for_status()?;
+        let response = match self.get_range(range).await {
+            // The range starts at or past the end of the archive
+            Err(PmtError::Http(e)) if e.status() == Some(StatusCode::RANGE_NOT_SATISFIABLE) => {
+                return Ok(Bytes::new());
+            }
+            result => result?,
+        };
+        if response.status() == StatusCode::OK {
+            let body = self.buffer_full_body(response).await?;
+            return Ok(slice_body(body, offset, length));
+        }
         if response.status() != StatusCode::PARTIAL_CONTENT {
             return Err(PmtError::RangeRequestsUnsupported);
         }
 
-        let response_bytes = response.bytes().await?;
+        let response_bytes = response.bytes().await.map_err(http_error)?;
         if response_bytes.len() > length {
             Err(PmtError::ResponseBodyTooLong(response_bytes.len(), length))
         } else {
             Ok(response_bytes)
         }
     }
+
+    fn initial_read_size(&self) -> usize {
+        self.initial_read_size
+    }
 }
 
 #[cfg(test)]
@@ -82,4 +493,318 @@ mod tests {
 
         AsyncPmTilesReader::try_from_source(backend).await.unwrap();
     }
+
+    #[test]
+    fn subdomain_urls() {
+        let url = "https://{s}.example.com/tiles.pmtiles";
+        let backend = HttpBackend::try_from(Client::new(), url)
+            .unwrap()
+            .with_subdomains(&["a", "b"])
+            .unwrap();
+        let hosts: Vec<_> = (0..3)
+            .map(|_| backend.next_url().host_str().unwrap().to_string())
+            .collect();
+        assert_eq!(hosts, ["a.example.com", "b.example.com", "a.example.com"]);
+
+        let backend = HttpBackend::try_from(Client::new(), url)
+            .unwrap()
+            .with_subdomains(&[])
+            .unwrap();
+        assert_eq!(backend.next_url().as_str(), url);
+
+        let backend = HttpBackend::try_from(Client::new(), "https://example.com/tiles.pmtiles");
+        assert!(matches!(
+            backend.unwrap().with_subdomains(&["a"]),
+            Err(PmtError::NoSubdomainPlaceholder(_))
+        ));
+    }
+
+    /// Serves one scripted response per connection, returning the heads of the requests received.
+    fn serve(responses: Vec<&'static str>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        use std::io::{BufRead as _, BufReader, Write as _};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/test.pmtiles", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut head = String::new();
+                while reader.read_line(&mut head).unwrap() > 0 && !head.ends_with("\r\n\r\n") {}
+                requests.push(head);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn retry_transient_errors() {
+        const UNAVAILABLE: &str =
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        const PARTIAL: &str = "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 0-2/10\r\n\
+            Content-Length: 3\r\nConnection: close\r\n\r\nabc";
+        const NOT_FOUND: &str =
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+        let (url, server) = serve(vec![UNAVAILABLE, UNAVAILABLE, PARTIAL]);
+        let backend = HttpBackend::try_from(Client::new(), url)
+            .unwrap()
+            .with_retries(2);
+        assert_eq!(backend.read(0, 3).await.unwrap(), &b"abc"[..]);
+        assert_eq!(server.join().unwrap().len(), 3);
+
+        let (url, server) = serve(vec![NOT_FOUND]);
+        let backend = HttpBackend::try_from(Client::new(), url)
+            .unwrap()
+            .with_retries(3);
+        assert!(matches!(backend.read(0, 3).await, Err(PmtError::Http(_))));
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn read_past_end() {
+        const SHORT: &str = "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 8-9/10\r\n\
+            Content-Length: 2\r\nConnection: close\r\n\r\nij";
+        const NOT_SATISFIABLE: &str = "HTTP/1.1 416 Range Not Satisfiable\r\n\
+            Content-Range: bytes */10\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+        let (url, server) = serve(vec![SHORT, NOT_SATISFIABLE, NOT_SATISFIABLE, SHORT]);
+        let backend = HttpBackend::try_from(Client::new(), url).unwrap();
+        assert_eq!(backend.read(8, 5).await.unwrap(), &b"ij"[..]);
+        assert!(backend.read(20, 5).await.unwrap().is_empty());
+        assert_eq!(
+            backend.read_ranges(&[(20, 5), (30, 0)]).await.unwrap(),
+            vec![Bytes::new(), Bytes::new()]
+        );
+        assert!(matches!(
+            backend.read_exact(8, 5).await,
+            Err(PmtError::UnexpectedNumberOfBytesReturned(5, 2))
+        ));
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 4);
+        assert!(requests[2]
+            .to_ascii_lowercase()
+            .contains("range: bytes=20-24\r\n"));
+    }
+
+    #[test]
+    fn byte_ranges() {
+        assert_eq!(byte_range(0, 1), "0-0");
+        assert_eq!(byte_range(8, 5), "8-12");
+        assert_eq!(
+            byte_range(usize::MAX - 1, 5),
+            format!("{}-{}", usize::MAX - 1, usize::MAX)
+        );
+    }
+
+    #[tokio::test]
+    async fn detect_archive_changes() {
+        const PARTIAL: &str = "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 0-2/10\r\n\
+            ETag: \"v1\"\r\nLast-Modified: Wed, 21 Oct 2015 07:28:00 GMT\r\n\
+            Content-Length: 3\r\nConnection: close\r\n\r\nabc";
+        const FULL: &str = "HTTP/1.1 200 OK\r\nETag: \"v2\"\r\n\
+            Content-Length: 10\r\nConnection: close\r\n\r\nabcdefghij";
+        const OTHER_ETAG: &str = "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 0-2/10\r\n\
+            ETag: \"v2\"\r\nContent-Length: 3\r\nConnection: close\r\n\r\nxyz";
+
+        let (url, server) = serve(vec![PARTIAL, PARTIAL, FULL, OTHER_ETAG]);
+        let backend = HttpBackend::try_from(Client::new(), url).unwrap();
+        assert_eq!(backend.etag(), None);
+
+        assert_eq!(backend.read(0, 3).await.unwrap(), &b"abc"[..]);
+        assert_eq!(backend.etag(), Some("\"v1\""));
+        assert_eq!(
+            backend.last_modified(),
+            Some("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+        assert_eq!(backend.read(0, 3).await.unwrap(), &b"abc"[..]);
+        assert!(matches!(
+            backend.read(0, 3).await,
+            Err(PmtError::ArchiveChanged)
+        ));
+        assert!(matches!(
+            backend.read(0, 3).await,
+            Err(PmtError::ArchiveChanged)
+        ));
+        assert_eq!(server.join().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn custom_headers() {
+        const PARTIAL: &str = "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 0-2/10\r\n\
+            Content-Length: 3\r\nConnection: close\r\n\r\nabc";
+
+        let (url, server) = serve(vec![PARTIAL, PARTIAL]);
+        let backend = HttpBackend::try_from(Client::new(), url)
+            .unwrap()
+            .with_header(
+                reqwest::header::AUTHORIZATION,
+                HeaderValue::from_static("Bearer secret"),
+            );
+        backend.read(0, 3).await.unwrap();
+        backend.read(0, 3).await.unwrap();
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        for request in requests {
+            let request = request.to_ascii_lowercase();
+            assert!(request.contains("\r\nauthorization: bearer secret\r\n"));
+            assert!(request.contains("\r\nrange: bytes=0-2\r\n"));
+        }
+    }
+
+    #[tokio::test]
+    async fn request_timeout() {
+        // Accepts connections, but never responds
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/test.pmtiles", listener.local_addr().unwrap());
+
+        let backend = HttpBackend::try_from(Client::new(), url)
+            .unwrap()
+            .with_timeout(Duration::from_millis(100));
+        assert!(matches!(backend.read(0, 3).await, Err(PmtError::Timeout)));
+        drop(listener);
+    }
+
+    #[tokio::test]
+    async fn initial_read_size() {
+        const PARTIAL: &str = "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 0-2/10\r\n\
+            Content-Length: 3\r\nConnection: close\r\n\r\nabc";
+
+        let (url, server) = serve(vec![PARTIAL, PARTIAL]);
+        for size in [512, 0] {
+            let backend = HttpBackend::try_from(Client::new(), &url)
+                .unwrap()
+                .with_initial_read_size(size);
+            assert!(matches!(
+                AsyncPmTilesReader::try_from_source(backend).await,
+                Err(PmtError::InvalidHeader)
+            ));
+        }
+
+        // Sizes below the header size still read the whole header
+        let requests = server.join().unwrap();
+        assert!(requests[0]
+            .to_ascii_lowercase()
+            .contains("range: bytes=0-511\r\n"));
+        assert!(requests[1]
+            .to_ascii_lowercase()
+            .contains("range: bytes=0-126\r\n"));
+    }
+
+    #[tokio::test]
+    async fn range_requests_unsupported() {
+        const FULL: &str = "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\
+            Connection: close\r\n\r\nabcdefghij";
+
+        let (url, server) = serve(vec![FULL]);
+        let backend = HttpBackend::try_from(Client::new(), url).unwrap();
+        assert_eq!(backend.read(2, 3).await.unwrap(), &b"cde"[..]);
+        assert_eq!(backend.read(8, 5).await.unwrap(), &b"ij"[..]);
+        assert!(backend.read(20, 5).await.unwrap().is_empty());
+        assert_eq!(
+            backend.read_ranges(&[(0, 2), (5, 1)]).await.unwrap(),
+            vec![Bytes::from_static(b"ab"), Bytes::from_static(b"f")]
+        );
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn read_empty_ranges() {
+        const PARTIAL: &str = "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 0-2/10\r\n\
+            Content-Length: 3\r\nConnection: close\r\n\r\nabc";
+
+        let (url, server) = serve(vec![PARTIAL]);
+        let backend = HttpBackend::try_from(Client::new(), url).unwrap();
+        // Only empty ranges are served without a request
+        assert_eq!(
+            backend.read_ranges(&[(5, 0), (0, 0)]).await.unwrap(),
+            vec![Bytes::new(), Bytes::new()]
+        );
+        assert_eq!(
+            backend
+                .read_ranges(&[(0, 2), (8, 0), (1, 2)])
+                .await
+                .unwrap(),
+            vec![
+                Bytes::from_static(b"ab"),
+                Bytes::new(),
+                Bytes::from_static(b"bc")
+            ]
+        );
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0]
+            .to_ascii_lowercase()
+            .contains("\r\nrange: bytes=0-1, 1-2\r\n"));
+    }
+
+    #[test]
+    fn if_range_validator() {
+        let validators =
+            |etag: Option<&'static str>, last_modified: Option<&'static str>| Validators {
+                etag: etag.map(HeaderValue::from_static),
+                last_modified: last_modified.map(HeaderValue::from_static),
+            };
+        let date = "Wed, 21 Oct 2015 07:28:00 GMT";
+
+        assert_eq!(
+            validators(Some("\"v1\""), Some(date)).if_range().unwrap(),
+            "\"v1\""
+        );
+        assert_eq!(
+            validators(Some("W/\"v1\""), Some(date)).if_range().unwrap(),
+            date
+        );
+        assert!(validators(Some("W/\"v1\""), None).if_range().is_none());
+        assert!(validators(None, None).if_range().is_none());
+    }
+
+    #[test]
+    fn retry_delays() {
+        for attempt in 0..10 {
+            let delay = retry_delay(attempt);
+            let max = (RETRY_BASE_DELAY * 2_u32.pow(attempt)).min(RETRY_MAX_DELAY);
+            assert!(delay >= max / 2 && delay <= max, "{attempt}: {delay:?}");
+        }
+    }
+
+    #[test]
+    fn parse_multipart() {
+        assert_eq!(
+            multipart_boundary("multipart/byteranges; boundary=3d6b6a416f9b5"),
+            Some("3d6b6a416f9b5")
+        );
+        assert_eq!(
+            multipart_boundary("multipart/byteranges; boundary=\"abc\""),
+            Some("abc")
+        );
+        assert_eq!(multipart_boundary("application/octet-stream"), None);
+
+        let body = Bytes::from_static(
+            b"\r\n--3d6b6a416f9b5\r\n\
+            Content-Type: application/octet-stream\r\n\
+            Content-Range: bytes 0-4/100\r\n\r\n\
+            hello\r\n\
+            --3d6b6a416f9b5\r\n\
+            Content-Type: application/octet-stream\r\n\
+            Content-Range: bytes 50-55/100\r\n\r\n\
+            \r\nw\r\nd\r\n\
+            --3d6b6a416f9b5--\r\n",
+        );
+        let parts = parse_multipart_byteranges(&body, "3d6b6a416f9b5").unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                (0, Bytes::from_static(b"hello")),
+                (50, Bytes::from_static(b"\r\nw\r\nd"))
+            ]
+        );
+
+        assert!(parse_multipart_byteranges(&Bytes::from_static(b"garbage"), "abc").is_err());
+    }
 }
@@ -0,0 +1,85 @@
+use bytes::Bytes;
+
+use crate::async_reader::{AsyncBackend, AsyncPmTilesReader};
+use crate::cache::{DirectoryCache, NoCache};
+use crate::error::PmtResult;
+
+impl AsyncPmTilesReader<MemoryBackend, NoCache> {
+    /// Creates a new `PMTiles` reader from an archive held in memory.
+    ///
+    /// Fails if `data` is not a valid archive.
+    pub async fn from_bytes(data: Bytes) -> PmtResult<Self> {
+        Self::from_cached_bytes(NoCache, data).await
+    }
+}
+
+impl<C: DirectoryCache + Sync + Send> AsyncPmTilesReader<MemoryBackend, C> {
+    /// Creates a new cached `PMTiles` reader from an archive held in memory.
+    ///
+    /// Fails if `data` is not a valid archive.
+    pub async fn from_cached_bytes(cache: C, data: Bytes) -> PmtResult<Self> {
+        let backend = MemoryBackend::new(data);
+
+        Self::try_from_cached_source(backend, cache).await
+    }
+}
+
+/// A backend serving reads from an in-memory buffer.
+///
+/// Reads slice the buffer without copying it.
+pub struct MemoryBackend {
+    data: Bytes,
+}
+
+impl MemoryBackend {
+    #[must_use]
+    pub fn new(data: Bytes) -> Self {
+        Self { data }
+    }
+}
+
+impl AsyncBackend for MemoryBackend {
+    async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        let start = offset.min(self.data.len());
+        let end = offset.saturating_add(length).min(self.data.len());
+        Ok(self.data.slice(start..end))
+    }
+
+    async fn size(&self) -> PmtResult<Option<u64>> {
+        Ok(Some(self.data.len() as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::MemoryBackend;
+    use crate::async_reader::{AsyncBackend as _, AsyncPmTilesReader};
+    use crate::tests::RASTER_FILE;
+    use crate::PmtError;
+
+    #[tokio::test]
+    async fn read_from_bytes() {
+        let data = Bytes::from(std::fs::read(RASTER_FILE).unwrap());
+        let tiles = AsyncPmTilesReader::from_bytes(data).await.unwrap();
+
+        let tile = tiles.get_tile(3, 4, 5).await.unwrap().unwrap();
+        assert_eq!(tile, &include_bytes!("../fixtures/3_4_5.png")[..]);
+        assert!(tiles.get_tile(4, 0, 0).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn read_past_end() {
+        let backend = MemoryBackend::new(Bytes::from_static(b"PMTiles"));
+
+        assert_eq!(backend.read(3, 100).await.unwrap(), &b"iles"[..]);
+        assert_eq!(backend.read(10, 5).await.unwrap(), &b""[..]);
+        assert_eq!(backend.read(3, usize::MAX).await.unwrap(), &b"iles"[..]);
+        assert!(matches!(
+            backend.read_exact(3, 100).await,
+            Err(PmtError::UnexpectedNumberOfBytesReturned(100, 4))
+        ));
+        assert!(AsyncPmTilesReader::from_bytes(Bytes::new()).await.is_err());
+    }
+}
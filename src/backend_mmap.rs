@@ -1,7 +1,7 @@
 use std::io;
 use std::path::Path;
 
-use bytes::{Buf, Bytes};
+use bytes::Bytes;
 use fmmap::tokio::{AsyncMmapFile, AsyncMmapFileExt as _, AsyncOptions};
 
 use crate::async_reader::{AsyncBackend, AsyncPmTilesReader};
@@ -28,16 +28,36 @@ impl<C: DirectoryCache + Sync + Send> AsyncPmTilesReader<MmapBackend, C> {
     }
 }
 
+/// Reads an archive through a read-only memory map of the file.
+///
+/// Reads return slices of the mapping without copying, which keep the mapping
+/// alive even after the backend is dropped.
+///
+/// The kernel's default readahead applies. There is no option to issue `madvise` access
+/// hints such as `MADV_RANDOM`: `fmmap` does not expose the underlying mapping, and creating
+/// the mapping directly requires `unsafe` code, which this crate forbids. To limit readahead
+/// for scattered tile reads, tune it for the device instead, e.g. with `blockdev --setra`.
 pub struct MmapBackend {
-    file: AsyncMmapFile,
+    /// The whole mapped file
+    data: Bytes,
+}
+
+/// Owner of the mapping, giving [`Bytes`] access to the mapped file.
+struct MmapOwner(AsyncMmapFile);
+
+impl AsRef<[u8]> for MmapOwner {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_slice()
+    }
 }
 
 impl MmapBackend {
     pub async fn try_from<P: AsRef<Path>>(p: P) -> PmtResult<Self> {
+        let file = AsyncMmapFile::open_with_options(p, AsyncOptions::new().read(true))
+            .await
+            .map_err(|_| PmtError::UnableToOpenMmapFile)?;
         Ok(Self {
-            file: AsyncMmapFile::open_with_options(p, AsyncOptions::new().read(true))
-                .await
-                .map_err(|_| PmtError::UnableToOpenMmapFile)?,
+            data: Bytes::from_owner(MmapOwner(file)),
         })
     }
 }
@@ -50,20 +70,23 @@ impl From<fmmap::error::Error> for PmtError {
 
 impl AsyncBackend for MmapBackend {
     async fn read_exact(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
-        if self.file.len() >= offset + length {
-            Ok(self.file.reader(offset)?.copy_to_bytes(length))
+        if self.data.len() >= offset.saturating_add(length) {
+            Ok(self.data.slice(offset..offset + length))
         } else {
-            Err(PmtError::Reading(io::Error::from(
-                io::ErrorKind::UnexpectedEof,
-            )))
+            let available = self.data.len().saturating_sub(offset);
+            Err(PmtError::UnexpectedNumberOfBytesReturned(length, available))
         }
     }
 
     async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
-        let reader = self.file.reader(offset)?;
-
-        let read_length = length.min(reader.len());
+        if offset >= self.data.len() {
+            return Ok(Bytes::new());
+        }
+        let end = offset.saturating_add(length).min(self.data.len());
+        Ok(self.data.slice(offset..end))
+    }
 
-        Ok(self.file.reader(offset)?.copy_to_bytes(read_length))
+    async fn size(&self) -> PmtResult<Option<u64>> {
+        Ok(Some(self.data.len() as u64))
     }
 }
@@ -0,0 +1,152 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use object_store::path::Path;
+use object_store::ObjectStore;
+
+use crate::async_reader::{AsyncBackend, AsyncPmTilesReader};
+use crate::cache::{DirectoryCache, NoCache};
+use crate::error::PmtResult;
+
+impl AsyncPmTilesReader<ObjectStoreBackend, NoCache> {
+    /// Creates a new `PMTiles` reader from an object in an [`ObjectStore`],
+    /// e.g. S3, GCS or Azure Blob Storage.
+    ///
+    /// Fails if [path] does not exist or is an invalid archive. (Note: requests are made to validate it.)
+    pub async fn new_with_object_store(store: Arc<dyn ObjectStore>, path: Path) -> PmtResult<Self> {
+        Self::new_with_cached_object_store(NoCache, store, path).await
+    }
+}
+
+impl<C: DirectoryCache + Sync + Send> AsyncPmTilesReader<ObjectStoreBackend, C> {
+    /// Creates a new cached `PMTiles` reader from an object in an [`ObjectStore`],
+    /// e.g. S3, GCS or Azure Blob Storage.
+    ///
+    /// Fails if [path] does not exist or is an invalid archive. (Note: requests are made to validate it.)
+    pub async fn new_with_cached_object_store(
+        cache: C,
+        store: Arc<dyn ObjectStore>,
+        path: Path,
+    ) -> PmtResult<Self> {
+        let backend = ObjectStoreBackend::new(store, path);
+
+        Self::try_from_cached_source(backend, cache).await
+    }
+}
+
+pub struct ObjectStoreBackend {
+    store: Arc<dyn ObjectStore>,
+    path: Path,
+}
+
+impl ObjectStoreBackend {
+    #[must_use]
+    pub fn new(store: Arc<dyn ObjectStore>, path: Path) -> Self {
+        Self { store, path }
+    }
+
+    /// Reads multiple byte ranges, given as `(offset, length)` pairs.
+    ///
+    /// The store may coalesce nearby ranges into fewer requests.
+    /// The returned buffers are in the same order as `ranges`.
+    pub async fn read_ranges(&self, ranges: &[(usize, usize)]) -> PmtResult<Vec<Bytes>> {
+        let ranges: Vec<_> = ranges
+            .iter()
+            .map(|&(offset, length)| offset..offset + length)
+            .collect();
+
+        Ok(self.store.get_ranges(&self.path, &ranges).await?)
+    }
+}
+
+impl AsyncBackend for ObjectStoreBackend {
+    async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
+        match self
+            .store
+            .get_range(&self.path, offset..offset + length)
+            .await
+        {
+            Ok(data) => Ok(data),
+            // Stores reject ranges starting past the end of the object, which reads nothing
+            Err(e) => match self.store.head(&self.path).await {
+                Ok(meta) if offset >= meta.size => Ok(Bytes::new()),
+                _ => Err(e.into()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::ObjectStore as _;
+
+    use super::ObjectStoreBackend;
+    use crate::async_reader::{AsyncBackend as _, AsyncPmTilesReader};
+    use crate::tests::RASTER_FILE;
+    use crate::PmtError;
+
+    async fn store_with(file: &str) -> Arc<InMemory> {
+        let store = Arc::new(InMemory::new());
+        let data = std::fs::read(file).unwrap();
+        store
+            .put(&Path::from("archive.pmtiles"), data.into())
+            .await
+            .unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn read_in_memory_store() {
+        let store = store_with(RASTER_FILE).await;
+        let tiles = AsyncPmTilesReader::new_with_object_store(store, Path::from("archive.pmtiles"))
+            .await
+            .unwrap();
+
+        let tile = tiles.get_tile(3, 4, 5).await.unwrap().unwrap();
+        assert_eq!(tile, &include_bytes!("../fixtures/3_4_5.png")[..]);
+
+        // The archive is much smaller than the initial read
+        let store = store_with("fixtures/leaf.pmtiles").await;
+        let tiles = AsyncPmTilesReader::new_with_object_store(store, Path::from("archive.pmtiles"))
+            .await
+            .unwrap();
+        assert_eq!(tiles.get_tile(1, 1, 0).await.unwrap().unwrap(), &b"4"[..]);
+    }
+
+    #[tokio::test]
+    async fn read_ranges() {
+        let store = store_with(RASTER_FILE).await;
+        let backend = ObjectStoreBackend::new(store, Path::from("archive.pmtiles"));
+
+        let ranges = backend.read_ranges(&[(0, 7), (3, 4)]).await.unwrap();
+        assert_eq!(ranges, vec![&b"PMTiles"[..], &b"iles"[..]]);
+    }
+
+    #[tokio::test]
+    async fn read_past_end() {
+        let store = store_with("fixtures/leaf.pmtiles").await;
+        let backend = ObjectStoreBackend::new(store, Path::from("archive.pmtiles"));
+        let len = std::fs::read("fixtures/leaf.pmtiles").unwrap().len();
+
+        assert_eq!(backend.read(len - 2, 10).await.unwrap().len(), 2);
+        assert!(backend.read(len + 5, 10).await.unwrap().is_empty());
+        assert!(matches!(
+            backend.read_exact(len - 2, 10).await,
+            Err(PmtError::UnexpectedNumberOfBytesReturned(10, 2))
+        ));
+    }
+
+    #[tokio::test]
+    async fn missing_object() {
+        let store = Arc::new(InMemory::new());
+        assert!(
+            AsyncPmTilesReader::new_with_object_store(store, Path::from("missing.pmtiles"))
+                .await
+                .is_err()
+        );
+    }
+}
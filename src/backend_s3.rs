@@ -1,4 +1,5 @@
 use bytes::Bytes;
+use s3::error::S3Error;
 use s3::Bucket;
 
 use crate::{
@@ -49,14 +50,22 @@ impl S3Backend {
 
 impl AsyncBackend for S3Backend {
     async fn read(&self, offset: usize, length: usize) -> PmtResult<Bytes> {
-        let response = self
+        if length == 0 {
+            return Ok(Bytes::new());
+        }
+        let response = match self
             .bucket
             .get_object_range(
                 self.path.as_str(),
                 offset as _,
                 Some((offset + length - 1) as _),
             )
-            .await?;
+            .await
+        {
+            // The range starts at or past the end of the archive
+            Err(S3Error::HttpFailWithBody(416, _)) => return Ok(Bytes::new()),
+            result => result?,
+        };
 
         let response_bytes = response.bytes();
 
@@ -1,6 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::future::Future;
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 
 use crate::directory::{DirEntry, Directory};
 
@@ -70,3 +71,199 @@ impl DirectoryCache for HashMapCache {
         self.cache.write().unwrap().insert(offset, directory);
     }
 }
+
+/// A bounded least-recently-used `PMTiles` directory cache.
+///
+/// Directories are keyed by their offset and evicted once the sum of their
+/// [`Directory::get_approx_byte_size`] exceeds the configured budget.
+pub struct LruDirCache {
+    max_bytes: usize,
+    inner: Mutex<LruInner>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct LruInner {
+    entries: HashMap<usize, (Directory, u64)>,
+    recency: BTreeMap<u64, usize>,
+    tick: u64,
+    bytes: usize,
+}
+
+impl LruInner {
+    fn touch(&mut self, offset: usize) -> Option<&Directory> {
+        self.tick += 1;
+        let (dir, used) = self.entries.get_mut(&offset)?;
+        self.recency.remove(used);
+        self.recency.insert(self.tick, offset);
+        *used = self.tick;
+        Some(dir)
+    }
+
+    fn remove(&mut self, offset: usize) {
+        if let Some((dir, used)) = self.entries.remove(&offset) {
+            self.recency.remove(&used);
+            self.bytes -= dir.get_approx_byte_size();
+        }
+    }
+}
+
+impl LruDirCache {
+    /// Creates a cache holding directories up to roughly `max_bytes` in total.
+    #[must_use]
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            inner: Mutex::default(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Number of lookups answered from the cache.
+    #[must_use]
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Number of lookups for a directory that was not cached.
+    #[must_use]
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Approximate size of all cached directories in bytes.
+    #[must_use]
+    pub fn cached_bytes(&self) -> usize {
+        self.lock().bytes
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LruInner> {
+        // Panic if the lock is poisoned is not something the user can handle
+        #[allow(clippy::unwrap_used)]
+        self.inner.lock().unwrap()
+    }
+}
+
+impl DirectoryCache for LruDirCache {
+    async fn get_dir_entry(&self, offset: usize, tile_id: u64) -> DirCacheResult {
+        let result = self
+            .lock()
+            .touch(offset)
+            .map(|dir| dir.find_tile_id(tile_id).into());
+
+        if let Some(result) = result {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            result
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            DirCacheResult::NotCached
+        }
+    }
+
+    async fn insert_dir(&self, offset: usize, directory: Directory) {
+        let size = directory.get_approx_byte_size();
+        if size > self.max_bytes {
+            return;
+        }
+
+        let mut inner = self.lock();
+        inner.remove(offset);
+        while inner.bytes + size > self.max_bytes {
+            let Some((_, &oldest)) = inner.recency.iter().next() else {
+                break;
+            };
+            inner.remove(oldest);
+        }
+
+        inner.tick += 1;
+        let tick = inner.tick;
+        inner.recency.insert(tick, offset);
+        inner.entries.insert(offset, (directory, tick));
+        inner.bytes += size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DirCacheResult, DirectoryCache as _, LruDirCache};
+    use crate::directory::{DirEntry, Directory};
+
+    fn directory(first_tile_id: u64) -> Directory {
+        Directory::from_entries(
+            (0..64)
+                .map(|i| DirEntry {
+                    tile_id: first_tile_id + i,
+                    offset: i * 10,
+                    length: 10,
+                    run_length: 1,
+                })
+                .collect(),
+        )
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used() {
+        let size = directory(0).get_approx_byte_size();
+        let cache = LruDirCache::new(size * 2);
+
+        cache.insert_dir(0, directory(0)).await;
+        cache.insert_dir(1, directory(100)).await;
+        // Touch 0 so that 1 becomes the eviction candidate
+        assert!(matches!(
+            cache.get_dir_entry(0, 5).await,
+            DirCacheResult::Found(_)
+        ));
+        cache.insert_dir(2, directory(200)).await;
+
+        assert!(matches!(
+            cache.get_dir_entry(1, 105).await,
+            DirCacheResult::NotCached
+        ));
+        assert!(matches!(
+            cache.get_dir_entry(0, 5).await,
+            DirCacheResult::Found(_)
+        ));
+        assert!(matches!(
+            cache.get_dir_entry(2, 1000).await,
+            DirCacheResult::NotFound
+        ));
+        assert_eq!(cache.cached_bytes(), size * 2);
+        assert_eq!((cache.hits(), cache.misses()), (3, 1));
+
+        // Re-inserting an existing key must not double count its size
+        cache.insert_dir(2, directory(200)).await;
+        assert_eq!(cache.cached_bytes(), size * 2);
+    }
+
+    #[tokio::test]
+    async fn random_workload_hit_rate() {
+        const LEAVES: u64 = 32;
+        let size = directory(0).get_approx_byte_size();
+
+        let mut hit_rates = Vec::new();
+        for budget in [0, 4, 16, 32] {
+            let cache = LruDirCache::new(size * budget);
+            // Simple LCG to get a reproducible random workload
+            let mut state = 42_u64;
+            for _ in 0..2000 {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1);
+                // Skew towards low leaves, as real workloads favor popular areas
+                let leaf = ((state >> 33) % LEAVES) * ((state >> 20) % LEAVES) / LEAVES;
+                let offset = usize::try_from(leaf).unwrap();
+                if let DirCacheResult::NotCached = cache.get_dir_entry(offset, leaf * 100).await {
+                    cache.insert_dir(offset, directory(leaf * 100)).await;
+                }
+            }
+            assert!(cache.cached_bytes() <= size * budget);
+            hit_rates.push(cache.hits() * 100 / (cache.hits() + cache.misses()));
+        }
+
+        assert_eq!(hit_rates[0], 0);
+        assert!(hit_rates.windows(2).all(|w| w[0] < w[1]), "{hit_rates:?}");
+        assert!(hit_rates[3] > 95, "{hit_rates:?}");
+    }
+}
@@ -1,15 +1,44 @@
+use std::cmp::Ordering;
 use std::fmt::{Debug, Formatter};
+use std::io::Write;
 
 use bytes::{Buf, Bytes};
-use varint_rs::VarintReader;
+use varint_rs::VarintWriter;
 
-use crate::error::PmtError;
+use crate::error::{PmtError, PmtResult};
+use crate::writer::WriteTo;
 
-#[derive(Clone)]
+/// Maximum number of entries of a leaf directory created by [`Directory::split_into_leaves`].
+/// A serialized entry takes at most 30 bytes, so the leaf length always fits into `u32`.
+const MAX_LEAF_ENTRIES: usize = (u32::MAX as usize - 10) / 30;
+
+#[derive(Clone, PartialEq, Eq)]
 pub struct Directory {
     entries: Vec<DirEntry>,
 }
 
+/// Result of [`Directory::find_entry_for`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirLookup<'a> {
+    /// The tile entry containing the tile ID.
+    Tile(&'a DirEntry),
+    /// The leaf directory that has to be searched for the tile ID.
+    Leaf(&'a DirEntry),
+    /// The tile ID is not part of the directory.
+    NotFound,
+}
+
+/// A tile found by [`Directory::resolve`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedTile {
+    /// The queried tile ID, which may lie inside of the run of the entry.
+    pub tile_id: u64,
+    /// Byte offset of the tile data, relative to the tile data section.
+    pub offset: u64,
+    /// Length of the tile data in bytes.
+    pub length: u32,
+}
+
 impl Debug for Directory {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!("Directory [entries: {}]", self.entries.len()))
@@ -17,6 +46,32 @@ impl Debug for Directory {
 }
 
 impl Directory {
+    #[cfg(any(test, feature = "__async", feature = "write"))]
+    pub(crate) fn from_entries(entries: Vec<DirEntry>) -> Self {
+        Self { entries }
+    }
+
+    /// Create a directory from entries sorted by tile ID.
+    ///
+    /// Returns [`PmtError::UnsortedEntries`] if an entry does not follow its predecessor,
+    /// [`PmtError::DuplicateTile`] if an entry starts within the run of its predecessor, and
+    /// [`PmtError::InvalidEntry`] if the tile IDs or data bytes of an entry exceed the `u64` range.
+    pub fn from_sorted_entries(entries: Vec<DirEntry>) -> PmtResult<Self> {
+        for entry in &entries {
+            entry.check_ranges()?;
+        }
+        for pair in entries.windows(2) {
+            let (last, entry) = (&pair[0], &pair[1]);
+            if entry.tile_id < last.tile_id {
+                return Err(PmtError::UnsortedEntries(entry.tile_id));
+            }
+            if last.next_tile_id() > entry.tile_id {
+                return Err(PmtError::DuplicateTile(entry.tile_id));
+            }
+        }
+        Ok(Self { entries })
+    }
+
     /// Find the directory entry for a given tile ID.
     #[must_use]
     pub fn find_tile_id(&self, tile_id: u64) -> Option<&DirEntry> {
@@ -38,6 +93,246 @@ impl Directory {
         }
     }
 
+    /// Find the tile data of a tile ID stored in this directory, also for tiles inside of a run.
+    ///
+    /// Returns `None` if the tile is not part of the directory or is stored in a leaf directory,
+    /// see [`Directory::find_entry_for`].
+    #[must_use]
+    pub fn resolve(&self, tile_id: u64) -> Option<ResolvedTile> {
+        match self.find_entry_for(tile_id) {
+            DirLookup::Tile(entry) => Some(ResolvedTile {
+                tile_id,
+                offset: entry.offset,
+                length: entry.length,
+            }),
+            DirLookup::Leaf(_) | DirLookup::NotFound => None,
+        }
+    }
+
+    /// Find the entry a tile ID falls under, telling tile entries apart from leaf directories.
+    ///
+    /// A [`DirLookup::Leaf`] result means the tile, if present, is stored in that leaf directory.
+    #[must_use]
+    pub fn find_entry_for(&self, tile_id: u64) -> DirLookup<'_> {
+        match self.find_tile_id(tile_id) {
+            Some(entry) if entry.is_leaf() => DirLookup::Leaf(entry),
+            Some(entry) => DirLookup::Tile(entry),
+            None => DirLookup::NotFound,
+        }
+    }
+
+    /// Iterate over all entries of the directory, ordered by tile ID.
+    pub fn iter(&self) -> impl Iterator<Item = &DirEntry> {
+        self.entries.iter()
+    }
+
+    /// Iterate over every tile ID covered by the tile entries of this directory,
+    /// expanding run-length encoded entries. Leaf directory entries are skipped.
+    pub fn iter_tile_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.entries
+            .iter()
+            .filter(|e| !e.is_leaf())
+            .flat_map(|e| e.tile_id..e.next_tile_id())
+    }
+
+    /// Merge the entries of another directory into this one, keeping entries sorted by tile ID.
+    ///
+    /// Identical entries present in both directories are only kept once. If both directories
+    /// contain different entries for the same tile ID, [`PmtError::DuplicateTile`] is returned
+    /// and this directory is left unchanged.
+    pub fn merge(&mut self, other: &Directory) -> PmtResult<()> {
+        let mut merged: Vec<DirEntry> =
+            Vec::with_capacity(self.entries.len() + other.entries.len());
+        let mut ours = self.entries.iter().peekable();
+        let mut theirs = other.entries.iter().peekable();
+
+        loop {
+            let entry = match (ours.peek(), theirs.peek()) {
+                (Some(a), Some(b)) => match a.tile_id.cmp(&b.tile_id) {
+                    Ordering::Less => ours.next(),
+                    Ordering::Greater => theirs.next(),
+                    Ordering::Equal if a == b => {
+                        theirs.next();
+                        ours.next()
+                    }
+                    Ordering::Equal => return Err(PmtError::DuplicateTile(a.tile_id)),
+                },
+                (Some(_), None) => ours.next(),
+                (None, Some(_)) => theirs.next(),
+                (None, None) => break,
+            };
+            let Some(entry) = entry else { break };
+
+            // Run-length encoded entries must not cover tile IDs of the following entry
+            if let Some(last) = merged.last() {
+                if last.next_tile_id() > entry.tile_id {
+                    return Err(PmtError::DuplicateTile(entry.tile_id));
+                }
+            }
+            merged.push(entry.clone());
+        }
+
+        self.entries = merged;
+        Ok(())
+    }
+
+    /// Split a flat directory into a root directory and leaf directories.
+    ///
+    /// The entries are partitioned into consecutive leaves of equal size, so that the root
+    /// directory has at most `max_root_entries` entries. Each root entry points to one leaf,
+    /// using the offset and length of the serialized (uncompressed) leaf within the leaf
+    /// directories section. If the directory already fits, it is returned as is with no leaves.
+    ///
+    /// Leaves hold at most about 143 million entries, so that their length fits into a directory
+    /// entry. Only for directories of more than `max_root_entries` times this many entries,
+    /// the root directory has more entries than requested.
+    #[must_use]
+    pub fn split_into_leaves(&self, max_root_entries: usize) -> (Directory, Vec<Directory>) {
+        let Ok((root, leaves, _)) =
+            self.split_into_leaves_with(max_root_entries, |leaf| Ok(leaf.to_vec()))
+        else {
+            unreachable!("serialized leaves of at most MAX_LEAF_ENTRIES entries fit into u32");
+        };
+        (root, leaves)
+    }
+
+    /// Split into a root directory and leaf directories, using `serialize` to produce
+    /// the bytes of each leaf as stored in the leaf directories section.
+    /// Also returns the serialized leaf directories section.
+    ///
+    /// Fails with [`PmtError::InvalidEntry`] if a serialized leaf does not fit into 4 GiB.
+    pub(crate) fn split_into_leaves_with(
+        &self,
+        max_root_entries: usize,
+        mut serialize: impl FnMut(&Directory) -> PmtResult<Vec<u8>>,
+    ) -> PmtResult<(Directory, Vec<Directory>, Vec<u8>)> {
+        let max_root_entries = max_root_entries.max(1);
+        if self.entries.len() <= max_root_entries {
+            return Ok((self.clone(), Vec::new(), Vec::new()));
+        }
+
+        let leaf_size = self
+            .entries
+            .len()
+            .div_ceil(max_root_entries)
+            .min(MAX_LEAF_ENTRIES);
+        let n_leaves = self.entries.len().div_ceil(leaf_size);
+        let mut root_entries = Vec::with_capacity(n_leaves);
+        let mut leaves = Vec::with_capacity(n_leaves);
+        let mut leaves_section = Vec::new();
+        let mut offset = 0;
+        for chunk in self.entries.chunks(leaf_size) {
+            let leaf = Directory {
+                entries: chunk.to_vec(),
+            };
+            let bytes = serialize(&leaf)?;
+            let length = u32::try_from(bytes.len()).map_err(|_| PmtError::InvalidEntry)?;
+
+            root_entries.push(DirEntry {
+                tile_id: chunk[0].tile_id,
+                offset,
+                length,
+                run_length: 0,
+            });
+            offset += u64::from(length);
+            leaves.push(leaf);
+            leaves_section.extend(bytes);
+        }
+
+        Ok((
+            Directory {
+                entries: root_entries,
+            },
+            leaves,
+            leaves_section,
+        ))
+    }
+
+    /// Check whether the tile data referenced by this directory is clustered, i.e. stored
+    /// in tile ID order without gaps.
+    ///
+    /// The offsets must be monotonically non-decreasing in entry order, with the data of each
+    /// tile entry starting exactly where the data of the previous one ended. Leaf directory
+    /// entries are ignored.
+    ///
+    /// This is stricter than the clustered flag of the header, which writers also set for
+    /// deduplicated tiles referencing data stored before.
+    #[must_use]
+    pub fn is_clustered(&self) -> bool {
+        let mut tiles = self.entries.iter().filter(|e| !e.is_leaf());
+        let Some(first) = tiles.next() else {
+            return true;
+        };
+        let mut end = first.data_end();
+        for entry in tiles {
+            if entry.offset != end {
+                return false;
+            }
+            end = entry.data_end();
+        }
+        true
+    }
+
+    /// Find tile entries whose data byte ranges `[offset, offset + length)` intersect.
+    ///
+    /// Returns pairs of tile IDs, the smaller ID first, sorted. Entries referencing exactly the
+    /// same byte range are deduplicated tiles and are not reported, neither are leaf directory
+    /// entries or empty tiles.
+    #[must_use]
+    pub fn find_overlaps(&self) -> Vec<(u64, u64)> {
+        let mut by_offset: Vec<&DirEntry> = self
+            .entries
+            .iter()
+            .filter(|e| !e.is_leaf() && e.length > 0)
+            .collect();
+        by_offset.sort_by_key(|e| (e.offset, e.length));
+
+        let end = |e: &DirEntry| e.offset.saturating_add(u64::from(e.length));
+        let mut overlaps = Vec::new();
+        // Entries whose data may still intersect the following entries
+        let mut active: Vec<&DirEntry> = Vec::new();
+        for entry in by_offset {
+            active.retain(|a| end(a) > entry.offset);
+            for other in &active {
+                if (other.offset, other.length) != (entry.offset, entry.length) {
+                    let (a, b) = (other.tile_id, entry.tile_id);
+                    overlaps.push((a.min(b), a.max(b)));
+                }
+            }
+            active.push(entry);
+        }
+
+        overlaps.sort_unstable();
+        overlaps
+    }
+
+    /// Number of bytes written by serializing the directory with [`WriteTo::write_to`],
+    /// before any compression, computed without serializing it.
+    #[must_use]
+    pub fn serialized_len(&self) -> usize {
+        let mut len = varint_len(self.entries.len() as u64);
+        let mut last_entry: Option<&DirEntry> = None;
+        for entry in &self.entries {
+            let offset_to_write = offset_to_write(last_entry, entry);
+            let tile_id_delta = entry.tile_id - last_entry.map_or(0, |e| e.tile_id);
+            len += varint_len(tile_id_delta)
+                + varint_len(entry.run_length.into())
+                + varint_len(entry.length.into())
+                + varint_len(offset_to_write);
+            last_entry = Some(entry);
+        }
+        len
+    }
+
+    /// Serialize the directory into a new buffer.
+    fn to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.serialized_len());
+        // Writing into a `Vec` cannot fail
+        #[allow(clippy::unwrap_used)]
+        self.write_to(&mut buf).unwrap();
+        buf
+    }
+
     /// Get an estimated byte size of the directory object. Use this for cache eviction.
     #[must_use]
     pub fn get_approx_byte_size(&self) -> usize {
@@ -45,50 +340,157 @@ impl Directory {
     }
 }
 
+/// Reads a varint, rejecting truncated input and values that do not fit into 64 bits.
+fn read_varint(buffer: &mut Bytes) -> PmtResult<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        if !buffer.has_remaining() {
+            return Err(PmtError::Reading(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+        let byte = buffer.get_u8();
+        let bits = u64::from(byte & 0x7f);
+        if shift == 63 && bits > 1 {
+            return Err(PmtError::InvalidEntry);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(PmtError::InvalidEntry)
+}
+
+/// Number of bytes of the varint encoding of `value`.
+fn varint_len(value: u64) -> usize {
+    let bits = 64 - value.leading_zeros() as usize;
+    bits.div_ceil(7).max(1)
+}
+
+fn read_u32_varint(buffer: &mut Bytes) -> PmtResult<u32> {
+    u32::try_from(read_varint(buffer)?).map_err(|_| PmtError::InvalidEntry)
+}
+
 impl TryFrom<Bytes> for Directory {
     type Error = PmtError;
 
     fn try_from(buffer: Bytes) -> Result<Self, Self::Error> {
-        let mut buffer = buffer.reader();
-        let n_entries = buffer.read_usize_varint()?;
+        let mut buffer = buffer;
+        let n_entries =
+            usize::try_from(read_varint(&mut buffer)?).map_err(|_| PmtError::InvalidEntry)?;
 
+        // Each entry takes at least one byte for each of its four fields,
+        // so a larger count cannot be valid and must not be allocated.
+        if n_entries > buffer.remaining() / 4 {
+            return Err(PmtError::InvalidEntry);
+        }
         let mut entries = vec![DirEntry::default(); n_entries];
 
         // Read tile IDs
-        let mut next_tile_id = 0;
+        let mut next_tile_id: u64 = 0;
         for entry in &mut entries {
-            next_tile_id += buffer.read_u64_varint()?;
+            next_tile_id = next_tile_id
+                .checked_add(read_varint(&mut buffer)?)
+                .ok_or(PmtError::InvalidEntry)?;
             entry.tile_id = next_tile_id;
         }
 
         // Read Run Lengths
         for entry in &mut entries {
-            entry.run_length = buffer.read_u32_varint()?;
+            entry.run_length = read_u32_varint(&mut buffer)?;
         }
 
         // Read Lengths
         for entry in &mut entries {
-            entry.length = buffer.read_u32_varint()?;
+            entry.length = read_u32_varint(&mut buffer)?;
         }
 
         // Read Offsets
         let mut last_entry: Option<&DirEntry> = None;
         for entry in &mut entries {
-            let offset = buffer.read_u64_varint()?;
+            let offset = read_varint(&mut buffer)?;
             entry.offset = if offset == 0 {
                 let e = last_entry.ok_or(PmtError::InvalidEntry)?;
-                e.offset + u64::from(e.length)
+                e.offset
+                    .checked_add(u64::from(e.length))
+                    .ok_or(PmtError::InvalidEntry)?
             } else {
                 offset - 1
             };
             last_entry = Some(entry);
         }
+        for entry in &entries {
+            entry.check_ranges()?;
+        }
 
         Ok(Directory { entries })
     }
 }
 
-#[derive(Clone, Default, Debug)]
+impl WriteTo for Directory {
+    fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        // Write number of entries
+        writer.write_usize_varint(self.entries.len())?;
+
+        // Write tile IDs
+        let mut last_tile_id = 0;
+        for entry in &self.entries {
+            writer.write_u64_varint(entry.tile_id - last_tile_id)?;
+            last_tile_id = entry.tile_id;
+        }
+
+        // Write Run Lengths
+        for entry in &self.entries {
+            writer.write_u32_varint(entry.run_length)?;
+        }
+
+        // Write Lengths
+        for entry in &self.entries {
+            writer.write_u32_varint(entry.length)?;
+        }
+
+        // Write Offsets
+        let mut last_entry: Option<&DirEntry> = None;
+        for entry in &self.entries {
+            writer.write_u64_varint(offset_to_write(last_entry, entry))?;
+            last_entry = Some(entry);
+        }
+
+        Ok(())
+    }
+}
+
+/// The serialized offset of `entry`: 0 if its data directly follows the data of `last`,
+/// otherwise its offset plus 1.
+fn offset_to_write(last: Option<&DirEntry>, entry: &DirEntry) -> u64 {
+    match last {
+        Some(last) if entry.offset == last.data_end() => 0,
+        _ => entry.offset + 1,
+    }
+}
+
+/// Whether the tile data of `entries` is clustered as required by the clustered flag of the
+/// header: each tile entry starts where the data of the previous new tile ended, or references
+/// data that was already referenced before (deduplicated tiles).
+#[cfg(any(test, feature = "write"))]
+pub(crate) fn entries_clustered(entries: &[DirEntry]) -> PmtResult<bool> {
+    // Byte range of all tile data seen so far
+    let mut data_range: Option<(u64, u64)> = None;
+    for entry in entries.iter().filter(|e| !e.is_leaf()) {
+        let entry_end = entry
+            .offset
+            .checked_add(u64::from(entry.length))
+            .ok_or(PmtError::InvalidEntry)?;
+        data_range = match data_range {
+            None => Some((entry.offset, entry_end)),
+            Some((start, end)) if entry.offset == end => Some((start, entry_end)),
+            Some((start, end)) if entry.offset >= start && entry_end <= end => data_range,
+            Some(_) => return Ok(false),
+        };
+    }
+    Ok(true)
+}
+
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
 pub struct DirEntry {
     pub(crate) tile_id: u64,
     pub(crate) offset: u64,
@@ -97,24 +499,88 @@ pub struct DirEntry {
 }
 
 impl DirEntry {
-    pub(crate) fn is_leaf(&self) -> bool {
+    /// Create an entry for `run_length` consecutive tiles starting at `tile_id`,
+    /// or for a leaf directory if `run_length` is 0.
+    #[must_use]
+    pub const fn new(tile_id: u64, offset: u64, length: u32, run_length: u32) -> Self {
+        Self {
+            tile_id,
+            offset,
+            length,
+            run_length,
+        }
+    }
+
+    /// The first tile ID covered by this entry.
+    #[must_use]
+    pub fn tile_id(&self) -> u64 {
+        self.tile_id
+    }
+
+    /// Byte offset of the entry, relative to the tile data section for tiles,
+    /// or to the leaf directories section for leaf directories.
+    #[must_use]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Length of the tile data or leaf directory in bytes.
+    #[must_use]
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// Number of consecutive tile IDs sharing the same data, or 0 for a leaf directory.
+    #[must_use]
+    pub fn run_length(&self) -> u32 {
+        self.run_length
+    }
+
+    /// Whether this entry points to a leaf directory rather than tile data.
+    #[must_use]
+    pub fn is_leaf(&self) -> bool {
         self.run_length == 0
     }
+
+    /// The first tile ID after this entry. A leaf directory entry covers its tile ID only.
+    ///
+    /// Entries of a [`Directory`] are checked with [`Self::check_ranges`] not to overflow.
+    pub(crate) fn next_tile_id(&self) -> u64 {
+        self.tile_id + u64::from(self.run_length.max(1))
+    }
+
+    /// The first byte after the data of this entry.
+    pub(crate) fn data_end(&self) -> u64 {
+        self.offset + u64::from(self.length)
+    }
+
+    /// Check that the tile IDs and data bytes of this entry, as well as its serialized offset
+    /// plus 1, are within the `u64` range.
+    fn check_ranges(&self) -> PmtResult<()> {
+        let tile_ids = self.tile_id.checked_add(u64::from(self.run_length.max(1)));
+        let data = self.offset.checked_add(u64::from(self.length.max(1)));
+        match (tile_ids, data) {
+            (Some(_), Some(_)) => Ok(()),
+            _ => Err(PmtError::InvalidEntry),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::io::{BufReader, Read, Write};
 
-    use bytes::BytesMut;
+    use bytes::{Bytes, BytesMut};
+    use varint_rs::VarintWriter as _;
 
-    use super::Directory;
+    use super::{entries_clustered, DirEntry, DirLookup, Directory, ResolvedTile};
+    use crate::error::PmtError;
     use crate::header::HEADER_SIZE;
     use crate::tests::RASTER_FILE;
+    use crate::writer::WriteTo;
     use crate::Header;
 
-    #[test]
-    fn read_root_directory() {
+    fn read_raster_root_directory() -> Directory {
         let test_file = std::fs::File::open(RASTER_FILE).unwrap();
         let mut reader = BufReader::new(test_file);
 
@@ -131,7 +597,12 @@ mod tests {
             gunzip.write_all(&directory_bytes).unwrap();
         }
 
-        let directory = Directory::try_from(decompressed.freeze()).unwrap();
+        Directory::try_from(decompressed.freeze()).unwrap()
+    }
+
+    #[test]
+    fn read_root_directory() {
+        let directory = read_raster_root_directory();
 
         assert_eq!(directory.entries.len(), 84);
         // Note: this is not true for all tiles, just the first few...
@@ -145,4 +616,427 @@ mod tests {
         assert_eq!(directory.entries[58].offset, 422_070);
         assert_eq!(directory.entries[58].length, 850);
     }
+
+    #[test]
+    fn iter_entries() {
+        let directory = read_raster_root_directory();
+
+        assert_eq!(directory.iter().count(), 84);
+        assert!(directory.iter().all(|e| !e.is_leaf()));
+        assert!(directory
+            .iter()
+            .zip(directory.iter().skip(1))
+            .all(|(a, b)| a.tile_id() < b.tile_id()));
+
+        let entry = directory.iter().nth(58).unwrap();
+        assert_eq!(entry.tile_id(), 58);
+        assert_eq!(entry.run_length(), 2);
+        assert_eq!(entry.offset(), 422_070);
+        assert_eq!(entry.length(), 850);
+    }
+
+    #[test]
+    fn iter_tile_ids() {
+        let directory = read_raster_root_directory();
+
+        // The raster fixture contains all 85 tiles of zoom levels 0 to 3 in 84 entries
+        let tile_ids: Vec<u64> = directory.iter_tile_ids().collect();
+        assert_eq!(tile_ids, (0..85).collect::<Vec<_>>());
+
+        let leaf_only = Directory {
+            entries: vec![DirEntry {
+                tile_id: 5,
+                offset: 0,
+                length: 10,
+                run_length: 0,
+            }],
+        };
+        assert_eq!(leaf_only.iter_tile_ids().count(), 0);
+    }
+
+    fn entry(tile_id: u64, offset: u64, run_length: u32) -> DirEntry {
+        DirEntry {
+            tile_id,
+            offset,
+            length: 10,
+            run_length,
+        }
+    }
+
+    #[test]
+    fn find_entry_for() {
+        let dir = Directory {
+            entries: vec![entry(0, 0, 2), entry(5, 0, 0), entry(20, 30, 1)],
+        };
+
+        assert_eq!(dir.find_entry_for(1), DirLookup::Tile(&dir.entries[0]));
+        assert_eq!(dir.find_entry_for(3), DirLookup::NotFound);
+        assert_eq!(dir.find_entry_for(5), DirLookup::Leaf(&dir.entries[1]));
+        assert_eq!(dir.find_entry_for(19), DirLookup::Leaf(&dir.entries[1]));
+        assert_eq!(dir.find_entry_for(20), DirLookup::Tile(&dir.entries[2]));
+        assert_eq!(dir.find_entry_for(21), DirLookup::NotFound);
+    }
+
+    #[test]
+    fn find_tile_id_at_the_last_run() {
+        let dir = Directory {
+            entries: vec![entry(0, 0, 1), entry(10, 1, 3)],
+        };
+
+        assert_eq!(dir.find_tile_id(10), Some(&dir.entries[1]));
+        assert_eq!(dir.find_tile_id(12), Some(&dir.entries[1]));
+        // Just past the run of the last entry
+        assert_eq!(dir.find_tile_id(13), None);
+        assert_eq!(dir.find_tile_id(u64::MAX), None);
+
+        let empty = Directory { entries: vec![] };
+        assert_eq!(empty.find_tile_id(0), None);
+        assert_eq!(empty.find_tile_id(10), None);
+    }
+
+    #[test]
+    fn resolve_tiles_in_runs() {
+        let dir = Directory {
+            entries: vec![entry(0, 0, 1), entry(4, 0, 0), entry(10, 30, 3)],
+        };
+
+        assert_eq!(
+            dir.resolve(11),
+            Some(ResolvedTile {
+                tile_id: 11,
+                offset: 30,
+                length: 10,
+            })
+        );
+        assert_eq!(dir.resolve(0).unwrap().tile_id, 0);
+        assert_eq!(dir.resolve(2), None);
+        // Stored in the leaf directory
+        assert_eq!(dir.resolve(5), None);
+        assert_eq!(dir.resolve(13), None);
+    }
+
+    #[test]
+    fn find_overlaps() {
+        let tile = |tile_id, offset, length| DirEntry {
+            tile_id,
+            offset,
+            length,
+            run_length: 1,
+        };
+        let dir = Directory {
+            entries: vec![
+                tile(0, 0, 10),
+                tile(1, 10, 10),
+                // Deduplicated data of tile 0
+                tile(2, 0, 10),
+                tile(3, 15, 10),
+                tile(4, 30, 5),
+                // Leaf directory offsets are relative to another section
+                entry(5, 0, 0),
+                tile(6, 32, 0),
+                tile(7, 5, 1),
+            ],
+        };
+
+        assert_eq!(dir.find_overlaps(), vec![(0, 7), (1, 3), (2, 7)]);
+        assert!(read_raster_root_directory().find_overlaps().is_empty());
+    }
+
+    #[test]
+    fn merge_directories() {
+        let mut dir = Directory {
+            entries: vec![entry(0, 0, 1), entry(2, 10, 1), entry(5, 20, 2)],
+        };
+        let other = Directory {
+            entries: vec![entry(1, 30, 1), entry(2, 10, 1), entry(7, 40, 1)],
+        };
+
+        dir.merge(&other).unwrap();
+
+        let tile_ids: Vec<u64> = dir.iter().map(DirEntry::tile_id).collect();
+        assert_eq!(tile_ids, vec![0, 1, 2, 5, 7]);
+    }
+
+    #[test]
+    fn merge_duplicate_tile() {
+        let mut dir = Directory {
+            entries: vec![entry(0, 0, 1), entry(2, 10, 1)],
+        };
+        let other = Directory {
+            entries: vec![entry(2, 20, 1)],
+        };
+
+        assert!(matches!(dir.merge(&other), Err(PmtError::DuplicateTile(2))));
+        // A failed merge leaves the directory untouched
+        assert_eq!(dir.entries, vec![entry(0, 0, 1), entry(2, 10, 1)]);
+
+        // Tile 6 is covered by the run of tile 5
+        let mut dir = Directory {
+            entries: vec![entry(5, 0, 2)],
+        };
+        let other = Directory {
+            entries: vec![entry(6, 20, 1)],
+        };
+        assert!(matches!(dir.merge(&other), Err(PmtError::DuplicateTile(6))));
+    }
+
+    #[test]
+    fn write_directory() {
+        let root_dir = read_raster_root_directory();
+
+        let mut buf = Vec::new();
+        root_dir.write_to(&mut buf).unwrap();
+        let dir = Directory::try_from(Bytes::from(buf)).unwrap();
+
+        assert_eq!(dir, root_dir);
+    }
+
+    #[test]
+    fn serialized_len() {
+        let dirs = [
+            read_raster_root_directory(),
+            Directory::from_entries(Vec::new()),
+            Directory::from_entries(vec![
+                entry(0, 0, 1),
+                entry(127, 1, 128),
+                entry(1 << 40, 1 << 30, u32::MAX),
+                DirEntry::new((1 << 40) + 1, 0, 16_384, 0),
+            ]),
+        ];
+        for dir in dirs {
+            let mut buf = Vec::new();
+            dir.write_to(&mut buf).unwrap();
+            assert_eq!(dir.serialized_len(), buf.len());
+        }
+    }
+
+    #[test]
+    fn new_dir_entry() {
+        const LEAF: DirEntry = DirEntry::new(64, 100, 20, 0);
+        assert!(LEAF.is_leaf());
+
+        let entry = DirEntry::new(5, 1000, 42, 3);
+        assert_eq!(
+            (
+                entry.tile_id(),
+                entry.offset(),
+                entry.length(),
+                entry.run_length()
+            ),
+            (5, 1000, 42, 3)
+        );
+        assert!(!entry.is_leaf());
+    }
+
+    #[test]
+    fn from_sorted_entries() {
+        let entries = vec![
+            DirEntry::new(0, 0, 10, 1),
+            DirEntry::new(1, 10, 10, 3),
+            DirEntry::new(4, 0, 30, 0),
+        ];
+        let dir = Directory::from_sorted_entries(entries.clone()).unwrap();
+        let mut buf = Vec::new();
+        dir.write_to(&mut buf).unwrap();
+        let dir = Directory::try_from(Bytes::from(buf)).unwrap();
+        assert_eq!(dir.entries, entries);
+
+        assert!(matches!(
+            Directory::from_sorted_entries(vec![entry(5, 0, 1), entry(3, 10, 1)]),
+            Err(PmtError::UnsortedEntries(3))
+        ));
+        // Tile 3 is covered by the run of tile 1
+        assert!(matches!(
+            Directory::from_sorted_entries(vec![entry(1, 0, 3), entry(3, 10, 1)]),
+            Err(PmtError::DuplicateTile(3))
+        ));
+        // Tile IDs or data bytes exceeding the u64 range
+        for entry in [
+            DirEntry::new(u64::MAX, 0, 10, 2),
+            DirEntry::new(0, u64::MAX, 10, 1),
+            DirEntry::new(0, u64::MAX, 0, 1),
+        ] {
+            assert!(matches!(
+                Directory::from_sorted_entries(vec![entry]),
+                Err(PmtError::InvalidEntry)
+            ));
+        }
+        assert!(matches!(
+            Directory::from_sorted_entries(vec![entry(1, 0, 0), entry(1, 10, 1)]),
+            Err(PmtError::DuplicateTile(1))
+        ));
+    }
+
+    #[test]
+    fn split_directory_into_leaves() {
+        let dir = Directory {
+            entries: (0..100).map(|i| entry(i * 2, i * 10, 1)).collect(),
+        };
+
+        let (root, leaves) = dir.split_into_leaves(200);
+        assert_eq!(root, dir);
+        assert!(leaves.is_empty());
+
+        let (root, leaves) = dir.split_into_leaves(8);
+        assert_eq!(root.entries.len(), 8);
+        assert_eq!(leaves.len(), 8);
+        assert!(root.iter().all(DirEntry::is_leaf));
+
+        let mut offset = 0;
+        for (root_entry, leaf) in root.iter().zip(&leaves) {
+            assert!(leaf.entries.len() <= 13);
+            assert_eq!(root_entry.tile_id, leaf.entries[0].tile_id);
+            assert_eq!(root_entry.offset, offset);
+
+            let mut buf = Vec::new();
+            leaf.write_to(&mut buf).unwrap();
+            assert_eq!(u64::from(root_entry.length), buf.len() as u64);
+            offset += buf.len() as u64;
+
+            let parsed = Directory::try_from(Bytes::from(buf)).unwrap();
+            assert_eq!(&parsed, leaf);
+        }
+
+        let all_entries: Vec<DirEntry> = leaves.into_iter().flat_map(|l| l.entries).collect();
+        assert_eq!(all_entries, dir.entries);
+    }
+
+    #[test]
+    fn clustered_directory() {
+        // The raster fixture references some tile data twice, as allowed by its header flag
+        let raster = read_raster_root_directory();
+        assert!(!raster.is_clustered());
+        assert!(entries_clustered(&raster.entries).unwrap());
+
+        let dir = Directory::from_entries(vec![entry(0, 0, 1), entry(1, 10, 1), entry(2, 20, 3)]);
+        assert!(dir.is_clustered());
+
+        // Deduplicated tile pointing back at already written data
+        let dir = Directory::from_entries(vec![entry(0, 0, 1), entry(1, 10, 1), entry(2, 0, 1)]);
+        assert!(!dir.is_clustered());
+        assert!(entries_clustered(&dir.entries).unwrap());
+
+        // Gap between tiles
+        let dir = Directory::from_entries(vec![entry(0, 0, 1), entry(1, 15, 1)]);
+        assert!(!dir.is_clustered());
+
+        // Tile data out of tile ID order
+        let dir = Directory::from_entries(vec![entry(0, 10, 1), entry(1, 20, 1), entry(2, 0, 1)]);
+        assert!(!dir.is_clustered());
+        let dir = Directory::from_entries(vec![entry(0, 10, 1), entry(1, 0, 1), entry(2, 20, 1)]);
+        assert!(!dir.is_clustered());
+
+        // Tile data exceeding the byte range
+        assert!(matches!(
+            entries_clustered(&[entry(0, u64::MAX, 1)]),
+            Err(PmtError::InvalidEntry)
+        ));
+    }
+
+    fn encode_directory(fields: &[&[u64]]) -> Bytes {
+        let mut buf = Vec::new();
+        for field in fields {
+            for &value in *field {
+                buf.write_u64_varint(value).unwrap();
+            }
+        }
+        Bytes::from(buf)
+    }
+
+    #[test]
+    fn truncated_directory() {
+        let mut buf = Vec::new();
+        read_raster_root_directory().write_to(&mut buf).unwrap();
+
+        for len in 0..buf.len() {
+            let result = Directory::try_from(Bytes::copy_from_slice(&buf[..len]));
+            assert!(result.is_err(), "prefix of {len} bytes");
+        }
+        assert!(Directory::try_from(Bytes::from(buf)).is_ok());
+    }
+
+    #[test]
+    fn entry_count_bound() {
+        // Two entries in exactly the minimum of eight bytes
+        let dir = encode_directory(&[&[2], &[0, 1], &[1, 1], &[1, 1], &[1, 0]]);
+        assert_eq!(Directory::try_from(dir).unwrap().entries.len(), 2);
+
+        // One more entry than the remaining bytes can hold is rejected before reading
+        let dir = encode_directory(&[&[3], &[0, 1], &[1, 1], &[1, 1], &[1, 0]]);
+        assert!(matches!(
+            Directory::try_from(dir),
+            Err(PmtError::InvalidEntry)
+        ));
+
+        // Largest varint count, which would not fit into memory
+        let dir = encode_directory(&[&[u64::MAX], &[0; 8]]);
+        assert!(matches!(
+            Directory::try_from(dir),
+            Err(PmtError::InvalidEntry)
+        ));
+    }
+
+    #[test]
+    fn hostile_directories() {
+        // Entry count far larger than the buffer, must not be allocated
+        let dir = encode_directory(&[&[u64::MAX >> 1], &[0, 1, 1, 1]]);
+        assert!(matches!(
+            Directory::try_from(dir),
+            Err(PmtError::InvalidEntry)
+        ));
+
+        // Tile IDs wrapping around
+        let dir = encode_directory(&[&[2], &[u64::MAX, 1], &[1, 1], &[1, 1], &[1, 1]]);
+        assert!(matches!(
+            Directory::try_from(dir),
+            Err(PmtError::InvalidEntry)
+        ));
+
+        // Contiguous offset wrapping around
+        let dir = encode_directory(&[&[2], &[0, 1], &[1, 1], &[10, 10], &[u64::MAX, 0]]);
+        assert!(matches!(
+            Directory::try_from(dir),
+            Err(PmtError::InvalidEntry)
+        ));
+
+        // Run of the last tile ID exceeding the tile ID range
+        let dir = encode_directory(&[&[1], &[u64::MAX], &[2], &[10], &[1]]);
+        assert!(matches!(
+            Directory::try_from(dir),
+            Err(PmtError::InvalidEntry)
+        ));
+
+        // Data of the last offset exceeding the byte range
+        let dir = encode_directory(&[&[1], &[0], &[1], &[10], &[u64::MAX]]);
+        assert!(matches!(
+            Directory::try_from(dir),
+            Err(PmtError::InvalidEntry)
+        ));
+
+        // First entry cannot be contiguous with a previous entry
+        let dir = encode_directory(&[&[1], &[0], &[1], &[10], &[0]]);
+        assert!(matches!(
+            Directory::try_from(dir),
+            Err(PmtError::InvalidEntry)
+        ));
+
+        // Varints longer than 64 bits
+        let dir = Bytes::from_static(&[0xff; 12]);
+        assert!(matches!(
+            Directory::try_from(dir),
+            Err(PmtError::InvalidEntry)
+        ));
+        let dir = Bytes::from_static(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02]);
+        assert!(matches!(
+            Directory::try_from(dir),
+            Err(PmtError::InvalidEntry)
+        ));
+
+        // Run length larger than u32
+        let dir = encode_directory(&[&[1], &[0], &[u64::from(u32::MAX) + 1], &[10], &[1]]);
+        assert!(matches!(
+            Directory::try_from(dir),
+            Err(PmtError::InvalidEntry)
+        ));
+    }
 }
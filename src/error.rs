@@ -14,14 +14,57 @@ pub enum PmtError {
     InvalidMagicNumber,
     #[error("Invalid PMTiles version")]
     UnsupportedPmTilesVersion,
+    #[error("Unsupported PMTiles version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Invalid zoom range {0}..={1}")]
+    InvalidZoomRange(u8, u8),
+    #[error("Invalid bounds {0:?}")]
+    InvalidBounds([f32; 4]),
+    #[error("Invalid center {longitude}, {latitude} at zoom {zoom}")]
+    InvalidCenter {
+        longitude: f32,
+        latitude: f32,
+        zoom: u8,
+    },
     #[error("Invalid compression")]
     InvalidCompression,
     #[error("Unsupported compression {0:?}")]
     UnsupportedCompression(Compression),
     #[error("Invalid PMTiles entry")]
     InvalidEntry,
+    #[cfg(any(feature = "__async", feature = "sync", feature = "write"))]
+    #[error("Leaf directories are nested deeper than the configured limit")]
+    LeafRecursionLimit,
+    #[cfg(feature = "__async")]
+    #[error("The archive does not contain any tiles")]
+    EmptyArchive,
+    #[cfg(feature = "__async")]
+    #[error("Base offset {0} exceeds the addressable range")]
+    InvalidBaseOffset(u64),
+    #[error("Conflicting entries for tile {0}")]
+    DuplicateTile(u64),
+    #[error("Directory entry for tile {0} is not sorted by tile ID")]
+    UnsortedEntries(u64),
+    #[cfg(feature = "write")]
+    #[error("Tile compression {1:?} does not match the archive's tile compression {0:?}")]
+    CompressionMismatch(Compression, Compression),
+    #[cfg(feature = "write")]
+    #[error("Tile {got} added out of order, expected a tile ID of at least {expected_min}")]
+    TilesOutOfOrder { expected_min: u64, got: u64 },
+    #[cfg(feature = "write")]
+    #[error("The root directory does not fit into {0} bytes")]
+    RootDirectoryTooLarge(usize),
+    #[cfg(feature = "write")]
+    #[error("The archive layout does not allow appending tiles")]
+    UnsupportedAppendLayout,
+    #[cfg(feature = "write")]
+    #[error("Tiles of type {1:?} cannot be stored with tiles of type {0:?}")]
+    MixedTileTypes(crate::TileType, crate::TileType),
     #[error("Invalid header")]
     InvalidHeader,
+    #[cfg(any(feature = "__async", feature = "sync"))]
+    #[error("The archive is truncated to {actual} bytes, its header references {expected} bytes")]
+    TruncatedArchive { expected: u64, actual: u64 },
     #[error("Invalid metadata")]
     InvalidMetadata,
     #[error("Invalid metadata UTF-8 encoding: {0}")]
@@ -35,22 +78,44 @@ pub enum PmtError {
     UnableToOpenMmapFile,
     #[error("Unexpected number of bytes returned [expected: {0}, received: {1}].")]
     UnexpectedNumberOfBytesReturned(usize, usize),
-    #[cfg(feature = "http-async")]
+    #[cfg(any(feature = "http-async", feature = "fetch-async"))]
     #[error("Range requests unsupported")]
     RangeRequestsUnsupported,
+    #[cfg(feature = "http-async")]
+    #[error("Invalid multipart range response")]
+    InvalidMultipartResponse,
+    #[cfg(feature = "http-async")]
+    #[error("The archive was modified on the server while reading it")]
+    ArchiveChanged,
+    #[cfg(feature = "http-async")]
+    #[error("The host of {0} has no {{s}} placeholder for subdomains")]
+    NoSubdomainPlaceholder(String),
+    #[cfg(feature = "http-async")]
+    #[error("The request timed out")]
+    Timeout,
     #[cfg(any(
         feature = "http-async",
+        feature = "fetch-async",
         feature = "__async-s3",
         feature = "__async-aws-s3"
     ))]
     #[error("HTTP response body is too long, Response {0}B > requested {1}B")]
     ResponseBodyTooLong(usize, usize),
+    #[cfg(feature = "fetch-async")]
+    #[error("Fetch error: {0}")]
+    Fetch(String),
     #[cfg(feature = "http-async")]
     #[error(transparent)]
     Http(#[from] reqwest::Error),
     #[cfg(feature = "http-async")]
     #[error(transparent)]
     InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderValue),
+    #[cfg(feature = "mbtiles")]
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    #[cfg(feature = "object_store")]
+    #[error(transparent)]
+    ObjectStore(#[from] object_store::Error),
     #[cfg(feature = "__async-s3")]
     #[error(transparent)]
     S3(#[from] s3::error::S3Error),
@@ -1,16 +1,18 @@
+use std::io::Write;
 use std::num::NonZeroU64;
 use std::panic::catch_unwind;
 
 use bytes::{Buf, Bytes};
 
 use crate::error::{PmtError, PmtResult};
+use crate::writer::WriteTo;
 
-#[cfg(feature = "__async")]
+#[cfg(any(feature = "__async", feature = "sync", feature = "write"))]
 pub(crate) const MAX_INITIAL_BYTES: usize = 16_384;
-#[cfg(any(test, feature = "__async"))]
 pub(crate) const HEADER_SIZE: usize = 127;
 
 #[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Header {
     pub(crate) version: u8,
     pub(crate) root_offset: u64,
@@ -40,6 +42,7 @@ pub struct Header {
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "metadata", derive(serde::Serialize))]
 pub enum Compression {
     Unknown,
     None,
@@ -51,11 +54,24 @@ pub enum Compression {
 impl Compression {
     #[must_use]
     pub fn content_encoding(&self) -> Option<&'static str> {
-        Some(match self {
-            Compression::Gzip => "gzip",
-            Compression::Brotli => "br",
-            _ => None?,
-        })
+        match self {
+            Compression::Gzip => Some("gzip"),
+            Compression::Brotli => Some("br"),
+            Compression::Zstd => Some("zstd"),
+            Compression::Unknown | Compression::None => None,
+        }
+    }
+}
+
+impl From<Compression> for u8 {
+    fn from(value: Compression) -> Self {
+        match value {
+            Compression::Unknown => 0,
+            Compression::None => 1,
+            Compression::Gzip => 2,
+            Compression::Brotli => 3,
+            Compression::Zstd => 4,
+        }
     }
 }
 
@@ -108,12 +124,14 @@ impl Header {
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "metadata", derive(serde::Serialize))]
 pub enum TileType {
     Unknown,
     Mvt,
     Png,
     Jpeg,
     Webp,
+    Avif,
 }
 
 impl TileType {
@@ -124,9 +142,50 @@ impl TileType {
             TileType::Png => "image/png",
             TileType::Webp => "image/webp",
             TileType::Jpeg => "image/jpeg",
+            TileType::Avif => "image/avif",
             TileType::Unknown => "application/octet-stream",
         }
     }
+
+    /// The usual file extension of tiles of this type, without a leading dot.
+    #[must_use]
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TileType::Mvt => "mvt",
+            TileType::Png => "png",
+            TileType::Webp => "webp",
+            TileType::Jpeg => "jpg",
+            TileType::Avif => "avif",
+            TileType::Unknown => "bin",
+        }
+    }
+
+    /// The tile type of a file extension or an `MBTiles` or `TileJSON` `format` value,
+    /// such as `pbf` or `png`, ignoring case. Returns `None` for unknown formats.
+    #[must_use]
+    pub fn from_format(format: &str) -> Option<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "pbf" | "mvt" => Some(TileType::Mvt),
+            "png" => Some(TileType::Png),
+            "jpg" | "jpeg" => Some(TileType::Jpeg),
+            "webp" => Some(TileType::Webp),
+            "avif" => Some(TileType::Avif),
+            _ => None,
+        }
+    }
+}
+
+impl From<TileType> for u8 {
+    fn from(value: TileType) -> Self {
+        match value {
+            TileType::Unknown => 0,
+            TileType::Mvt => 1,
+            TileType::Png => 2,
+            TileType::Jpeg => 3,
+            TileType::Webp => 4,
+            TileType::Avif => 5,
+        }
+    }
 }
 
 impl TryInto<TileType> for u8 {
@@ -139,19 +198,399 @@ impl TryInto<TileType> for u8 {
             2 => Ok(TileType::Png),
             3 => Ok(TileType::Jpeg),
             4 => Ok(TileType::Webp),
+            5 => Ok(TileType::Avif),
             _ => Err(PmtError::InvalidTileType),
         }
     }
 }
 
+impl Header {
+    #[allow(clippy::cast_possible_truncation)]
+    fn to_e7(value: f32) -> i32 {
+        (f64::from(value) * 10_000_000.).round() as i32
+    }
+
+    /// The `PMTiles` spec version of the archive.
+    #[must_use]
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Whether the header declares the tile data as clustered, i.e. stored in tile ID order.
+    ///
+    /// This is the flag stored in the archive, see [`crate::Directory::is_clustered`]
+    /// to check the directory entries instead.
+    #[must_use]
+    pub fn is_clustered(&self) -> bool {
+        self.clustered
+    }
+
+    /// Compression of the directories and the metadata.
+    #[must_use]
+    pub fn internal_compression(&self) -> Compression {
+        self.internal_compression
+    }
+
+    /// Compression of the tile data.
+    #[must_use]
+    pub fn tile_compression(&self) -> Compression {
+        self.tile_compression
+    }
+
+    /// Type of the tiles stored in the archive.
+    #[must_use]
+    pub fn tile_type(&self) -> TileType {
+        self.tile_type
+    }
+
+    #[must_use]
+    pub fn min_zoom(&self) -> u8 {
+        self.min_zoom
+    }
+
+    #[must_use]
+    pub fn max_zoom(&self) -> u8 {
+        self.max_zoom
+    }
+
+    #[must_use]
+    pub fn center_zoom(&self) -> u8 {
+        self.center_zoom
+    }
+
+    /// Number of addressed tiles, or 0 if the archive does not specify it.
+    #[must_use]
+    pub fn tile_count(&self) -> u64 {
+        self.n_addressed_tiles.map_or(0, NonZeroU64::get)
+    }
+
+    /// Offset of the tile data section from the start of the archive, in bytes.
+    #[must_use]
+    pub fn tile_data_offset(&self) -> u64 {
+        self.data_offset
+    }
+
+    /// Length of the tile data section, in bytes.
+    #[must_use]
+    pub fn tile_data_length(&self) -> u64 {
+        self.data_length
+    }
+
+    /// Minimum longitude of the bounds, in the E7 fixed-point representation of the spec.
+    #[must_use]
+    pub fn min_lon_e7(&self) -> i32 {
+        Self::to_e7(self.min_longitude)
+    }
+
+    /// Minimum latitude of the bounds, in the E7 fixed-point representation of the spec.
+    #[must_use]
+    pub fn min_lat_e7(&self) -> i32 {
+        Self::to_e7(self.min_latitude)
+    }
+
+    /// Maximum longitude of the bounds, in the E7 fixed-point representation of the spec.
+    #[must_use]
+    pub fn max_lon_e7(&self) -> i32 {
+        Self::to_e7(self.max_longitude)
+    }
+
+    /// Maximum latitude of the bounds, in the E7 fixed-point representation of the spec.
+    #[must_use]
+    pub fn max_lat_e7(&self) -> i32 {
+        Self::to_e7(self.max_latitude)
+    }
+
+    /// Longitude of the center, in the E7 fixed-point representation of the spec.
+    #[must_use]
+    pub fn center_lon_e7(&self) -> i32 {
+        Self::to_e7(self.center_longitude)
+    }
+
+    /// Latitude of the center, in the E7 fixed-point representation of the spec.
+    #[must_use]
+    pub fn center_lat_e7(&self) -> i32 {
+        Self::to_e7(self.center_latitude)
+    }
+
+    /// Bounds of the archive in degrees, as `[min_lon, min_lat, max_lon, max_lat]`.
+    ///
+    /// Returns the full Web Mercator world extent if the bounds are unset (all zero).
+    #[must_use]
+    pub fn bounds(&self) -> [f64; 4] {
+        let e7 = [
+            self.min_lon_e7(),
+            self.min_lat_e7(),
+            self.max_lon_e7(),
+            self.max_lat_e7(),
+        ];
+        if e7 == [0; 4] {
+            return WORLD_BOUNDS;
+        }
+        e7.map(|v| f64::from(v) / 10_000_000.)
+    }
+
+    /// Center of the archive as `(lon, lat, zoom)`, with coordinates in degrees.
+    #[must_use]
+    pub fn center(&self) -> (f64, f64, u8) {
+        (
+            f64::from(self.center_lon_e7()) / 10_000_000.,
+            f64::from(self.center_lat_e7()) / 10_000_000.,
+            self.center_zoom,
+        )
+    }
+}
+
+/// Summary of an archive, as shown by `pmtiles info` style tools.
+///
+/// Built from the [`Header`], e.g. by `AsyncPmTilesReader::info`.
+/// With the `metadata` feature, it can be serialized with `serde`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "metadata", derive(serde::Serialize))]
+pub struct ArchiveInfo {
+    pub version: u8,
+    pub clustered: bool,
+    pub tile_type: TileType,
+    pub internal_compression: Compression,
+    pub tile_compression: Compression,
+    pub min_zoom: u8,
+    pub max_zoom: u8,
+    /// Bounds in degrees, as `[min_lon, min_lat, max_lon, max_lat]`, see [`Header::bounds`]
+    pub bounds: [f64; 4],
+    /// Center as `(lon, lat, zoom)`, see [`Header::center`]
+    pub center: (f64, f64, u8),
+    /// Number of addressed tiles, if the archive specifies it
+    pub addressed_tiles: Option<u64>,
+    /// Number of tile entries, if the archive specifies it
+    pub tile_entries: Option<u64>,
+    /// Number of distinct tile contents, if the archive specifies it
+    pub tile_contents: Option<u64>,
+    /// Lengths of the sections in bytes, as stored (possibly compressed)
+    pub root_directory_length: u64,
+    pub metadata_length: u64,
+    pub leaf_directories_length: u64,
+    pub tile_data_length: u64,
+}
+
+impl From<&Header> for ArchiveInfo {
+    fn from(header: &Header) -> Self {
+        Self {
+            version: header.version,
+            clustered: header.clustered,
+            tile_type: header.tile_type,
+            internal_compression: header.internal_compression,
+            tile_compression: header.tile_compression,
+            min_zoom: header.min_zoom,
+            max_zoom: header.max_zoom,
+            bounds: header.bounds(),
+            center: header.center(),
+            addressed_tiles: header.n_addressed_tiles.map(NonZeroU64::get),
+            tile_entries: header.n_tile_entries.map(NonZeroU64::get),
+            tile_contents: header.n_tile_contents.map(NonZeroU64::get),
+            root_directory_length: header.root_length,
+            metadata_length: header.metadata_length,
+            leaf_directories_length: header.leaf_length,
+            tile_data_length: header.data_length,
+        }
+    }
+}
+
+/// Highest zoom level accepted by [`HeaderBuilder::build`].
+pub const MAX_HEADER_ZOOM: u8 = 30;
+
+/// Latitude limit of the Web Mercator projection, rounded up.
+const MAX_LATITUDE: f32 = 85.051_13;
+
+/// Builds a [`Header`] with explicitly set fields, e.g. for `PmTilesWriter::from_header`.
+///
+/// The section offsets and lengths, as well as the tile counts, are left at zero to be filled
+/// in by a writer. Defaults match `PmTilesWriter::new`.
+///
+/// ```
+/// # use pmtiles::{Compression, HeaderBuilder, TileType};
+/// let header = HeaderBuilder::new(TileType::Mvt)
+///     .tile_compression(Compression::Gzip)
+///     .zoom_range(0, 14)
+///     .bounds(11.0, 43.0, 12.0, 44.0)
+///     .center(11.25, 43.77, 12)
+///     .build()
+///     .unwrap();
+/// assert_eq!(header.max_zoom(), 14);
+/// ```
+#[derive(Debug, Clone)]
+pub struct HeaderBuilder {
+    pub(crate) header: Header,
+}
+
+impl HeaderBuilder {
+    /// Create a builder for tiles of the given type.
+    ///
+    /// Vector tiles are gzip-compressed by default, all other tile types are stored as is.
+    #[must_use]
+    pub fn new(tile_type: TileType) -> Self {
+        let tile_compression = match tile_type {
+            TileType::Mvt => Compression::Gzip,
+            _ => Compression::None,
+        };
+        Self {
+            header: Header {
+                version: 3,
+                root_offset: 0,
+                root_length: 0,
+                metadata_offset: 0,
+                metadata_length: 0,
+                leaf_offset: 0,
+                leaf_length: 0,
+                data_offset: 0,
+                data_length: 0,
+                n_addressed_tiles: None,
+                n_tile_entries: None,
+                n_tile_contents: None,
+                clustered: false,
+                internal_compression: Compression::Gzip,
+                tile_compression,
+                tile_type,
+                min_zoom: 0,
+                max_zoom: 22,
+                min_longitude: -180.0,
+                min_latitude: -85.0,
+                max_longitude: 180.0,
+                max_latitude: 85.0,
+                center_zoom: 0,
+                center_longitude: 0.0,
+                center_latitude: 0.0,
+            },
+        }
+    }
+
+    #[must_use]
+    pub fn tile_type(mut self, tile_type: TileType) -> Self {
+        self.header.tile_type = tile_type;
+        self
+    }
+
+    /// Compression of the directories and metadata, gzip by default.
+    #[must_use]
+    pub fn internal_compression(mut self, compression: Compression) -> Self {
+        self.header.internal_compression = compression;
+        self
+    }
+
+    #[must_use]
+    pub fn tile_compression(mut self, compression: Compression) -> Self {
+        self.header.tile_compression = compression;
+        self
+    }
+
+    #[must_use]
+    pub fn zoom_range(mut self, min_zoom: u8, max_zoom: u8) -> Self {
+        self.header.min_zoom = min_zoom;
+        self.header.max_zoom = max_zoom;
+        self
+    }
+
+    /// Bounds in degrees.
+    #[must_use]
+    pub fn bounds(mut self, min_lon: f32, min_lat: f32, max_lon: f32, max_lat: f32) -> Self {
+        let h = &mut self.header;
+        (h.min_longitude, h.min_latitude) = (min_lon, min_lat);
+        (h.max_longitude, h.max_latitude) = (max_lon, max_lat);
+        self
+    }
+
+    /// Center in degrees, and the zoom level to show it at.
+    #[must_use]
+    pub fn center(mut self, longitude: f32, latitude: f32, zoom: u8) -> Self {
+        let h = &mut self.header;
+        (h.center_longitude, h.center_latitude, h.center_zoom) = (longitude, latitude, zoom);
+        self
+    }
+
+    /// Whether the tile data is declared as clustered. Writers set this from the written tiles.
+    #[must_use]
+    pub fn clustered(mut self, clustered: bool) -> Self {
+        self.header.clustered = clustered;
+        self
+    }
+
+    /// Create the header, checking that zoom levels are at most [`MAX_HEADER_ZOOM`] and
+    /// coordinates lie within the Web Mercator extent.
+    pub fn build(self) -> PmtResult<Header> {
+        let h = self.header;
+        if h.min_zoom > h.max_zoom || h.max_zoom > MAX_HEADER_ZOOM {
+            return Err(PmtError::InvalidZoomRange(h.min_zoom, h.max_zoom));
+        }
+        let valid_lon = |lon: f32| (-180.0..=180.0).contains(&lon);
+        let valid_lat = |lat: f32| (-MAX_LATITUDE..=MAX_LATITUDE).contains(&lat);
+        let bounds = [
+            h.min_longitude,
+            h.min_latitude,
+            h.max_longitude,
+            h.max_latitude,
+        ];
+        let [min_lon, min_lat, max_lon, max_lat] = bounds;
+        let valid_bounds = valid_lon(min_lon)
+            && valid_lon(max_lon)
+            && valid_lat(min_lat)
+            && valid_lat(max_lat)
+            && min_lon <= max_lon
+            && min_lat <= max_lat;
+        if !valid_bounds {
+            return Err(PmtError::InvalidBounds(bounds));
+        }
+        if !valid_lon(h.center_longitude)
+            || !valid_lat(h.center_latitude)
+            || h.center_zoom > MAX_HEADER_ZOOM
+        {
+            return Err(PmtError::InvalidCenter {
+                longitude: h.center_longitude,
+                latitude: h.center_latitude,
+                zoom: h.center_zoom,
+            });
+        }
+        Ok(h)
+    }
+}
+
+/// Full extent of the Web Mercator projection, used when an archive does not specify its bounds.
+const WORLD_BOUNDS: [f64; 4] = [-180., -85.051_128_779_806_59, 180., 85.051_128_779_806_59];
+
 static V3_MAGIC: &str = "PMTiles";
 static V2_MAGIC: &str = "PM";
 
 impl Header {
-    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::cast_possible_truncation)]
     fn read_coordinate_part<B: Buf>(mut buf: B) -> f32 {
-        // TODO: would it be more precise to do `((value as f64) / 10_000_000.) as f32` ?
-        buf.get_i32_le() as f32 / 10_000_000.
+        // Divide as f64 to get the closest f32, so that `to_e7` restores the stored value
+        (f64::from(buf.get_i32_le()) / 10_000_000.) as f32
+    }
+
+    /// The smallest archive length containing all sections referenced by the header.
+    #[cfg(any(feature = "__async", feature = "sync"))]
+    pub(crate) fn archive_length(&self) -> u64 {
+        [
+            (self.root_offset, self.root_length),
+            (self.metadata_offset, self.metadata_length),
+            (self.leaf_offset, self.leaf_length),
+            (self.data_offset, self.data_length),
+        ]
+        .into_iter()
+        .map(|(offset, length)| offset.saturating_add(length))
+        .max()
+        .unwrap_or_default()
+    }
+
+    /// Fails with [`PmtError::TruncatedArchive`] if an archive of `size` bytes is too short
+    /// for the sections referenced by the header.
+    #[cfg(any(feature = "__async", feature = "sync"))]
+    pub(crate) fn check_archive_size(&self, size: u64) -> PmtResult<()> {
+        let expected = self.archive_length();
+        if size < expected {
+            return Err(PmtError::TruncatedArchive {
+                expected,
+                actual: size,
+            });
+        }
+        Ok(())
     }
 
     pub fn try_from_bytes(mut bytes: Bytes) -> PmtResult<Self> {
@@ -167,7 +606,7 @@ impl Header {
         }
 
         // Wrap the panics that are possible in `get_u*_le` calls. (Panic occurs if the buffer is exhausted.)
-        catch_unwind(move || {
+        let header: Self = catch_unwind(move || -> PmtResult<Self> {
             Ok(Self {
                 version: bytes.get_u8(),
                 root_offset: bytes.get_u64_le(),
@@ -196,7 +635,85 @@ impl Header {
                 center_latitude: Self::read_coordinate_part(&mut bytes),
             })
         })
-        .map_err(|_| PmtError::InvalidHeader)?
+        .map_err(|_| PmtError::InvalidHeader)??;
+
+        header.validate()?;
+        Ok(header)
+    }
+
+    /// Serializes the header into its 127-byte binary representation,
+    /// the inverse of [`Header::try_from_bytes`].
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)]
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = Vec::with_capacity(HEADER_SIZE);
+        self.write_to(&mut buf)
+            .expect("writing to a Vec should never fail");
+        Bytes::from(buf)
+    }
+
+    /// Rejects header values that would make the rest of the archive unreadable.
+    fn validate(&self) -> PmtResult<()> {
+        // Some writers store the version as the ASCII character '3'
+        if !matches!(self.version, 3 | b'3') {
+            return Err(PmtError::UnsupportedVersion(self.version));
+        }
+        if self.min_zoom > self.max_zoom {
+            return Err(PmtError::InvalidZoomRange(self.min_zoom, self.max_zoom));
+        }
+        for (offset, length) in [
+            (self.root_offset, self.root_length),
+            (self.metadata_offset, self.metadata_length),
+            (self.leaf_offset, self.leaf_length),
+            (self.data_offset, self.data_length),
+        ] {
+            if offset.checked_add(length).is_none() {
+                return Err(PmtError::InvalidHeader);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl WriteTo for Header {
+    fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        // Write a magic number
+        writer.write_all(V3_MAGIC.as_bytes())?;
+
+        // Write header fields
+        writer.write_all(&[self.version])?;
+        writer.write_all(&self.root_offset.to_le_bytes())?;
+        writer.write_all(&self.root_length.to_le_bytes())?;
+        writer.write_all(&self.metadata_offset.to_le_bytes())?;
+        writer.write_all(&self.metadata_length.to_le_bytes())?;
+        writer.write_all(&self.leaf_offset.to_le_bytes())?;
+        writer.write_all(&self.leaf_length.to_le_bytes())?;
+        writer.write_all(&self.data_offset.to_le_bytes())?;
+        writer.write_all(&self.data_length.to_le_bytes())?;
+        for count in [
+            self.n_addressed_tiles,
+            self.n_tile_entries,
+            self.n_tile_contents,
+        ] {
+            writer.write_all(&count.map_or(0, NonZeroU64::get).to_le_bytes())?;
+        }
+        writer.write_all(&[
+            u8::from(self.clustered),
+            self.internal_compression.into(),
+            self.tile_compression.into(),
+            self.tile_type.into(),
+            self.min_zoom,
+            self.max_zoom,
+        ])?;
+        writer.write_all(&self.min_lon_e7().to_le_bytes())?;
+        writer.write_all(&self.min_lat_e7().to_le_bytes())?;
+        writer.write_all(&self.max_lon_e7().to_le_bytes())?;
+        writer.write_all(&self.max_lat_e7().to_le_bytes())?;
+        writer.write_all(&[self.center_zoom])?;
+        writer.write_all(&self.center_lon_e7().to_le_bytes())?;
+        writer.write_all(&self.center_lat_e7().to_le_bytes())?;
+
+        Ok(())
     }
 }
 
@@ -209,8 +726,10 @@ mod tests {
 
     use bytes::{Bytes, BytesMut};
 
-    use crate::header::{Header, TileType, HEADER_SIZE};
+    use crate::header::{ArchiveInfo, Compression, Header, HeaderBuilder, TileType, HEADER_SIZE};
     use crate::tests::{RASTER_FILE, VECTOR_FILE};
+    use crate::writer::WriteTo;
+    use crate::PmtError;
 
     #[test]
     fn read_header() {
@@ -220,7 +739,8 @@ mod tests {
 
         let header = Header::try_from_bytes(Bytes::copy_from_slice(&header_bytes)).unwrap();
 
-        // TODO: should be 3, but currently the ascii char 3, assert_eq!(header.version, 3);
+        // Should be 3, but the fixture stores the ascii char 3
+        assert_eq!(header.version, b'3');
         assert_eq!(header.tile_type, TileType::Png);
         assert_eq!(header.n_addressed_tiles, NonZeroU64::new(85));
         assert_eq!(header.n_tile_entries, NonZeroU64::new(84));
@@ -234,7 +754,97 @@ mod tests {
         assert_eq!(header.max_latitude, 85.0);
         assert_eq!(header.min_longitude, -180.0);
         assert_eq!(header.max_longitude, 180.0);
-        assert!(header.clustered);
+        assert!(header.is_clustered());
+    }
+
+    #[test]
+    fn header_builder() {
+        let header = HeaderBuilder::new(TileType::Webp)
+            .internal_compression(Compression::None)
+            .zoom_range(2, 10)
+            .bounds(-10.0, -20.0, 10.0, 20.0)
+            .center(1.0, 2.0, 5)
+            .clustered(true)
+            .build()
+            .unwrap();
+        assert_eq!(header.tile_type, TileType::Webp);
+        assert_eq!(header.tile_compression, Compression::None);
+        assert_eq!(header.internal_compression, Compression::None);
+        assert_eq!((header.min_zoom, header.max_zoom), (2, 10));
+        assert_eq!(header.bounds(), [-10.0, -20.0, 10.0, 20.0]);
+        assert_eq!(header.center(), (1.0, 2.0, 5));
+        assert!(header.is_clustered());
+        assert_eq!((header.root_offset, header.data_length), (0, 0));
+        // Vector tiles default to gzip, as for the writer
+        assert_eq!(
+            HeaderBuilder::new(TileType::Mvt)
+                .build()
+                .unwrap()
+                .tile_compression,
+            Compression::Gzip
+        );
+
+        let invalid = |builder: HeaderBuilder| builder.build().unwrap_err();
+        let builder = || HeaderBuilder::new(TileType::Png);
+        assert!(matches!(
+            invalid(builder().zoom_range(5, 4)),
+            PmtError::InvalidZoomRange(5, 4)
+        ));
+        assert!(matches!(
+            invalid(builder().zoom_range(0, 31)),
+            PmtError::InvalidZoomRange(0, 31)
+        ));
+        assert!(matches!(
+            invalid(builder().bounds(-180.0, -86.0, 180.0, 85.0)),
+            PmtError::InvalidBounds(_)
+        ));
+        assert!(matches!(
+            invalid(builder().bounds(10.0, 0.0, -10.0, 1.0)),
+            PmtError::InvalidBounds(_)
+        ));
+        assert!(matches!(
+            invalid(builder().center(181.0, 0.0, 0)),
+            PmtError::InvalidCenter { .. }
+        ));
+        assert!(matches!(
+            invalid(builder().center(0.0, 0.0, 31)),
+            PmtError::InvalidCenter { zoom: 31, .. }
+        ));
+        // The Web Mercator limits themselves are valid
+        builder()
+            .bounds(-180.0, -85.051_13, 180.0, 85.051_13)
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn archive_info() {
+        let mut header_bytes = [0; HEADER_SIZE];
+        File::open(RASTER_FILE)
+            .unwrap()
+            .read_exact(&mut header_bytes)
+            .unwrap();
+        let header = Header::try_from_bytes(Bytes::copy_from_slice(&header_bytes)).unwrap();
+
+        let info = ArchiveInfo::from(&header);
+        assert_eq!(info.tile_type, TileType::Png);
+        assert_eq!(info.tile_compression, Compression::None);
+        assert_eq!((info.min_zoom, info.max_zoom), (0, 3));
+        assert_eq!(info.bounds, [-180., -85., 180., 85.]);
+        assert_eq!(info.center, (0., 0., 0));
+        assert_eq!(info.addressed_tiles, Some(85));
+        assert_eq!(info.tile_entries, Some(84));
+        assert_eq!(info.root_directory_length, header.root_length);
+        assert_eq!(info.tile_data_length, header.data_length);
+        assert!(info.clustered);
+
+        #[cfg(feature = "metadata")]
+        {
+            let json = serde_json::to_value(&info).unwrap();
+            assert_eq!(json["tile_type"], "Png");
+            assert_eq!(json["addressed_tiles"], 85);
+            assert_eq!(json["center"], serde_json::json!([0.0, 0.0, 0]));
+        }
     }
 
     #[test]
@@ -256,12 +866,202 @@ mod tests {
         assert_eq!(header.center_latitude, 43.779778);
         assert_eq!(header.center_longitude, 11.241483);
         assert_eq!(header.min_latitude, 43.727013);
-        assert_eq!(header.max_latitude, 43.832542);
+        assert_eq!(header.max_latitude, 43.832546);
         assert_eq!(header.min_longitude, 11.154026);
         assert_eq!(header.max_longitude, 11.328939);
         assert!(header.clustered);
     }
 
+    #[test]
+    fn reject_invalid_headers() {
+        let mut test = File::open(VECTOR_FILE).unwrap();
+        let mut valid = vec![0; HEADER_SIZE];
+        test.read_exact(&mut valid).unwrap();
+
+        let parse = |modify: &dyn Fn(&mut [u8])| {
+            let mut bytes = valid.clone();
+            modify(&mut bytes);
+            Header::try_from_bytes(Bytes::from(bytes))
+        };
+
+        assert!(matches!(
+            parse(&|b| b[..7].copy_from_slice(b"MBTiles")),
+            Err(PmtError::InvalidMagicNumber)
+        ));
+        assert!(matches!(
+            parse(&|b| b[7] = 4),
+            Err(PmtError::UnsupportedVersion(4))
+        ));
+        // min_zoom > max_zoom
+        assert!(matches!(
+            parse(&|b| b[100] = 15),
+            Err(PmtError::InvalidZoomRange(15, 14))
+        ));
+        // data_offset + data_length overflow
+        assert!(matches!(
+            parse(&|b| b[56..64].copy_from_slice(&u64::MAX.to_le_bytes())),
+            Err(PmtError::InvalidHeader)
+        ));
+        // Truncated header
+        assert!(matches!(
+            Header::try_from_bytes(Bytes::from(valid[..50].to_vec())),
+            Err(PmtError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn tile_type_values() {
+        for (value, tile_type, content_type) in [
+            (0, TileType::Unknown, "application/octet-stream"),
+            (1, TileType::Mvt, "application/vnd.mapbox-vector-tile"),
+            (2, TileType::Png, "image/png"),
+            (3, TileType::Jpeg, "image/jpeg"),
+            (4, TileType::Webp, "image/webp"),
+            (5, TileType::Avif, "image/avif"),
+        ] {
+            assert_eq!(TryInto::<TileType>::try_into(value).unwrap(), tile_type);
+            assert_eq!(u8::from(tile_type), value);
+            assert_eq!(tile_type.content_type(), content_type);
+            if tile_type != TileType::Unknown {
+                assert_eq!(
+                    TileType::from_format(tile_type.extension()),
+                    Some(tile_type)
+                );
+            }
+        }
+        assert_eq!(TileType::from_format("PBF"), Some(TileType::Mvt));
+        assert_eq!(TileType::from_format("jpeg"), Some(TileType::Jpeg));
+        assert_eq!(TileType::from_format("bin"), None);
+        assert!(matches!(
+            TryInto::<TileType>::try_into(6),
+            Err(PmtError::InvalidTileType)
+        ));
+        assert_eq!(Compression::Gzip.content_encoding(), Some("gzip"));
+        assert_eq!(Compression::None.content_encoding(), None);
+    }
+
+    #[test]
+    fn header_accessors() {
+        let mut test = File::open(VECTOR_FILE).unwrap();
+        let mut header_bytes = BytesMut::zeroed(HEADER_SIZE);
+        test.read_exact(header_bytes.as_mut()).unwrap();
+
+        let header = Header::try_from_bytes(header_bytes.freeze()).unwrap();
+
+        assert_eq!(header.version(), 3);
+        assert_eq!(header.tile_type(), TileType::Mvt);
+        assert_eq!(header.tile_compression(), Compression::Gzip);
+        assert_eq!(header.internal_compression(), Compression::Gzip);
+        assert_eq!(header.min_zoom(), 0);
+        assert_eq!(header.max_zoom(), 14);
+        assert_eq!(header.center_zoom(), 0);
+        assert_eq!(header.min_lon_e7(), 111_540_260);
+        assert_eq!(header.min_lat_e7(), 437_270_126);
+        assert_eq!(header.max_lon_e7(), 113_289_394);
+        assert_eq!(header.max_lat_e7(), 438_325_462);
+        assert_eq!(header.center_lon_e7(), 112_414_827);
+        assert_eq!(header.center_lat_e7(), 437_797_775);
+        assert_eq!(header.tile_count(), 108);
+        assert_eq!(header.tile_data_offset(), header.data_offset);
+        assert_eq!(header.tile_data_length(), header.data_length);
+    }
+
+    #[test]
+    fn bounds_and_center_in_degrees() {
+        let mut test = File::open(VECTOR_FILE).unwrap();
+        let mut header_bytes = BytesMut::zeroed(HEADER_SIZE);
+        test.read_exact(header_bytes.as_mut()).unwrap();
+
+        let mut header = Header::try_from_bytes(header_bytes.freeze()).unwrap();
+        assert_eq!(
+            header.bounds(),
+            [11.154_026, 43.727_012_6, 11.328_939_4, 43.832_546_2]
+        );
+        assert_eq!(header.center(), (11.241_482_7, 43.779_777_5, 0));
+
+        header.min_longitude = 0.;
+        header.min_latitude = 0.;
+        header.max_longitude = 0.;
+        header.max_latitude = 0.;
+        assert_eq!(header.bounds(), super::WORLD_BOUNDS);
+    }
+
+    #[test]
+    fn write_header() {
+        let mut test = File::open(VECTOR_FILE).unwrap();
+        let mut header_bytes = [0; HEADER_SIZE];
+        test.read_exact(header_bytes.as_mut_slice()).unwrap();
+
+        let header = Header::try_from_bytes(Bytes::copy_from_slice(&header_bytes)).unwrap();
+
+        let mut buf = Vec::new();
+        header.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len(), HEADER_SIZE);
+        // Coordinates are only stored as f32, so they may not round-trip to the exact same bytes
+        assert_eq!(buf[..102], header_bytes[..102]);
+
+        let written = Header::try_from_bytes(Bytes::from(buf)).unwrap();
+        assert_eq!(written.min_longitude, header.min_longitude);
+        assert_eq!(written.min_latitude, header.min_latitude);
+        assert_eq!(written.max_longitude, header.max_longitude);
+        assert_eq!(written.max_latitude, header.max_latitude);
+        assert_eq!(written.center_zoom, header.center_zoom);
+        assert_eq!(written.center_longitude, header.center_longitude);
+        assert_eq!(written.center_latitude, header.center_latitude);
+    }
+
+    #[test]
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap,
+        clippy::cast_sign_loss
+    )]
+    fn header_bytes_round_trip() {
+        // xorshift64, so that failures are reproducible without an extra dependency
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..1000 {
+            let mut bytes = Vec::with_capacity(HEADER_SIZE);
+            bytes.extend_from_slice(b"PMTiles");
+            bytes.push(3);
+            // Offsets and lengths of the sections, then the tile counts
+            for _ in 0..11 {
+                bytes.extend_from_slice(&(next() >> 4).to_le_bytes());
+            }
+            let min_zoom = (next() % 32) as u8;
+            bytes.extend_from_slice(&[
+                (next() % 2) as u8,
+                (next() % 5) as u8,
+                (next() % 5) as u8,
+                (next() % 5) as u8,
+                min_zoom,
+                min_zoom + (next() % 32) as u8,
+            ]);
+            // Bounds, center zoom and center, with coordinates in e7 degrees
+            for max in [180, 90, 180, 90, 0, 180, 90] {
+                if max == 0 {
+                    bytes.push((next() % 32) as u8);
+                } else {
+                    let max = max * 10_000_000_i64;
+                    let value = (next() % (2 * max as u64 + 1)) as i64 - max;
+                    bytes.extend_from_slice(&(value as i32).to_le_bytes());
+                }
+            }
+            assert_eq!(bytes.len(), HEADER_SIZE);
+
+            let header = Header::try_from_bytes(Bytes::from(bytes)).unwrap();
+            let serialized = header.to_bytes();
+            assert_eq!(serialized.len(), HEADER_SIZE);
+            assert_eq!(Header::try_from_bytes(serialized).unwrap(), header);
+        }
+    }
+
     #[test]
     #[cfg(feature = "tilejson")]
     fn get_tilejson_raster() {
@@ -299,7 +1099,7 @@ mod tests {
                 11.15402603149414,
                 43.727012634277344,
                 11.328939437866211,
-                43.832542419433594
+                43.83254623413086
             ))
         );
     }
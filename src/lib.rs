@@ -1,13 +1,23 @@
 #![forbid(unsafe_code)]
 
+#[cfg(feature = "write")]
+mod appender;
 #[cfg(feature = "__async")]
 pub mod async_reader;
+#[cfg(feature = "async-read")]
+mod backend_async_read;
 #[cfg(feature = "__async-aws-s3")]
 mod backend_aws_s3;
+#[cfg(feature = "fetch-async")]
+mod backend_fetch;
 #[cfg(feature = "http-async")]
 mod backend_http;
+#[cfg(feature = "__async")]
+mod backend_memory;
 #[cfg(feature = "mmap-async-tokio")]
 mod backend_mmap;
+#[cfg(feature = "object_store")]
+mod backend_object_store;
 #[cfg(feature = "__async-s3")]
 mod backend_s3;
 #[cfg(feature = "__async")]
@@ -15,24 +25,59 @@ pub mod cache;
 mod directory;
 mod error;
 mod header;
+#[cfg(feature = "mbtiles")]
+mod mbtiles;
+#[cfg(feature = "metadata")]
+mod metadata;
+#[cfg(feature = "sync")]
+pub mod sync_reader;
+#[cfg(feature = "write")]
+mod tile_dir;
+pub mod tile_id;
 #[cfg(feature = "__async")]
-mod tile;
+mod validation;
+mod writer;
 
+#[cfg(feature = "write")]
+pub use appender::PmTilesAppender;
+#[cfg(feature = "async-read")]
+pub use backend_async_read::AsyncReadBackend;
 #[cfg(feature = "aws-s3-async")]
 pub use backend_aws_s3::AwsS3Backend;
+#[cfg(feature = "fetch-async")]
+pub use backend_fetch::FetchBackend;
 #[cfg(feature = "http-async")]
 pub use backend_http::HttpBackend;
+#[cfg(feature = "__async")]
+pub use backend_memory::MemoryBackend;
 #[cfg(feature = "mmap-async-tokio")]
 pub use backend_mmap::MmapBackend;
+#[cfg(feature = "object_store")]
+pub use backend_object_store::ObjectStoreBackend;
 #[cfg(feature = "__async-s3")]
 pub use backend_s3::S3Backend;
-pub use directory::{DirEntry, Directory};
+pub use directory::{DirEntry, DirLookup, Directory, ResolvedTile};
 pub use error::{PmtError, PmtResult};
-pub use header::{Compression, Header, TileType};
+pub use header::{ArchiveInfo, Compression, Header, HeaderBuilder, TileType, MAX_HEADER_ZOOM};
+#[cfg(feature = "mbtiles")]
+pub use mbtiles::convert_mbtiles;
+#[cfg(feature = "metadata")]
+pub use metadata::{Metadata, VectorLayer};
+#[cfg(feature = "write")]
+pub use tile_dir::convert_dir;
+#[cfg(feature = "__async")]
+pub use validation::{Section, ValidationIssue, ValidationReport, ValidationWarning};
+pub use writer::WriteTo;
+#[cfg(feature = "write")]
+pub use writer::{
+    PmTilesSeeklessWriter, PmTilesStreamWriter, PmTilesUnsortedWriter, PmTilesWriter,
+};
 //
 // Re-export crates exposed in our API to simplify dependency management
 #[cfg(feature = "__async-aws-s3")]
 pub use aws_sdk_s3;
+#[cfg(feature = "object_store")]
+pub use object_store;
 #[cfg(feature = "http-async")]
 pub use reqwest;
 #[cfg(feature = "__async-s3")]
@@ -44,4 +89,67 @@ pub use tilejson;
 mod tests {
     pub const RASTER_FILE: &str = "fixtures/stamen_toner(raster)CC-BY+ODbL_z3.pmtiles";
     pub const VECTOR_FILE: &str = "fixtures/protomaps(vector)ODbL_firenze.pmtiles";
+
+    /// Builds an uncompressed archive from a root directory, a leaf directories section and tile data.
+    ///
+    /// The header fields other than the section offsets and compression are taken from the raster fixture.
+    #[cfg(any(feature = "__async", feature = "sync"))]
+    pub fn archive_from_parts(root: &crate::Directory, leaves: &[u8], data: &[u8]) -> Vec<u8> {
+        use crate::header::HEADER_SIZE;
+        use crate::writer::WriteTo as _;
+        use crate::{Compression, Header};
+
+        let mut root_bytes = Vec::new();
+        root.write_to(&mut root_bytes).unwrap();
+
+        let bytes = std::fs::read(RASTER_FILE).unwrap();
+        let mut header = Header::try_from_bytes(bytes[..HEADER_SIZE].to_vec().into()).unwrap();
+        header.internal_compression = Compression::None;
+        header.tile_compression = Compression::None;
+        header.root_offset = HEADER_SIZE as u64;
+        header.root_length = root_bytes.len() as u64;
+        header.leaf_offset = header.root_offset + header.root_length;
+        header.leaf_length = leaves.len() as u64;
+        header.metadata_offset = header.leaf_offset + header.leaf_length;
+        header.metadata_length = 0;
+        header.data_offset = header.metadata_offset;
+        header.data_length = data.len() as u64;
+
+        let mut archive = Vec::new();
+        header.write_to(&mut archive).unwrap();
+        archive.extend_from_slice(&root_bytes);
+        archive.extend_from_slice(leaves);
+        archive.extend_from_slice(data);
+        archive
+    }
+
+    /// Builds an archive whose root directory points to a leaf directory that references itself.
+    #[cfg(any(feature = "__async", feature = "sync"))]
+    pub fn self_referential_leaf_archive() -> Vec<u8> {
+        use crate::writer::WriteTo as _;
+        use crate::{DirEntry, Directory};
+
+        // The directory length is part of its own encoding, grow it until it is stable
+        let mut dir_bytes: Vec<u8> = Vec::new();
+        loop {
+            let entry = DirEntry {
+                tile_id: 0,
+                offset: 0,
+                length: u32::try_from(dir_bytes.len()).unwrap(),
+                run_length: 0,
+            };
+            let dir = Directory::from_entries(vec![entry]);
+            let mut buf = Vec::new();
+            dir.write_to(&mut buf).unwrap();
+            if buf.len() == dir_bytes.len() {
+                return archive_from_parts(&dir, &buf, &[]);
+            }
+            dir_bytes = buf;
+        }
+    }
+
+    #[cfg(feature = "mmap-async-tokio")]
+    pub fn get_temp_file_path(suffix: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("pmtiles-test-{}-{suffix}", std::process::id()))
+    }
 }
@@ -0,0 +1,236 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use rusqlite::{Connection, OpenFlags};
+use serde_json::{Map, Value};
+
+use crate::error::{PmtError, PmtResult};
+use crate::tile_id::tile_id;
+use crate::{PmTilesWriter, TileType};
+
+/// Converts an `MBTiles` database at `src` into a `PMTiles` archive written to `dst`.
+///
+/// The `tiles` rows are flipped from the TMS scheme used by `MBTiles` to XYZ and written
+/// in tile ID order, so the result is clustered. Identical tiles are stored only once.
+/// The tile type is derived from the `format` metadata value, and all metadata values
+/// are carried over into the JSON metadata of the archive.
+pub fn convert_mbtiles<P: AsRef<Path>>(src: P, dst: P) -> PmtResult<()> {
+    let conn = Connection::open_with_flags(src, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let metadata = read_metadata(&conn)?;
+    let tile_type = metadata
+        .get("format")
+        .and_then(Value::as_str)
+        .and_then(TileType::from_format)
+        .unwrap_or(TileType::Unknown);
+
+    let mut tiles = Vec::new();
+    let mut stmt = conn.prepare("SELECT zoom_level, tile_column, tile_row FROM tiles")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let (z, x, tms_y): (u8, u64, u64) = (row.get(0)?, row.get(1)?, row.get(2)?);
+        if z > 31 || x >> z != 0 || tms_y >> z != 0 {
+            return Err(PmtError::InvalidEntry);
+        }
+        let y = (1 << z) - 1 - tms_y;
+        tiles.push((tile_id(z, x, y), z, x, tms_y));
+    }
+    tiles.sort_unstable_by_key(|&(id, ..)| id);
+
+    let mut writer = PmTilesWriter::new(tile_type).dedup(true);
+    let zooms = tiles.iter().map(|&(_, z, ..)| z);
+    if let (Some(min_zoom), Some(max_zoom)) = (zooms.clone().min(), zooms.max()) {
+        writer = writer.with_min_zoom(min_zoom).with_max_zoom(max_zoom);
+    }
+    if let Some(&[min_lon, min_lat, max_lon, max_lat]) = number_list(&metadata, "bounds").as_deref()
+    {
+        writer = writer.with_bounds(min_lon, min_lat, max_lon, max_lat);
+    }
+    if let Some(&[lon, lat, zoom]) = number_list(&metadata, "center").as_deref() {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let zoom = zoom as u8;
+        writer = writer.with_center(lon, lat, zoom);
+    }
+    let writer = writer.with_raw_metadata(&Value::Object(metadata).to_string());
+
+    let mut writer = writer.create(BufWriter::new(File::create(dst)?))?;
+    let mut stmt = conn.prepare(
+        "SELECT tile_data FROM tiles WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+    )?;
+    for (id, z, x, tms_y) in tiles {
+        let data: Vec<u8> = stmt.query_row((z, x, tms_y), |row| row.get(0))?;
+        writer.add_imported_tile(id, &data, false)?;
+    }
+
+    writer.finalize()
+}
+
+/// Reads the `metadata` table into a JSON object.
+///
+/// Numeric values and the comma separated `bounds` and `center` become numbers,
+/// and the keys of the `json` value are merged into the object.
+fn read_metadata(conn: &Connection) -> PmtResult<Map<String, Value>> {
+    let mut metadata = Map::new();
+    let mut stmt = conn.prepare("SELECT name, value FROM metadata")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let (name, value): (String, String) = (row.get(0)?, row.get(1)?);
+        let value = match name.as_str() {
+            "json" => {
+                let Ok(Value::Object(json)) = serde_json::from_str(&value) else {
+                    return Err(PmtError::InvalidMetadata);
+                };
+                metadata.extend(json);
+                continue;
+            }
+            "minzoom" | "maxzoom" => value
+                .parse::<u8>()
+                .map_or(Value::String(value), Value::from),
+            "bounds" | "center" => value
+                .split(',')
+                .map(|v| v.trim().parse::<f64>().map(Value::from))
+                .collect::<Result<_, _>>()
+                .map_or(Value::String(value), Value::Array),
+            _ => Value::String(value),
+        };
+        metadata.insert(name, value);
+    }
+    Ok(metadata)
+}
+
+/// Returns the numbers of an array value, e.g. `bounds` or `center`.
+fn number_list(metadata: &Map<String, Value>, key: &str) -> Option<Vec<f32>> {
+    #[allow(clippy::cast_possible_truncation)]
+    metadata
+        .get(key)?
+        .as_array()?
+        .iter()
+        .map(|v| v.as_f64().map(|v| v as f32))
+        .collect()
+}
+
+#[cfg(test)]
+#[cfg(feature = "mmap-async-tokio")]
+mod tests {
+    use std::io::Write as _;
+    use std::num::NonZeroU64;
+
+    use rusqlite::Connection;
+
+    use super::convert_mbtiles;
+    use crate::async_reader::AsyncPmTilesReader;
+    use crate::tests::get_temp_file_path;
+    use crate::{Compression, MmapBackend, TileType};
+
+    fn create_mbtiles(name: &str, metadata: &[(&str, &str)], tiles: &[(u8, u64, u64, &[u8])]) {
+        let path = get_temp_file_path(name);
+        let _ = std::fs::remove_file(&path);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE metadata (name TEXT, value TEXT);
+             CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB);",
+        )
+        .unwrap();
+        for (name, value) in metadata {
+            conn.execute("INSERT INTO metadata VALUES (?1, ?2)", (name, value))
+                .unwrap();
+        }
+        for (z, x, y, data) in tiles {
+            conn.execute("INSERT INTO tiles VALUES (?1, ?2, ?3, ?4)", (z, x, y, data))
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn convert_raster() {
+        let tile_0 = include_bytes!("../fixtures/0_0_0.png");
+        let tile_2 = include_bytes!("../fixtures/2_2_2.png");
+        // TMS rows, e.g. row 3 at zoom 2 is the XYZ row 0
+        create_mbtiles(
+            "raster.mbtiles",
+            &[
+                ("name", "test"),
+                ("format", "png"),
+                ("bounds", "-10,-20,10,20"),
+                ("center", "1.5,2.5,2"),
+                ("minzoom", "0"),
+            ],
+            &[
+                (0, 0, 0, tile_0),
+                (2, 2, 1, tile_2),
+                (2, 0, 3, tile_2),
+                (2, 3, 0, tile_0),
+            ],
+        );
+
+        let src = get_temp_file_path("raster.mbtiles");
+        let dst = get_temp_file_path("mbtiles-raster.pmtiles");
+        convert_mbtiles(&src, &dst).unwrap();
+
+        let backend = MmapBackend::try_from(&dst).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let header = tiles.get_header();
+        assert_eq!(header.tile_type, TileType::Png);
+        assert_eq!(header.tile_compression, Compression::None);
+        assert_eq!((header.min_zoom, header.max_zoom), (0, 2));
+        assert_eq!((header.min_longitude, header.max_latitude), (-10.0, 20.0));
+        assert_eq!(header.center_zoom, 2);
+        assert!(header.clustered);
+        assert_eq!(header.n_addressed_tiles, NonZeroU64::new(4));
+        assert_eq!(header.n_tile_contents, NonZeroU64::new(2));
+
+        for (z, x, y, expected) in [
+            (0, 0, 0, &tile_0[..]),
+            (2, 2, 2, tile_2),
+            (2, 0, 0, tile_2),
+            (2, 3, 3, tile_0),
+        ] {
+            let tile = tiles.get_tile(z, x, y).await.unwrap().unwrap();
+            assert_eq!(tile, expected, "tile {z}/{x}/{y}");
+        }
+        assert!(tiles.get_tile(2, 2, 1).await.unwrap().is_none());
+
+        let metadata: serde_json::Value =
+            serde_json::from_str(&tiles.get_metadata().await.unwrap()).unwrap();
+        assert_eq!(metadata["name"], "test");
+        assert_eq!(metadata["minzoom"], 0);
+        assert_eq!(
+            metadata["bounds"],
+            serde_json::json!([-10.0, -20.0, 10.0, 20.0])
+        );
+    }
+
+    #[tokio::test]
+    async fn convert_gzipped_vector() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"vector tile").unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        create_mbtiles(
+            "vector.mbtiles",
+            &[
+                ("format", "pbf"),
+                ("json", r#"{"vector_layers":[{"id":"roads","fields":{}}]}"#),
+            ],
+            &[(1, 0, 0, &gzipped)],
+        );
+
+        let src = get_temp_file_path("vector.mbtiles");
+        let dst = get_temp_file_path("mbtiles-vector.pmtiles");
+        convert_mbtiles(&src, &dst).unwrap();
+
+        let backend = MmapBackend::try_from(&dst).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        assert_eq!(tiles.get_header().tile_type, TileType::Mvt);
+        assert_eq!(tiles.get_header().tile_compression, Compression::Gzip);
+
+        let tile = tiles.get_tile_zxy(1, 0, 1).await.unwrap().unwrap();
+        assert_eq!(tile, &b"vector tile"[..]);
+
+        let metadata: serde_json::Value =
+            serde_json::from_str(&tiles.get_metadata().await.unwrap()).unwrap();
+        assert_eq!(metadata["vector_layers"][0]["id"], "roads");
+        assert!(metadata.get("json").is_none());
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The JSON metadata of an archive, with the standard `TileJSON` fields parsed into typed values.
+///
+/// Any other fields are preserved in [`Metadata::other`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// Bounds as `[min_lon, min_lat, max_lon, max_lat]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<[f64; 4]>,
+    /// Center as `[lon, lat, zoom]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub center: Option<[f64; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minzoom: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maxzoom: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector_layers: Option<Vec<VectorLayer>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<String>,
+    /// All other, non-standard fields.
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>,
+}
+
+/// Description of a layer of vector tiles.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VectorLayer {
+    pub id: String,
+    /// Attribute names of the layer's features, mapped to their types.
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minzoom: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maxzoom: Option<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Metadata;
+
+    #[test]
+    fn parse_metadata() {
+        let meta: Metadata = serde_json::from_str(
+            r#"{
+                "name": "test",
+                "format": "pbf",
+                "bounds": [-180, -85, 180, 85],
+                "center": [0, 0, 2],
+                "minzoom": 0,
+                "maxzoom": 14,
+                "vector_layers": [{"id": "roads", "fields": {"name": "String"}}],
+                "generator": "test-suite"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(meta.name.as_deref(), Some("test"));
+        assert_eq!(meta.format.as_deref(), Some("pbf"));
+        assert_eq!(meta.bounds, Some([-180.0, -85.0, 180.0, 85.0]));
+        assert_eq!(meta.center, Some([0.0, 0.0, 2.0]));
+        assert_eq!(meta.maxzoom, Some(14));
+        let layers = meta.vector_layers.as_ref().unwrap();
+        assert_eq!(layers[0].id, "roads");
+        assert_eq!(layers[0].fields["name"], "String");
+        assert_eq!(meta.other["generator"], "test-suite");
+
+        let json = serde_json::to_string(&meta).unwrap();
+        assert_eq!(serde_json::from_str::<Metadata>(&json).unwrap(), meta);
+    }
+}
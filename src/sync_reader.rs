@@ -0,0 +1,266 @@
+// Same caveat as in the async reader: offsets are converted from u64 to usize.
+#![allow(clippy::cast_possible_truncation)]
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use bytes::Bytes;
+
+use crate::directory::{DirEntry, Directory};
+use crate::error::{PmtError, PmtResult};
+use crate::header::{HEADER_SIZE, MAX_INITIAL_BYTES};
+use crate::tile_id::tile_id;
+use crate::writer::decompress;
+use crate::{Compression, Header};
+
+/// Default limit of nested leaf directories followed by [`PmTilesReader`].
+pub const DEFAULT_MAX_LEAF_DEPTH: u8 = 4;
+
+/// A blocking `PMTiles` reader over any [`Read`] + [`Seek`] source.
+///
+/// Leaf directories are read on demand and are not cached.
+pub struct PmTilesReader<R> {
+    reader: R,
+    header: Header,
+    root_directory: Directory,
+    max_leaf_depth: u8,
+}
+
+impl PmTilesReader<File> {
+    /// Opens a `PMTiles` archive from a file on disk.
+    pub fn new_with_path<P: AsRef<Path>>(path: P) -> PmtResult<Self> {
+        Self::new(File::open(path)?)
+    }
+}
+
+impl<R: Read + Seek> PmTilesReader<R> {
+    /// Creates a reader, reading the header and root directory from `reader`.
+    pub fn new(mut reader: R) -> PmtResult<Self> {
+        // Read the first 127 and up to 16,384 bytes to ensure we can initialize the header and root directory.
+        reader.seek(SeekFrom::Start(0))?;
+        let mut initial_bytes = Vec::with_capacity(MAX_INITIAL_BYTES);
+        (&mut reader)
+            .take(MAX_INITIAL_BYTES as u64)
+            .read_to_end(&mut initial_bytes)?;
+        if initial_bytes.len() < HEADER_SIZE {
+            return Err(PmtError::InvalidHeader);
+        }
+
+        let initial_bytes = Bytes::from(initial_bytes);
+        let header = Header::try_from_bytes(initial_bytes.slice(..HEADER_SIZE))?;
+        header.check_archive_size(reader.seek(SeekFrom::End(0))?)?;
+
+        let root_start = header.root_offset as usize;
+        let root_end = root_start + header.root_length as usize;
+        let root_directory = if root_start >= HEADER_SIZE && root_end <= initial_bytes.len() {
+            let directory_bytes = initial_bytes.slice(root_start..root_end);
+            Self::read_compressed_directory(header.internal_compression, directory_bytes)?
+        } else {
+            Self::read_directory_at(
+                &mut reader,
+                header.internal_compression,
+                header.root_offset,
+                header.root_length as _,
+            )?
+        };
+
+        Ok(Self {
+            reader,
+            header,
+            root_directory,
+            max_leaf_depth: DEFAULT_MAX_LEAF_DEPTH,
+        })
+    }
+
+    /// Sets how deep leaf directories may be nested before lookups fail with
+    /// [`PmtError::LeafRecursionLimit`]. Defaults to [`DEFAULT_MAX_LEAF_DEPTH`].
+    #[must_use]
+    pub fn with_max_leaf_depth(mut self, max_leaf_depth: u8) -> Self {
+        self.max_leaf_depth = max_leaf_depth;
+        self
+    }
+
+    /// Fetches tile bytes from the archive, as stored (possibly compressed).
+    pub fn get_tile(&mut self, z: u8, x: u64, y: u64) -> PmtResult<Option<Bytes>> {
+        // Zoom levels above 31 have no tiles
+        if z > 31 {
+            return Ok(None);
+        }
+        let Some(entry) = self.find_tile_entry(tile_id(z, x, y))? else {
+            return Ok(None);
+        };
+
+        let offset = self.header.data_offset + entry.offset;
+        Ok(Some(read_range(
+            &mut self.reader,
+            offset,
+            entry.length as _,
+        )?))
+    }
+
+    /// Access header information.
+    pub fn get_header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Gets metadata from the archive.
+    ///
+    /// Note: by spec, this should be valid JSON. This method currently returns a [String].
+    /// This may change in the future.
+    pub fn get_metadata(&mut self) -> PmtResult<String> {
+        let metadata = self.get_metadata_raw()?;
+        let decompressed_metadata = decompress(self.header.internal_compression, metadata)?;
+
+        Ok(String::from_utf8(decompressed_metadata.to_vec())?)
+    }
+
+    /// Gets the metadata as stored in the archive, without decompressing or parsing it.
+    pub fn get_metadata_raw(&mut self) -> PmtResult<Bytes> {
+        read_range(
+            &mut self.reader,
+            self.header.metadata_offset,
+            self.header.metadata_length as _,
+        )
+    }
+
+    /// Consumes the reader, returning the underlying source.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn find_tile_entry(&mut self, tile_id: u64) -> PmtResult<Option<DirEntry>> {
+        let mut entry = self.root_directory.find_tile_id(tile_id).cloned();
+        let mut depth = 0;
+        while let Some(leaf) = entry.as_ref().filter(|e| e.is_leaf()) {
+            depth += 1;
+            if depth > self.max_leaf_depth {
+                return Err(PmtError::LeafRecursionLimit);
+            }
+            let dir = Self::read_directory_at(
+                &mut self.reader,
+                self.header.internal_compression,
+                self.header.leaf_offset + leaf.offset,
+                leaf.length as _,
+            )?;
+            entry = dir.find_tile_id(tile_id).cloned();
+        }
+
+        Ok(entry)
+    }
+
+    fn read_directory_at(
+        reader: &mut R,
+        compression: Compression,
+        offset: u64,
+        length: usize,
+    ) -> PmtResult<Directory> {
+        let data = read_range(reader, offset, length)?;
+        Self::read_compressed_directory(compression, data)
+    }
+
+    fn read_compressed_directory(compression: Compression, bytes: Bytes) -> PmtResult<Directory> {
+        let decompressed_bytes = decompress(compression, bytes)?;
+        Directory::try_from(decompressed_bytes)
+    }
+}
+
+fn read_range<R: Read + Seek>(reader: &mut R, offset: u64, length: usize) -> PmtResult<Bytes> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut data = vec![0; length];
+    reader.read_exact(&mut data)?;
+    Ok(Bytes::from(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use super::PmTilesReader;
+    use crate::directory::DirEntry;
+    use crate::error::PmtError;
+    use crate::tests::{self_referential_leaf_archive, RASTER_FILE, VECTOR_FILE};
+
+    #[test]
+    fn get_raster_tiles() {
+        let mut tiles = PmTilesReader::new_with_path(RASTER_FILE).unwrap();
+        assert_eq!(tiles.get_header().max_zoom, 3);
+
+        let tile = tiles.get_tile(0, 0, 0).unwrap().unwrap();
+        assert_eq!(tile, &include_bytes!("../fixtures/0_0_0.png")[..]);
+        let tile = tiles.get_tile(3, 4, 5).unwrap().unwrap();
+        assert_eq!(tile, &include_bytes!("../fixtures/3_4_5.png")[..]);
+
+        assert!(tiles.get_tile(4, 0, 0).unwrap().is_none());
+        assert!(tiles.get_tile(32, 0, 0).unwrap().is_none());
+    }
+
+    #[test]
+    fn get_tiles_in_leaf_directories() {
+        let data = std::fs::read("fixtures/leaf.pmtiles").unwrap();
+        let mut tiles = PmTilesReader::new(Cursor::new(data)).unwrap();
+
+        assert!(tiles.root_directory.iter().any(DirEntry::is_leaf));
+        assert!(tiles.get_tile(1, 1, 0).unwrap().is_some());
+    }
+
+    #[test]
+    fn uncompressed_directories() {
+        let mut tiles = PmTilesReader::new_with_path("fixtures/uncompressed_dirs.pmtiles").unwrap();
+        let tile = tiles.get_tile(1, 0, 1).unwrap().unwrap();
+        assert_eq!(tile, &b"1/0/1"[..]);
+        assert_eq!(
+            tiles.get_metadata().unwrap(),
+            r#"{"name":"uncompressed directories"}"#
+        );
+        assert_eq!(
+            tiles.get_metadata_raw().unwrap(),
+            &br#"{"name":"uncompressed directories"}"#[..]
+        );
+    }
+
+    #[test]
+    fn multi_member_gzip() {
+        let mut tiles = PmTilesReader::new_with_path("fixtures/multi_member_gzip.pmtiles").unwrap();
+        let tile = tiles.get_tile(2, 3, 3).unwrap().unwrap();
+        assert_eq!(tile, &b"2/3/3"[..]);
+        assert_eq!(
+            tiles.get_metadata().unwrap(),
+            r#"{"name":"multi-member gzip"}"#
+        );
+    }
+
+    #[test]
+    fn truncated_archive() {
+        let mut data = std::fs::read("fixtures/leaf.pmtiles").unwrap();
+        data.pop();
+        assert!(matches!(
+            PmTilesReader::new(Cursor::new(data)),
+            Err(PmtError::TruncatedArchive { .. })
+        ));
+    }
+
+    #[test]
+    fn leaf_recursion_limit() {
+        let data = self_referential_leaf_archive();
+        let mut tiles = PmTilesReader::new(Cursor::new(data)).unwrap();
+        assert!(matches!(
+            tiles.get_tile(0, 0, 0),
+            Err(PmtError::LeafRecursionLimit)
+        ));
+    }
+
+    #[test]
+    fn read_vector_metadata() {
+        let mut tiles = PmTilesReader::new_with_path(VECTOR_FILE).unwrap();
+        let metadata = tiles.get_metadata().unwrap();
+        assert!(metadata.starts_with('{'));
+
+        let compressed = tiles.get_tile(12, 2174, 1492).unwrap().unwrap();
+        let mut tile = Vec::new();
+        flate2::read::GzDecoder::new(&compressed[..])
+            .read_to_end(&mut tile)
+            .unwrap();
+        assert!(!tile.is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use crate::error::{PmtError, PmtResult};
+use crate::tile_id::tile_id;
+use crate::{PmTilesWriter, TileType};
+
+/// A tile file of a `z/x/y` directory tree.
+struct TileFile {
+    tile_id: u64,
+    path: PathBuf,
+    tile_type: TileType,
+    gzip: bool,
+}
+
+/// Converts a directory tree of `src/{z}/{x}/{y}.{ext}` tile files into a `PMTiles` archive written to `dst`.
+///
+/// The tile type is derived from the extension, e.g. `pbf` or `mvt` for vector tiles, and must be
+/// the same for all tiles. Files ending in `.gz`, as well as vector tiles starting with the gzip
+/// magic bytes, are stored as gzip-compressed tiles without recompressing them. Other files and
+/// directories are ignored.
+///
+/// The tiles are written in tile ID order, so the result is clustered. Identical tiles are stored
+/// only once, and the zoom range and bounds are computed from the tiles.
+/// This is the inverse of `AsyncPmTilesReader::export_to_dir`.
+pub fn convert_dir<P: AsRef<Path>>(src: P, dst: P) -> PmtResult<()> {
+    let mut tiles = read_tile_files(src.as_ref())?;
+    tiles.sort_unstable_by_key(|t| t.tile_id);
+    if let Some(pair) = tiles.windows(2).find(|p| p[0].tile_id == p[1].tile_id) {
+        return Err(PmtError::DuplicateTile(pair[0].tile_id));
+    }
+    let tile_type = tiles.first().map_or(TileType::Unknown, |t| t.tile_type);
+    if let Some(other) = tiles.iter().find(|t| t.tile_type != tile_type) {
+        return Err(PmtError::MixedTileTypes(tile_type, other.tile_type));
+    }
+
+    let writer = PmTilesWriter::new(tile_type).dedup(true);
+    let mut writer = writer.create(BufWriter::new(File::create(dst)?))?;
+    for tile in tiles {
+        let data = std::fs::read(&tile.path)?;
+        writer.add_imported_tile(tile.tile_id, &data, tile.gzip)?;
+    }
+
+    writer.finalize()
+}
+
+/// Lists the tile files of a `z/x/y` tree, in no particular order.
+fn read_tile_files(root: &Path) -> PmtResult<Vec<TileFile>> {
+    let mut tiles = Vec::new();
+    for (z, z_path) in numbered_dirs(root)? {
+        for (x, x_path) in numbered_dirs(&z_path)? {
+            for file in std::fs::read_dir(&x_path)? {
+                let file = file?;
+                if !file.file_type()?.is_file() {
+                    continue;
+                }
+                let name = file.file_name();
+                let Some((y, tile_type, gzip)) = name.to_str().and_then(parse_file_name) else {
+                    continue;
+                };
+                let (Ok(z), true) = (u8::try_from(z), z <= 31) else {
+                    return Err(PmtError::InvalidEntry);
+                };
+                if x >> z != 0 || y >> z != 0 {
+                    return Err(PmtError::InvalidEntry);
+                }
+                tiles.push(TileFile {
+                    tile_id: tile_id(z, x, y),
+                    path: file.path(),
+                    tile_type,
+                    gzip,
+                });
+            }
+        }
+    }
+    Ok(tiles)
+}
+
+/// The subdirectories of `dir` whose name is a number, with that number.
+fn numbered_dirs(dir: &Path) -> PmtResult<Vec<(u64, PathBuf)>> {
+    let mut dirs = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let number = entry.file_name().to_str().and_then(|n| n.parse().ok());
+        if let (Some(number), true) = (number, entry.file_type()?.is_dir()) {
+            dirs.push((number, entry.path()));
+        }
+    }
+    Ok(dirs)
+}
+
+/// Parses a `{y}.{ext}` or `{y}.{ext}.gz` file name into the row, tile type and whether it is gzipped.
+fn parse_file_name(name: &str) -> Option<(u64, TileType, bool)> {
+    let (name, gzip) = match name.strip_suffix(".gz") {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    let (y, extension) = name.split_once('.')?;
+    Some((y.parse().ok()?, TileType::from_format(extension)?, gzip))
+}
+
+#[cfg(test)]
+#[cfg(feature = "mmap-async-tokio")]
+mod tests {
+    use std::num::NonZeroU64;
+
+    use futures_util::TryStreamExt as _;
+
+    use super::{convert_dir, parse_file_name};
+    use crate::async_reader::AsyncPmTilesReader;
+    use crate::tests::{get_temp_file_path, RASTER_FILE, VECTOR_FILE};
+    use crate::tile_id::tile_coord;
+    use crate::{Compression, MmapBackend, PmtError, TileType};
+
+    #[test]
+    fn file_names() {
+        assert_eq!(parse_file_name("5.png"), Some((5, TileType::Png, false)));
+        assert_eq!(
+            parse_file_name("12.pbf.gz"),
+            Some((12, TileType::Mvt, true))
+        );
+        assert_eq!(parse_file_name("3.JPG"), Some((3, TileType::Jpeg, false)));
+        assert_eq!(parse_file_name("metadata.json"), None);
+        assert_eq!(parse_file_name("x.png"), None);
+        assert_eq!(parse_file_name("7"), None);
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        for (file, name) in [(RASTER_FILE, "raster"), (VECTOR_FILE, "vector")] {
+            let backend = MmapBackend::try_from(file).await.unwrap();
+            let tiles_in = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+            let root = get_temp_file_path(&format!("convert-dir-{name}"));
+            let _ = std::fs::remove_dir_all(&root);
+            tiles_in.export_to_dir(&root).await.unwrap();
+            std::fs::write(root.join("metadata.json"), "{}").unwrap();
+
+            let dst = get_temp_file_path(&format!("convert-dir-{name}.pmtiles"));
+            convert_dir(&root, &dst).unwrap();
+            std::fs::remove_dir_all(&root).unwrap();
+
+            let backend = MmapBackend::try_from(&dst).await.unwrap();
+            let tiles_out = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+            let (header_in, header_out) = (tiles_in.get_header(), tiles_out.get_header());
+            assert_eq!(header_out.tile_type, header_in.tile_type);
+            assert_eq!(header_out.tile_compression, header_in.tile_compression);
+            assert_eq!(header_out.n_addressed_tiles, header_in.n_addressed_tiles);
+            assert_eq!(header_out.max_zoom, header_in.max_zoom);
+            assert!(header_out.is_clustered());
+
+            let ids: Vec<u64> = tiles_in
+                .stream_all_tiles()
+                .map_ok(|(id, _)| id)
+                .try_collect()
+                .await
+                .unwrap();
+            for id in ids {
+                let (z, x, y) = tile_coord(id);
+                assert_eq!(
+                    tiles_out.get_tile_zxy(z, x, y).await.unwrap(),
+                    tiles_in.get_tile_zxy(z, x, y).await.unwrap(),
+                    "tile {z}/{x}/{y}"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn gzipped_files() {
+        let root = get_temp_file_path("convert-dir-gzip");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("1/0")).unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, b"tile").unwrap();
+        let gzipped = encoder.finish().unwrap();
+        std::fs::write(root.join("1/0/0.png.gz"), &gzipped).unwrap();
+        std::fs::write(root.join("1/0/1.png.gz"), &gzipped).unwrap();
+
+        let dst = get_temp_file_path("convert-dir-gzip.pmtiles");
+        convert_dir(&root, &dst).unwrap();
+
+        let backend = MmapBackend::try_from(&dst).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let header = tiles.get_header();
+        assert_eq!(header.tile_compression, Compression::Gzip);
+        assert_eq!((header.min_zoom, header.max_zoom), (1, 1));
+        assert_eq!(header.n_addressed_tiles, NonZeroU64::new(2));
+        assert_eq!(header.n_tile_contents, NonZeroU64::new(1));
+        assert_eq!(
+            tiles.get_tile_zxy(1, 0, 1).await.unwrap().unwrap(),
+            &b"tile"[..]
+        );
+
+        std::fs::write(root.join("1/0/1.webp"), b"other").unwrap();
+        assert!(matches!(
+            convert_dir(&root, &dst),
+            Err(PmtError::DuplicateTile(_))
+        ));
+        std::fs::create_dir_all(root.join("2/0")).unwrap();
+        std::fs::write(root.join("2/0/0.webp"), b"other").unwrap();
+        std::fs::remove_file(root.join("1/0/1.webp")).unwrap();
+        assert!(matches!(
+            convert_dir(&root, &dst),
+            Err(PmtError::MixedTileTypes(TileType::Png, TileType::Webp))
+        ));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
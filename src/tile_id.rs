@@ -0,0 +1,211 @@
+//! Conversion between `z/x/y` tile coordinates and `PMTiles` tile IDs.
+//!
+//! Tile IDs enumerate all tiles of the pyramid, zoom level by zoom level, following a
+//! Hilbert curve within each level. The first tile ID of zoom level `z` is `(4^z - 1) / 3`.
+#![allow(clippy::unreadable_literal)]
+
+const PYRAMID_SIZE_BY_ZOOM: [u64; 21] = [
+    /*  0 */ 0,
+    /*  1 */ 1,
+    /*  2 */ 5,
+    /*  3 */ 21,
+    /*  4 */ 85,
+    /*  5 */ 341,
+    /*  6 */ 1365,
+    /*  7 */ 5461,
+    /*  8 */ 21845,
+    /*  9 */ 87381,
+    /* 10 */ 349525,
+    /* 11 */ 1398101,
+    /* 12 */ 5592405,
+    /* 13 */ 22369621,
+    /* 14 */ 89478485,
+    /* 15 */ 357913941,
+    /* 16 */ 1431655765,
+    /* 17 */ 5726623061,
+    /* 18 */ 22906492245,
+    /* 19 */ 91625968981,
+    /* 20 */ 366503875925,
+];
+
+/// Number of tiles in all zoom levels below `z`, i.e. the first tile ID of zoom level `z`.
+fn base_id(z: u8) -> u64 {
+    let z_ind = usize::from(z);
+    if z_ind < PYRAMID_SIZE_BY_ZOOM.len() {
+        PYRAMID_SIZE_BY_ZOOM[z_ind]
+    } else {
+        let last_ind = PYRAMID_SIZE_BY_ZOOM.len() - 1;
+        PYRAMID_SIZE_BY_ZOOM[last_ind] + (last_ind..z_ind).map(|i| 1_u64 << (i << 1)).sum::<u64>()
+    }
+}
+
+/// Convert a `z/x/y` tile coordinate into a `PMTiles` tile ID.
+///
+/// # Panics
+///
+/// Panics if `z` is above 31, as the tile IDs of those zoom levels do not fit into `u64`.
+#[must_use]
+pub fn tile_id(z: u8, x: u64, y: u64) -> u64 {
+    assert!(z <= 31, "zoom level {z} is above the maximum of 31");
+    // The 0/0/0 case is not needed for the base id computation, but it will fail hilbert_2d::u64::xy2h_discrete
+    if z == 0 {
+        return 0;
+    }
+
+    let tile_id = hilbert_2d::u64::xy2h_discrete(x, y, z.into(), hilbert_2d::Variant::Hilbert);
+
+    base_id(z) + tile_id
+}
+
+/// Convert a `PMTiles` tile ID back into its `(z, x, y)` tile coordinate.
+///
+/// This is the inverse of [`tile_id`].
+#[must_use]
+pub fn tile_coord(tile_id: u64) -> (u8, u64, u64) {
+    if tile_id == 0 {
+        return (0, 0, 0);
+    }
+
+    // Zoom levels above 31 do not fit into `u64` tile IDs.
+    let mut z = 0;
+    let mut base_id = 0;
+    while z < 31 {
+        let next_base_id = base_id + (1_u64 << (u32::from(z) << 1));
+        if tile_id < next_base_id {
+            break;
+        }
+        base_id = next_base_id;
+        z += 1;
+    }
+
+    let (x, y) =
+        hilbert_2d::u64::h2xy_discrete(tile_id - base_id, z.into(), hilbert_2d::Variant::Hilbert);
+
+    (z, x, y)
+}
+
+/// All tile IDs of zoom level `z`, from `(4^z - 1) / 3` for the `4^z` tiles of the level.
+///
+/// The range is empty for zoom levels above 31, as their tile IDs do not fit into `u64`.
+#[must_use]
+pub fn tile_ids_for_zoom(z: u8) -> std::ops::Range<u64> {
+    if z > 31 {
+        return 0..0;
+    }
+    base_id(z)..base_id(z + 1)
+}
+
+/// Convert a WGS84 longitude/latitude position into the `x/y` coordinate of the
+/// Web Mercator tile containing it at zoom level `z`.
+///
+/// Positions outside the Web Mercator extent are clamped to the nearest tile.
+#[cfg(feature = "__async")]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub(crate) fn lon_lat_to_tile(z: u8, lon: f64, lat: f64) -> (u64, u64) {
+    let n = f64::from(1_u32 << z.min(31));
+    let max = (n - 1.).max(0.);
+    let lat = lat
+        .clamp(-85.051_128_779_806_59, 85.051_128_779_806_59)
+        .to_radians();
+
+    let x = ((lon + 180.) / 360. * n).floor().clamp(0., max);
+    let y = ((1. - lat.tan().asinh() / std::f64::consts::PI) / 2. * n)
+        .floor()
+        .clamp(0., max);
+
+    (x as u64, y as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{tile_coord, tile_id, tile_ids_for_zoom};
+
+    #[test]
+    fn test_tile_id() {
+        assert_eq!(tile_id(0, 0, 0), 0);
+        assert_eq!(tile_id(1, 1, 0), 4);
+        assert_eq!(tile_id(2, 1, 3), 11);
+        assert_eq!(tile_id(3, 3, 0), 26);
+        assert_eq!(tile_id(20, 0, 0), 366503875925);
+        assert_eq!(tile_id(21, 0, 0), 1466015503701);
+        assert_eq!(tile_id(22, 0, 0), 5864062014805);
+        assert_eq!(tile_id(22, 0, 0), 5864062014805);
+        assert_eq!(tile_id(23, 0, 0), 23456248059221);
+        assert_eq!(tile_id(24, 0, 0), 93824992236885);
+        assert_eq!(tile_id(25, 0, 0), 375299968947541);
+        assert_eq!(tile_id(26, 0, 0), 1501199875790165);
+        assert_eq!(tile_id(27, 0, 0), 6004799503160661);
+        assert_eq!(tile_id(28, 0, 0), 24019198012642645);
+        assert_eq!(tile_id(31, 0, 0), tile_ids_for_zoom(31).start);
+    }
+
+    #[test]
+    #[should_panic(expected = "zoom level 32 is above the maximum of 31")]
+    fn test_tile_id_zoom_too_high() {
+        let _ = tile_id(32, 0, 0);
+    }
+
+    #[test]
+    fn test_tile_coord() {
+        assert_eq!(tile_coord(0), (0, 0, 0));
+        assert_eq!(tile_coord(4), (1, 1, 0));
+        assert_eq!(tile_coord(11), (2, 1, 3));
+        assert_eq!(tile_coord(26), (3, 3, 0));
+        assert_eq!(tile_coord(366503875925), (20, 0, 0));
+        assert_eq!(tile_coord(24019198012642645), (28, 0, 0));
+    }
+
+    #[test]
+    fn test_tile_ids_for_zoom() {
+        assert_eq!(tile_ids_for_zoom(0), 0..1);
+        assert_eq!(tile_ids_for_zoom(1), 1..5);
+        assert_eq!(tile_ids_for_zoom(3), 21..85);
+        for z in 0..=31 {
+            let ids = tile_ids_for_zoom(z);
+            assert_eq!(ids.end - ids.start, 1 << (2 * u32::from(z)));
+            assert_eq!(tile_coord(ids.start).0, z);
+            assert_eq!(tile_coord(ids.end - 1).0, z);
+        }
+        assert!(tile_ids_for_zoom(32).is_empty());
+        assert!(tile_ids_for_zoom(u8::MAX).is_empty());
+    }
+
+    fn assert_round_trip(max_zoom: u8) {
+        for z in 0..=max_zoom {
+            let size = 1_u64 << z;
+            for x in 0..size {
+                for y in 0..size {
+                    assert_eq!(tile_coord(tile_id(z, x, y)), (z, x, y));
+                }
+            }
+        }
+    }
+
+    /// Exhaustive up to zoom level 10 (~1.4M tiles) to stay fast in debug builds,
+    /// higher zoom levels are spot-checked below.
+    #[test]
+    fn test_tile_id_round_trip() {
+        assert_round_trip(10);
+    }
+
+    #[test]
+    fn test_tile_id_round_trip_high_zoom() {
+        for z in 11..=31 {
+            let max = (1_u64 << z) - 1;
+            for (x, y) in [(0, 0), (0, max), (max, 0), (max, max), (max / 3, max / 7)] {
+                assert_eq!(tile_coord(tile_id(z, x, y)), (z, x, y));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "__async")]
+    fn test_lon_lat_to_tile() {
+        use super::lon_lat_to_tile;
+
+        assert_eq!(lon_lat_to_tile(0, 11.25, 43.77), (0, 0));
+        assert_eq!(lon_lat_to_tile(1, -180.0, 85.0), (0, 0));
+        assert_eq!(lon_lat_to_tile(1, 180.0, -85.0), (1, 1));
+        assert_eq!(lon_lat_to_tile(12, 11.25, 43.77), (2176, 1493));
+    }
+}
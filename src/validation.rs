@@ -0,0 +1,110 @@
+use thiserror::Error;
+
+use crate::{Compression, TileType};
+
+/// A section of a `PMTiles` archive, as described by the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    RootDirectory,
+    Metadata,
+    LeafDirectories,
+    TileData,
+}
+
+/// A structural problem found by [`AsyncPmTilesReader::validate`](crate::async_reader::AsyncPmTilesReader::validate).
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ValidationIssue {
+    #[error("{0:?} section ends beyond the end of the file")]
+    SectionOutOfBounds(Section),
+    #[error("Directory at offset {offset} could not be read: {reason}")]
+    UnreadableDirectory { offset: u64, reason: String },
+    #[error(
+        "Leaf directory at offset {offset} (length {length}) exceeds the leaf directories section"
+    )]
+    LeafOutOfBounds { offset: u64, length: u32 },
+    #[error("Leaf directories are nested too deep at offset {0}")]
+    DirectoryTooDeep(u64),
+    #[error("Tile ID {tile_id} does not follow {previous}")]
+    TileIdNotIncreasing { tile_id: u64, previous: u64 },
+    #[error("Tile {tile_id} at offset {offset} (length {length}) exceeds the tile data section")]
+    TileOutOfBounds {
+        tile_id: u64,
+        offset: u64,
+        length: u32,
+    },
+    #[error("Tile {tile_id} could not be read: {reason}")]
+    UnreadableTile { tile_id: u64, reason: String },
+    #[error("Tile {tile_id} could not be decompressed: {reason}")]
+    TileDecompressionFailed { tile_id: u64, reason: String },
+}
+
+/// An inconsistency found by [`AsyncPmTilesReader::validate`](crate::async_reader::AsyncPmTilesReader::validate),
+/// which does not prevent reading the archive, but hints at a buggy converter.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ValidationWarning {
+    #[error("Header tile type {tile_type:?} does not match the metadata format {format:?}")]
+    TileTypeMismatch { tile_type: TileType, format: String },
+    #[error("Tile compression {compression:?} is unusual for {tile_type:?} tiles")]
+    UnusualTileCompression {
+        tile_type: TileType,
+        compression: Compression,
+    },
+}
+
+/// Whether `compression` is what tiles of `tile_type` are usually stored with.
+///
+/// Vector tiles are usually compressed, while image formats are compressed already.
+pub(crate) fn is_usual_tile_compression(tile_type: TileType, compression: Compression) -> bool {
+    match tile_type {
+        TileType::Unknown => true,
+        TileType::Mvt => compression != Compression::None,
+        TileType::Png | TileType::Jpeg | TileType::Webp | TileType::Avif => {
+            compression == Compression::None
+        }
+    }
+}
+
+/// The result of validating an archive. Lists every problem that was found.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub(crate) issues: Vec<ValidationIssue>,
+    pub(crate) warnings: Vec<ValidationWarning>,
+    pub(crate) n_directories: u64,
+    pub(crate) n_tile_entries: u64,
+}
+
+impl ValidationReport {
+    /// Returns `true` if no problems were found. Warnings are not taken into account.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// All problems that were found, in the order they were encountered.
+    #[must_use]
+    pub fn issues(&self) -> &[ValidationIssue] {
+        &self.issues
+    }
+
+    /// Inconsistencies between header fields and the metadata, which do not prevent reading the archive.
+    #[must_use]
+    pub fn warnings(&self) -> &[ValidationWarning] {
+        &self.warnings
+    }
+
+    /// Number of directories that were read, including the root directory.
+    #[must_use]
+    pub fn n_directories(&self) -> u64 {
+        self.n_directories
+    }
+
+    /// Number of tile entries found in all directories.
+    #[must_use]
+    pub fn n_tile_entries(&self) -> u64 {
+        self.n_tile_entries
+    }
+
+    pub(crate) fn push(&mut self, issue: ValidationIssue) {
+        self.issues.push(issue);
+    }
+}
@@ -0,0 +1,1697 @@
+#[cfg(feature = "write")]
+use std::collections::HashMap;
+#[cfg(feature = "write")]
+use std::fs::File;
+#[cfg(feature = "write")]
+use std::hash::BuildHasher;
+#[cfg(any(feature = "write", feature = "sync"))]
+use std::io::Read as _;
+use std::io::Write;
+#[cfg(feature = "write")]
+use std::io::{BufWriter, Seek, SeekFrom};
+#[cfg(feature = "write")]
+use std::num::NonZeroU64;
+#[cfg(feature = "write")]
+use std::path::{Path, PathBuf};
+
+#[cfg(any(feature = "write", feature = "sync"))]
+use bytes::Bytes;
+
+#[cfg(feature = "write")]
+use crate::directory::{entries_clustered, DirEntry, Directory};
+#[cfg(any(feature = "write", feature = "sync"))]
+use crate::error::{PmtError, PmtResult};
+#[cfg(feature = "write")]
+use crate::header::{HEADER_SIZE, MAX_INITIAL_BYTES};
+#[cfg(feature = "write")]
+use crate::tile_id::tile_coord;
+#[cfg(any(feature = "write", feature = "sync"))]
+use crate::Compression;
+#[cfg(feature = "write")]
+use crate::{Header, HeaderBuilder, TileType};
+
+/// Serialization of `PMTiles` structures into their binary representation.
+pub trait WriteTo {
+    /// Writes the binary representation of `self` into `writer`.
+    fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()>;
+}
+
+/// Builder for a [`PmTilesStreamWriter`].
+///
+/// ```no_run
+/// # use pmtiles::{PmTilesWriter, TileType};
+/// # fn main() -> pmtiles::PmtResult<()> {
+/// let file = std::fs::File::create("out.pmtiles")?;
+/// let mut writer = PmTilesWriter::new(TileType::Png).create(file)?;
+/// writer.add_tile(0, &[0x89, 0x50, 0x4E, 0x47])?;
+/// writer.finalize()?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "write")]
+pub struct PmTilesWriter {
+    header: Header,
+    metadata: String,
+    /// Zoom range declared in metadata set with `with_metadata`, checked against the header
+    #[cfg(feature = "metadata")]
+    metadata_zooms: Option<(Option<u8>, Option<u8>)>,
+    dedup: Option<Dedup>,
+    /// Alignment of the tile data offsets in the file, 1 for none
+    tile_alignment: u64,
+    overrides: Overrides,
+}
+
+/// How duplicate tiles are found, see [`PmTilesWriter::dedup`].
+#[cfg(feature = "write")]
+#[derive(Default)]
+struct Dedup {
+    #[cfg(feature = "sha256")]
+    sha256: bool,
+    hasher: ContentHasher,
+}
+
+#[cfg(feature = "write")]
+type HashFn = dyn Fn(&[u8]) -> u64 + Send + Sync;
+
+/// Hasher of the tile contents, boxed so that the writers have no hasher type parameter.
+#[cfg(feature = "write")]
+struct ContentHasher(Box<HashFn>);
+
+#[cfg(feature = "write")]
+impl Default for ContentHasher {
+    fn default() -> Self {
+        Self::new(xxhash_rust::xxh3::Xxh3DefaultBuilder::new())
+    }
+}
+
+#[cfg(feature = "write")]
+impl ContentHasher {
+    fn new<S: BuildHasher + Send + Sync + 'static>(hasher: S) -> Self {
+        Self(Box::new(move |data| hasher.hash_one(data)))
+    }
+}
+
+/// Key of the tile contents identifying duplicate tiles, together with their length.
+#[cfg(feature = "write")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ContentKey {
+    /// Hash of the contents, which are compared on collisions
+    Hash(u64),
+    /// SHA-256 digest of the contents, which are not kept
+    #[cfg(feature = "sha256")]
+    Sha256([u8; 32]),
+}
+
+/// A tile written before, with its contents if they are needed to tell collisions apart.
+#[cfg(feature = "write")]
+struct WrittenTile {
+    data: Option<Box<[u8]>>,
+    offset: u64,
+    length: u32,
+}
+
+/// Offset and length of the data of the tiles written so far, to find duplicates.
+#[cfg(feature = "write")]
+struct WrittenTiles {
+    #[cfg(feature = "sha256")]
+    sha256: bool,
+    hasher: ContentHasher,
+    by_key: HashMap<(ContentKey, usize), Vec<WrittenTile>>,
+}
+
+#[cfg(feature = "write")]
+impl WrittenTiles {
+    fn key(&self, data: &[u8]) -> (ContentKey, usize) {
+        #[cfg(feature = "sha256")]
+        if self.sha256 {
+            use sha2::Digest as _;
+            return (
+                ContentKey::Sha256(sha2::Sha256::digest(data).into()),
+                data.len(),
+            );
+        }
+        (ContentKey::Hash((self.hasher.0)(data)), data.len())
+    }
+
+    fn get(&self, key: (ContentKey, usize), data: &[u8]) -> Option<(u64, u32)> {
+        let tiles = self.by_key.get(&key)?;
+        tiles
+            .iter()
+            .find(|t| t.data.as_deref().map_or(true, |d| d == data))
+            .map(|t| (t.offset, t.length))
+    }
+
+    fn insert(&mut self, key: (ContentKey, usize), data: &[u8], (offset, length): (u64, u32)) {
+        let data = matches!(key.0, ContentKey::Hash(_)).then(|| data.into());
+        self.by_key.entry(key).or_default().push(WrittenTile {
+            data,
+            offset,
+            length,
+        });
+    }
+}
+
+/// Header fields set explicitly on the [`PmTilesWriter`], which are not computed from the tiles.
+#[cfg(feature = "write")]
+#[derive(Debug, Clone, Copy, Default)]
+#[allow(clippy::struct_excessive_bools)]
+struct Overrides {
+    min_zoom: bool,
+    max_zoom: bool,
+    bounds: bool,
+    center: bool,
+}
+
+/// Zoom range of the added tiles, and their extent at the highest zoom level.
+#[cfg(feature = "write")]
+#[derive(Debug, Clone, Copy)]
+struct TileExtent {
+    min_zoom: u8,
+    max_zoom: u8,
+    min_x: u64,
+    min_y: u64,
+    max_x: u64,
+    max_y: u64,
+}
+
+#[cfg(feature = "write")]
+impl TileExtent {
+    /// Extends the extent by a tile. Tiles are added in tile ID order, so zoom levels never decrease.
+    fn add(extent: &mut Option<Self>, z: u8, x: u64, y: u64) {
+        match extent {
+            Some(e) if e.max_zoom == z => {
+                e.min_x = e.min_x.min(x);
+                e.min_y = e.min_y.min(y);
+                e.max_x = e.max_x.max(x);
+                e.max_y = e.max_y.max(y);
+            }
+            _ => {
+                *extent = Some(Self {
+                    min_zoom: extent.map_or(z, |e| e.min_zoom),
+                    max_zoom: z,
+                    min_x: x,
+                    min_y: y,
+                    max_x: x,
+                    max_y: y,
+                });
+            }
+        }
+    }
+
+    /// Bounds as `[min_lon, min_lat, max_lon, max_lat]`.
+    #[allow(clippy::cast_possible_truncation)]
+    fn bounds(&self) -> [f32; 4] {
+        let (west, north) = tile_corner(self.max_zoom, self.min_x, self.min_y);
+        let (east, south) = tile_corner(self.max_zoom, self.max_x + 1, self.max_y + 1);
+        [west, south, east, north].map(|v| v as f32)
+    }
+
+    /// Center of the extent as `(lon, lat)`, halfway between the tile rows in the Web Mercator projection.
+    #[allow(clippy::cast_possible_truncation)]
+    fn center(&self) -> (f32, f32) {
+        let [west, _, east, _] = self.bounds();
+        // One zoom level deeper, row `min_y + max_y + 1` lies halfway between both edges
+        let (_, lat) = tile_corner(self.max_zoom + 1, 0, self.min_y + self.max_y + 1);
+        ((west + east) / 2., lat as f32)
+    }
+}
+
+/// Longitude and latitude of the top left corner of the tile `x`/`y` at zoom `z`.
+#[cfg(feature = "write")]
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn tile_corner(z: u8, x: u64, y: u64) -> (f64, f64) {
+    let n = (1_u64 << z) as f64;
+    let lon = x as f64 / n * 360.0 - 180.0;
+    let lat = (std::f64::consts::PI * (1.0 - 2.0 * y as f64 / n))
+        .sinh()
+        .atan()
+        .to_degrees();
+    (lon, lat)
+}
+
+/// Writer producing a `PMTiles` archive while tiles are added.
+///
+/// Tile data is written to the output immediately, only directory entries are kept in memory.
+/// The archive uses the following layout, so that the header, root directory and metadata
+/// can be written first, and leaf directories appended after the tile data:
+///
+/// ```text
+/// +--------+----------------+----------+-----------+------------------+
+/// |        |                |          |           |                  |
+/// | Header | Root Directory | Metadata | Tile Data | Leaf Directories |
+/// |        |                |          |           |                  |
+/// +--------+----------------+----------+-----------+------------------+
+/// ```
+///
+/// The header and the root directory are only written by [`Self::finalize`].
+/// Dropping the writer without finalizing it leaves an invalid archive behind.
+#[cfg(feature = "write")]
+pub struct PmTilesStreamWriter<W: Write + Seek> {
+    out: W,
+    header: Header,
+    entries: Vec<DirEntry>,
+    n_addressed_tiles: u64,
+    n_tile_contents: u64,
+    prev_tile_data: Vec<u8>,
+    written_tiles: Option<WrittenTiles>,
+    tile_alignment: u64,
+    /// Zoom range declared in metadata set with `with_metadata`, checked against the header
+    #[cfg(feature = "metadata")]
+    metadata_zooms: Option<(Option<u8>, Option<u8>)>,
+    overrides: Overrides,
+    extent: Option<TileExtent>,
+    /// ID of the last added tile, including empty tiles
+    last_tile_id: Option<u64>,
+}
+
+#[cfg(feature = "write")]
+impl PmTilesWriter {
+    /// Create a new writer builder for tiles of the given type.
+    ///
+    /// Vector tiles are gzip-compressed by default, all other tile types are stored as is.
+    #[must_use]
+    pub fn new(tile_type: TileType) -> Self {
+        let header = HeaderBuilder::new(tile_type).header;
+        Self {
+            header,
+            metadata: "{}".to_string(),
+            #[cfg(feature = "metadata")]
+            metadata_zooms: None,
+            dedup: None,
+            tile_alignment: 1,
+            overrides: Overrides::default(),
+        }
+    }
+
+    /// Create a writer builder with the tile type, compressions, zoom range, bounds and center
+    /// of an existing header, e.g. one built with a [`HeaderBuilder`].
+    ///
+    /// These fields are written as is instead of being computed from the tiles. The section
+    /// offsets and lengths, tile counts and clustered flag are set by the writer.
+    #[must_use]
+    pub fn from_header(header: &Header) -> Self {
+        let mut writer = Self::new(header.tile_type);
+        let h = &mut writer.header;
+        h.internal_compression = header.internal_compression;
+        h.tile_compression = header.tile_compression;
+        h.min_zoom = header.min_zoom;
+        h.max_zoom = header.max_zoom;
+        h.min_longitude = header.min_longitude;
+        h.min_latitude = header.min_latitude;
+        h.max_longitude = header.max_longitude;
+        h.max_latitude = header.max_latitude;
+        h.center_zoom = header.center_zoom;
+        h.center_longitude = header.center_longitude;
+        h.center_latitude = header.center_latitude;
+        writer.overrides = Overrides {
+            min_zoom: true,
+            max_zoom: true,
+            bounds: true,
+            center: true,
+        };
+        writer
+    }
+
+    /// Set the type of the tiles, replacing the one passed to [`PmTilesWriter::new`].
+    ///
+    /// The tile compression is left as is, see [`PmTilesWriter::with_tile_compression`].
+    #[must_use]
+    pub fn with_tile_type(mut self, tile_type: TileType) -> Self {
+        self.header.tile_type = tile_type;
+        self
+    }
+
+    /// Set the compression of the directories and metadata, gzip by default.
+    #[must_use]
+    pub fn with_internal_compression(mut self, compression: Compression) -> Self {
+        self.header.internal_compression = compression;
+        self
+    }
+
+    /// Set the compression applied to the tile data passed to [`PmTilesStreamWriter::add_tile`].
+    ///
+    /// Defaults to gzip for vector tiles and no compression for all other tile types.
+    /// Use [`Compression::None`] to store tiles as is.
+    #[must_use]
+    pub fn with_tile_compression(mut self, compression: Compression) -> Self {
+        self.header.tile_compression = compression;
+        self
+    }
+
+    /// Set the minimum zoom level of the tiles.
+    ///
+    /// By default, the lowest zoom level of the added tiles is used.
+    #[must_use]
+    pub fn with_min_zoom(mut self, level: u8) -> Self {
+        self.header.min_zoom = level;
+        self.overrides.min_zoom = true;
+        self
+    }
+
+    /// Set the maximum zoom level of the tiles.
+    ///
+    /// By default, the highest zoom level of the added tiles is used.
+    #[must_use]
+    pub fn with_max_zoom(mut self, level: u8) -> Self {
+        self.header.max_zoom = level;
+        self.overrides.max_zoom = true;
+        self
+    }
+
+    /// Set the bounds of the tiles.
+    ///
+    /// By default, the bounds are computed from the extent of the tiles at the highest zoom level.
+    #[must_use]
+    pub fn with_bounds(mut self, min_lon: f32, min_lat: f32, max_lon: f32, max_lat: f32) -> Self {
+        self.header.min_longitude = min_lon;
+        self.header.min_latitude = min_lat;
+        self.header.max_longitude = max_lon;
+        self.header.max_latitude = max_lat;
+        self.overrides.bounds = true;
+        self
+    }
+
+    /// Set the center position and zoom level.
+    ///
+    /// By default, the center of the tiles at the highest zoom level is used, with that zoom level.
+    #[must_use]
+    pub fn with_center(mut self, longitude: f32, latitude: f32, zoom: u8) -> Self {
+        self.header.center_longitude = longitude;
+        self.header.center_latitude = latitude;
+        self.header.center_zoom = zoom;
+        self.overrides.center = true;
+        self
+    }
+
+    /// Set the JSON metadata of the archive.
+    #[must_use]
+    pub fn with_raw_metadata(mut self, metadata: &str) -> Self {
+        metadata.clone_into(&mut self.metadata);
+        #[cfg(feature = "metadata")]
+        {
+            self.metadata_zooms = None;
+        }
+        self
+    }
+
+    /// Set the metadata of the archive, serialized as JSON.
+    ///
+    /// A warning is logged when the archive is finalized if the `minzoom` or `maxzoom`
+    /// of the metadata differ from the zoom levels of the header.
+    #[cfg(feature = "metadata")]
+    #[must_use]
+    // Serializing into a string cannot fail, all map keys are strings
+    #[allow(clippy::missing_panics_doc, clippy::unwrap_used)]
+    pub fn with_metadata(mut self, metadata: &crate::Metadata) -> Self {
+        let json = serde_json::to_string(metadata).unwrap();
+        self.metadata = json;
+        self.metadata_zooms = Some((metadata.minzoom, metadata.maxzoom));
+        self
+    }
+
+    /// Deduplicate tiles with identical contents, storing their data only once.
+    ///
+    /// Tiles are looked up by their length and `XXH3` hash, and their contents are compared
+    /// on hash collisions, so the contents of all distinct tiles are kept in memory while
+    /// writing. With the `sha256` feature, `dedup_sha256` keeps only a digest instead.
+    #[must_use]
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup.then(Dedup::default);
+        self
+    }
+
+    /// Deduplicate tiles as with [`Self::dedup`], identifying them by their SHA-256 digest instead.
+    ///
+    /// Only the digest and the location of the data are kept in memory for each distinct tile,
+    /// the contents are not compared. Accidental collisions of this digest are practically
+    /// impossible, also for tiles crafted to collide.
+    #[cfg(feature = "sha256")]
+    #[must_use]
+    pub fn dedup_sha256(mut self) -> Self {
+        self.dedup.get_or_insert_with(Dedup::default).sha256 = true;
+        self
+    }
+
+    /// Deduplicate tiles as with [`Self::dedup`], hashing the tile contents with `hasher`
+    /// instead of `XXH3`. As contents are compared on hash collisions, a weaker but faster
+    /// hasher never merges different tiles, it only costs comparisons.
+    #[must_use]
+    pub fn dedup_with_hasher<S: BuildHasher + Send + Sync + 'static>(mut self, hasher: S) -> Self {
+        self.dedup.get_or_insert_with(Dedup::default).hasher = ContentHasher::new(hasher);
+        self
+    }
+
+    /// Pad the tile data so that the file offset of each tile is a multiple of `bytes`,
+    /// e.g. the page size for memory-mapped reads.
+    ///
+    /// The padding grows the archive, and its gaps mean the archive is no longer clustered.
+    /// Values of 0 and 1 disable alignment.
+    #[must_use]
+    pub fn align_tiles(mut self, bytes: u64) -> Self {
+        self.tile_alignment = bytes.max(1);
+        self
+    }
+
+    /// Create a new `PMTiles` writer, writing the archive into `writer`.
+    pub fn create<W: Write + Seek>(self, writer: W) -> PmtResult<PmTilesStreamWriter<W>> {
+        let mut writer = PmTilesStreamWriter {
+            out: writer,
+            header: self.header,
+            entries: Vec::new(),
+            n_addressed_tiles: 0,
+            n_tile_contents: 0,
+            prev_tile_data: Vec::new(),
+            written_tiles: self.dedup.map(|dedup| WrittenTiles {
+                #[cfg(feature = "sha256")]
+                sha256: dedup.sha256,
+                hasher: dedup.hasher,
+                by_key: HashMap::new(),
+            }),
+            tile_alignment: self.tile_alignment,
+            #[cfg(feature = "metadata")]
+            metadata_zooms: self.metadata_zooms,
+            overrides: self.overrides,
+            extent: None,
+            last_tile_id: None,
+        };
+
+        // Reserve space for the header and the root directory, which are written on finalize
+        writer.out.write_all(&[0; MAX_INITIAL_BYTES])?;
+
+        let metadata = compress(writer.header.internal_compression, self.metadata.as_bytes())?;
+        writer.out.write_all(&metadata)?;
+        writer.header.metadata_offset = MAX_INITIAL_BYTES as u64;
+        writer.header.metadata_length = metadata.len() as u64;
+        let metadata_end = writer.header.metadata_offset + writer.header.metadata_length;
+        writer.header.data_offset = metadata_end.next_multiple_of(writer.tile_alignment);
+        writer.write_padding(writer.header.data_offset - metadata_end)?;
+
+        Ok(writer)
+    }
+
+    /// Create a `PMTiles` writer accepting tiles in any order, writing the archive into `writer`.
+    ///
+    /// Tile data is buffered in a new file at `temp_path`, and only copied into the archive
+    /// in tile ID order by [`PmTilesUnsortedWriter::finalize`]. The temporary file is removed
+    /// when the writer is finalized or dropped.
+    pub fn create_unsorted<W: Write + Seek, P: AsRef<Path>>(
+        self,
+        writer: W,
+        temp_path: P,
+    ) -> PmtResult<PmTilesUnsortedWriter<W>> {
+        let temp_path = temp_path.as_ref().to_path_buf();
+        let spill = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+        Ok(PmTilesUnsortedWriter {
+            writer: self.create(writer)?,
+            spill: BufWriter::new(spill),
+            spill_length: 0,
+            temp_file: TempFile(temp_path),
+            tiles: Vec::new(),
+        })
+    }
+
+    /// Create a `PMTiles` writer for outputs which cannot seek, e.g. pipes or uploads.
+    ///
+    /// The archive is written to a new file at `temp_path` first, and copied into `writer`
+    /// from start to end by [`PmTilesSeeklessWriter::finalize`]. The temporary file is
+    /// removed when the writer is finalized or dropped.
+    pub fn create_seekless<W: Write, P: AsRef<Path>>(
+        self,
+        writer: W,
+        temp_path: P,
+    ) -> PmtResult<PmTilesSeeklessWriter<W>> {
+        let temp_path = temp_path.as_ref().to_path_buf();
+        let temp = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+        let temp_file = TempFile(temp_path);
+        Ok(PmTilesSeeklessWriter {
+            writer: self.create(BufWriter::new(temp.try_clone()?))?,
+            temp,
+            temp_file,
+            out: writer,
+        })
+    }
+}
+
+#[cfg(feature = "write")]
+impl<W: Write + Seek> PmTilesStreamWriter<W> {
+    /// Add a tile to the archive, writing its data to the output immediately.
+    ///
+    /// Tiles must be added in strictly ascending tile ID order, otherwise
+    /// [`PmtError::TilesOutOfOrder`] is returned. Empty tiles are skipped, and
+    /// consecutive tiles with identical data are stored as a single run-length encoded entry.
+    /// With [`PmTilesWriter::dedup`] enabled, non-consecutive duplicates reference the data
+    /// written for the first occurrence.
+    pub fn add_tile(&mut self, tile_id: u64, data: &[u8]) -> PmtResult<()> {
+        self.add_tile_data(tile_id, data, true)
+    }
+
+    /// Add a tile imported from another tile store. Tiles known to be `gzip`-compressed and
+    /// vector tiles starting with the gzip magic bytes are stored as is, as vector tiles are
+    /// usually stored gzipped. Other tiles are added with [`Self::add_tile`].
+    pub(crate) fn add_imported_tile(
+        &mut self,
+        tile_id: u64,
+        data: &[u8],
+        gzip: bool,
+    ) -> PmtResult<()> {
+        if gzip || (self.header.tile_type == TileType::Mvt && data.starts_with(&[0x1f, 0x8b])) {
+            self.add_tile_precompressed(tile_id, data, Compression::Gzip)
+        } else {
+            self.add_tile(tile_id, data)
+        }
+    }
+
+    /// Add a tile whose data is already compressed, storing the bytes as is.
+    ///
+    /// The first stored tile determines the tile compression of the archive, overriding
+    /// [`PmTilesWriter::with_tile_compression`]. Later tiles must use the same compression,
+    /// otherwise [`PmtError::CompressionMismatch`] is returned. Ordering, run-length encoding
+    /// and deduplication work as for [`Self::add_tile`].
+    pub fn add_tile_precompressed(
+        &mut self,
+        tile_id: u64,
+        data: &[u8],
+        compression: Compression,
+    ) -> PmtResult<()> {
+        if self.entries.is_empty() {
+            self.header.tile_compression = compression;
+        } else if compression != self.header.tile_compression {
+            return Err(PmtError::CompressionMismatch(
+                self.header.tile_compression,
+                compression,
+            ));
+        }
+        self.add_tile_data(tile_id, data, false)
+    }
+
+    fn add_tile_data(&mut self, tile_id: u64, data: &[u8], compress: bool) -> PmtResult<()> {
+        if let Some(last) = self.last_tile_id.filter(|&last| tile_id <= last) {
+            return Err(PmtError::TilesOutOfOrder {
+                expected_min: last + 1,
+                got: tile_id,
+            });
+        }
+        self.last_tile_id = Some(tile_id);
+
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        self.n_addressed_tiles += 1;
+        let (z, x, y) = tile_coord(tile_id);
+        TileExtent::add(&mut self.extent, z, x, y);
+
+        if let Some(last) = self.entries.last_mut() {
+            if tile_id == last.tile_id + u64::from(last.run_length) && data == self.prev_tile_data {
+                last.run_length += 1;
+                return Ok(());
+            }
+        }
+        data.clone_into(&mut self.prev_tile_data);
+
+        let key = self.written_tiles.as_ref().map(|w| w.key(data));
+        let written = key.and_then(|key| self.written_tiles.as_ref()?.get(key, data));
+        let (offset, length) = if let Some(written) = written {
+            written
+        } else {
+            let written = self.write_tile_data(data, compress)?;
+            if let (Some(written_tiles), Some(key)) = (&mut self.written_tiles, key) {
+                written_tiles.insert(key, data, written);
+            }
+            written
+        };
+        self.entries.push(DirEntry {
+            tile_id,
+            offset,
+            length,
+            run_length: 1,
+        });
+
+        Ok(())
+    }
+
+    /// Compress and write tile data, returning its offset and length within the tile data section.
+    fn write_tile_data(&mut self, data: &[u8], compress_data: bool) -> PmtResult<(u64, u32)> {
+        let compressed = if compress_data {
+            compress(self.header.tile_compression, data)?
+        } else {
+            data.to_vec()
+        };
+        // The data section starts aligned, so aligning offsets within it aligns the file offsets
+        let offset = self
+            .header
+            .data_length
+            .next_multiple_of(self.tile_alignment);
+        self.write_padding(offset - self.header.data_length)?;
+        self.header.data_length = offset;
+        let length = into_u32(compressed.len())?;
+        self.out.write_all(&compressed)?;
+        self.header.data_length += u64::from(length);
+        self.n_tile_contents += 1;
+        Ok((offset, length))
+    }
+
+    fn write_padding(&mut self, length: u64) -> PmtResult<()> {
+        std::io::copy(&mut std::io::repeat(0).take(length), &mut self.out)?;
+        Ok(())
+    }
+
+    /// Write the directories and the header, completing the archive.
+    ///
+    /// The output is flushed after seeking back and writing the header, so all bytes have
+    /// reached the underlying writer, e.g. of a [`std::io::BufWriter`], when this returns.
+    pub fn finalize(mut self) -> PmtResult<()> {
+        self.apply_extent();
+        #[cfg(feature = "metadata")]
+        if let Some(warning) = self.metadata_zoom_mismatch() {
+            log::warn!("{warning}");
+        }
+        let (root_dir, leaf_dirs) = build_directories(
+            &self.entries,
+            self.header.internal_compression,
+            MAX_INITIAL_BYTES - HEADER_SIZE,
+        )?;
+
+        // Leaf directories are appended after the tile data
+        self.header.leaf_offset = self.header.data_offset + self.header.data_length;
+        self.header.leaf_length = leaf_dirs.len() as u64;
+        self.out.write_all(&leaf_dirs)?;
+
+        self.header.root_offset = HEADER_SIZE as u64;
+        self.header.root_length = root_dir.len() as u64;
+        self.header.n_addressed_tiles = NonZeroU64::new(self.n_addressed_tiles);
+        self.header.n_tile_entries = NonZeroU64::new(self.entries.len() as u64);
+        self.header.n_tile_contents = NonZeroU64::new(self.n_tile_contents);
+        self.header.clustered = entries_clustered(&self.entries)?;
+
+        self.out.seek(SeekFrom::Start(0))?;
+        self.header.write_to(&mut self.out)?;
+        self.out.write_all(&root_dir)?;
+        self.out.flush()?;
+
+        Ok(())
+    }
+
+    /// Describes how the zoom range of the metadata differs from the one of the header, if at all.
+    #[cfg(feature = "metadata")]
+    fn metadata_zoom_mismatch(&self) -> Option<String> {
+        let (min_zoom, max_zoom) = self.metadata_zooms?;
+        let (header_min, header_max) = (self.header.min_zoom, self.header.max_zoom);
+        (min_zoom.is_some_and(|z| z != header_min) || max_zoom.is_some_and(|z| z != header_max))
+            .then(|| {
+                format!(
+                    "Metadata zoom range {min_zoom:?}..={max_zoom:?} does not match the header zoom range {header_min}..={header_max}"
+                )
+            })
+    }
+
+    /// Set the zoom levels, bounds and center of the header from the added tiles,
+    /// unless they were set explicitly.
+    fn apply_extent(&mut self) {
+        let Some(extent) = self.extent else {
+            return;
+        };
+        let h = &mut self.header;
+        if !self.overrides.min_zoom {
+            h.min_zoom = extent.min_zoom;
+        }
+        if !self.overrides.max_zoom {
+            h.max_zoom = extent.max_zoom;
+        }
+        if !self.overrides.bounds {
+            [
+                h.min_longitude,
+                h.min_latitude,
+                h.max_longitude,
+                h.max_latitude,
+            ] = extent.bounds();
+        }
+        if !self.overrides.center {
+            (h.center_longitude, h.center_latitude) = extent.center();
+            h.center_zoom = extent.max_zoom;
+        }
+    }
+}
+
+/// Writer accepting tiles in any order, created by [`PmTilesWriter::create_unsorted`].
+///
+/// Only the tile IDs and the location of their data in the temporary file are kept in memory.
+/// Nothing but the reserved space for the header is written to the output before
+/// [`Self::finalize`], so dropping the writer without finalizing it leaves an invalid archive.
+#[cfg(feature = "write")]
+pub struct PmTilesUnsortedWriter<W: Write + Seek> {
+    writer: PmTilesStreamWriter<W>,
+    spill: BufWriter<File>,
+    spill_length: u64,
+    temp_file: TempFile,
+    tiles: Vec<SpilledTile>,
+}
+
+/// Writer for outputs which cannot seek, created by [`PmTilesWriter::create_seekless`].
+///
+/// Tiles are added as with a [`PmTilesStreamWriter`], but nothing is written to the output
+/// before [`Self::finalize`]. Dropping the writer without finalizing it writes nothing.
+#[cfg(feature = "write")]
+pub struct PmTilesSeeklessWriter<W: Write> {
+    writer: PmTilesStreamWriter<BufWriter<File>>,
+    /// Handle of the temporary file to read the archive back from
+    temp: File,
+    temp_file: TempFile,
+    out: W,
+}
+
+#[cfg(feature = "write")]
+impl<W: Write> PmTilesSeeklessWriter<W> {
+    /// Add a tile to the archive, see [`PmTilesStreamWriter::add_tile`].
+    pub fn add_tile(&mut self, tile_id: u64, data: &[u8]) -> PmtResult<()> {
+        self.writer.add_tile(tile_id, data)
+    }
+
+    /// Add a tile whose data is already compressed, see
+    /// [`PmTilesStreamWriter::add_tile_precompressed`].
+    pub fn add_tile_precompressed(
+        &mut self,
+        tile_id: u64,
+        data: &[u8],
+        compression: Compression,
+    ) -> PmtResult<()> {
+        self.writer
+            .add_tile_precompressed(tile_id, data, compression)
+    }
+
+    /// Complete the archive in the temporary file and copy it into the output, which is
+    /// flushed afterwards.
+    pub fn finalize(mut self) -> PmtResult<()> {
+        self.writer.finalize()?;
+        self.temp.seek(SeekFrom::Start(0))?;
+        std::io::copy(&mut self.temp, &mut self.out)?;
+        self.out.flush()?;
+        drop(self.temp);
+        drop(self.temp_file);
+
+        Ok(())
+    }
+}
+
+/// Location of a tile's data in the temporary file of a [`PmTilesUnsortedWriter`].
+#[cfg(feature = "write")]
+struct SpilledTile {
+    tile_id: u64,
+    offset: u64,
+    length: usize,
+    /// Compression of precompressed tile data
+    compression: Option<Compression>,
+}
+
+/// Removes the file at the path when dropped.
+#[cfg(feature = "write")]
+struct TempFile(PathBuf);
+
+#[cfg(feature = "write")]
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        // The file may already be gone, there is nothing to do about other errors either
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(feature = "write")]
+impl<W: Write + Seek> PmTilesUnsortedWriter<W> {
+    /// Add a tile to the archive, buffering its data in the temporary file.
+    ///
+    /// Tiles may be added in any order, but every tile ID only once, otherwise
+    /// [`Self::finalize`] fails with [`PmtError::DuplicateTile`]. Empty tiles are skipped.
+    pub fn add_tile(&mut self, tile_id: u64, data: &[u8]) -> PmtResult<()> {
+        self.spill_tile(tile_id, data, None)
+    }
+
+    /// Add a tile whose data is already compressed, see
+    /// [`PmTilesStreamWriter::add_tile_precompressed`].
+    pub fn add_tile_precompressed(
+        &mut self,
+        tile_id: u64,
+        data: &[u8],
+        compression: Compression,
+    ) -> PmtResult<()> {
+        self.spill_tile(tile_id, data, Some(compression))
+    }
+
+    fn spill_tile(
+        &mut self,
+        tile_id: u64,
+        data: &[u8],
+        compression: Option<Compression>,
+    ) -> PmtResult<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.spill.write_all(data)?;
+        self.tiles.push(SpilledTile {
+            tile_id,
+            offset: self.spill_length,
+            length: data.len(),
+            compression,
+        });
+        self.spill_length += data.len() as u64;
+        Ok(())
+    }
+
+    /// Copy the tiles into the archive in tile ID order, and write the directories and
+    /// the header, completing the archive. The output is flushed, see
+    /// [`PmTilesStreamWriter::finalize`].
+    pub fn finalize(mut self) -> PmtResult<()> {
+        self.tiles.sort_by_key(|t| t.tile_id);
+        if let Some(pair) = self.tiles.windows(2).find(|t| t[0].tile_id == t[1].tile_id) {
+            return Err(PmtError::DuplicateTile(pair[0].tile_id));
+        }
+
+        let mut spill = self
+            .spill
+            .into_inner()
+            .map_err(std::io::IntoInnerError::into_error)?;
+        let mut data = Vec::new();
+        for tile in &self.tiles {
+            data.resize(tile.length, 0);
+            spill.seek(SeekFrom::Start(tile.offset))?;
+            spill.read_exact(&mut data)?;
+            match tile.compression {
+                Some(compression) => {
+                    self.writer
+                        .add_tile_precompressed(tile.tile_id, &data, compression)?;
+                }
+                None => self.writer.add_tile(tile.tile_id, &data)?,
+            }
+        }
+        drop(spill);
+        self.writer.finalize()?;
+        drop(self.temp_file);
+
+        Ok(())
+    }
+}
+
+/// Build the compressed root directory and leaf directories section from the tile entries.
+///
+/// The root directory must fit into `max_root_length` bytes, e.g. the first 16 KiB of
+/// the archive together with the header, so entries are moved into leaf directories of
+/// increasing size until it does.
+#[cfg(feature = "write")]
+pub(crate) fn build_directories(
+    entries: &[DirEntry],
+    compression: Compression,
+    max_root_length: usize,
+) -> PmtResult<(Vec<u8>, Vec<u8>)> {
+    let directory = Directory::from_entries(entries.to_vec());
+
+    let root_dir = compress_directory(compression, &directory)?;
+    if root_dir.len() <= max_root_length {
+        return Ok((root_dir, Vec::new()));
+    }
+
+    let mut leaf_size = 4096;
+    loop {
+        let max_root_entries = entries.len().div_ceil(leaf_size);
+        let (root, _, leaf_dirs) = directory
+            .split_into_leaves_with(max_root_entries, |dir| compress_directory(compression, dir))?;
+        let root_dir = compress_directory(compression, &root)?;
+        if root_dir.len() <= max_root_length {
+            return Ok((root_dir, leaf_dirs));
+        }
+        if max_root_entries == 1 {
+            return Err(PmtError::RootDirectoryTooLarge(max_root_length));
+        }
+        leaf_size += leaf_size / 5;
+    }
+}
+
+#[cfg(feature = "write")]
+pub(crate) fn into_u32(value: usize) -> PmtResult<u32> {
+    u32::try_from(value).map_err(|_| PmtError::InvalidEntry)
+}
+
+#[cfg(feature = "write")]
+fn compress_directory(compression: Compression, directory: &Directory) -> PmtResult<Vec<u8>> {
+    let mut buf = Vec::new();
+    directory.write_to(&mut buf)?;
+    compress(compression, &buf)
+}
+
+#[cfg(feature = "write")]
+pub(crate) fn compress(compression: Compression, data: &[u8]) -> PmtResult<Vec<u8>> {
+    match compression {
+        Compression::None => Ok(data.to_vec()),
+        Compression::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        }
+        #[cfg(feature = "brotli")]
+        Compression::Brotli => {
+            let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+            encoder.write_all(data)?;
+            Ok(encoder.into_inner())
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Ok(zstd::encode_all(data, zstd::DEFAULT_COMPRESSION_LEVEL)?),
+        v => Err(PmtError::UnsupportedCompression(v)),
+    }
+}
+
+#[cfg(any(feature = "write", feature = "sync"))]
+pub(crate) fn decompress(compression: Compression, bytes: Bytes) -> PmtResult<Bytes> {
+    if compression == Compression::None {
+        return Ok(bytes);
+    }
+
+    let mut decompressed_bytes = Vec::with_capacity(bytes.len() * 2);
+    match compression {
+        Compression::Gzip => {
+            // Some writers store the data as several concatenated gzip members
+            flate2::read::MultiGzDecoder::new(&bytes[..]).read_to_end(&mut decompressed_bytes)?;
+        }
+        #[cfg(feature = "brotli")]
+        Compression::Brotli => {
+            brotli::Decompressor::new(&bytes[..], 4096).read_to_end(&mut decompressed_bytes)?;
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => {
+            zstd::Decoder::new(&bytes[..])?.read_to_end(&mut decompressed_bytes)?;
+        }
+        v => Err(PmtError::UnsupportedCompression(v))?,
+    }
+
+    Ok(Bytes::from(decompressed_bytes))
+}
+
+#[cfg(test)]
+#[cfg(all(feature = "write", feature = "mmap-async-tokio"))]
+mod tests {
+    use std::fs::File;
+    use std::io::Read as _;
+    use std::num::NonZeroU64;
+
+    use bytes::Bytes;
+
+    use super::PmTilesWriter;
+    use crate::async_reader::AsyncPmTilesReader;
+    use crate::tests::{get_temp_file_path, RASTER_FILE};
+    use crate::tile_id::tile_coord;
+    use crate::{Compression, Directory, Header, HeaderBuilder, MmapBackend, PmtError, TileType};
+
+    #[tokio::test]
+    async fn roundtrip_raster() {
+        let backend = MmapBackend::try_from(RASTER_FILE).await.unwrap();
+        let tiles_in = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let path = get_temp_file_path("writer-raster.pmtiles");
+        let file = File::create(&path).unwrap();
+        let mut writer = PmTilesWriter::new(TileType::Png)
+            .with_max_zoom(3)
+            .with_raw_metadata(&tiles_in.get_metadata().await.unwrap())
+            .create(file)
+            .unwrap();
+        for id in 0..85 {
+            let (z, x, y) = tile_coord(id);
+            let tile = tiles_in.get_tile(z, x, y).await.unwrap().unwrap();
+            writer.add_tile(id, &tile).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let backend = MmapBackend::try_from(&path).await.unwrap();
+        let tiles_out = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+
+        let header_in = tiles_in.get_header();
+        let header_out = tiles_out.get_header();
+        assert_eq!(header_out.tile_type, TileType::Png);
+        assert_eq!(header_out.max_zoom, 3);
+        assert_eq!(header_out.n_addressed_tiles, header_in.n_addressed_tiles);
+        assert_eq!(header_out.n_tile_entries, header_in.n_tile_entries);
+        assert_eq!(header_out.leaf_length, 0);
+        assert!(header_out.is_clustered());
+        assert!(tiles_out.is_clustered().await.unwrap());
+        assert_eq!(
+            tiles_out.get_metadata().await.unwrap(),
+            tiles_in.get_metadata().await.unwrap()
+        );
+
+        for id in 0..85 {
+            let (z, x, y) = tile_coord(id);
+            assert_eq!(
+                tiles_out.get_tile(z, x, y).await.unwrap(),
+                tiles_in.get_tile(z, x, y).await.unwrap()
+            );
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "metadata")]
+    async fn write_typed_metadata() {
+        let fname = get_temp_file_path("writer-metadata.pmtiles");
+        let metadata = crate::Metadata {
+            name: Some("test".to_string()),
+            minzoom: Some(0),
+            maxzoom: Some(1),
+            ..Default::default()
+        };
+
+        let file = File::create(&fname).unwrap();
+        let mut writer = PmTilesWriter::new(TileType::Png)
+            .with_max_zoom(1)
+            .with_metadata(&metadata)
+            .create(file)
+            .unwrap();
+        writer.add_tile(0, &[1, 2, 3]).unwrap();
+        writer.finalize().unwrap();
+
+        let backend = MmapBackend::try_from(&fname).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        assert_eq!(tiles.get_metadata_typed().await.unwrap(), metadata);
+        std::fs::remove_file(fname).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "metadata")]
+    fn metadata_zooms_from_tiles() {
+        use crate::tile_id::tile_id;
+
+        let writer = |minzoom, maxzoom| {
+            let metadata = crate::Metadata {
+                minzoom,
+                maxzoom,
+                ..Default::default()
+            };
+            let mut writer = PmTilesWriter::new(TileType::Png)
+                .with_metadata(&metadata)
+                .create(std::io::Cursor::new(Vec::new()))
+                .unwrap();
+            // The tiles, not an override, set the zoom range to 1..=2
+            writer.add_tile(tile_id(1, 0, 0), b"a").unwrap();
+            writer.add_tile(tile_id(2, 0, 0), b"b").unwrap();
+            writer.apply_extent();
+            writer
+        };
+
+        assert_eq!(writer(Some(1), Some(2)).metadata_zoom_mismatch(), None);
+        assert_eq!(writer(None, Some(2)).metadata_zoom_mismatch(), None);
+        let warning = writer(Some(0), Some(2)).metadata_zoom_mismatch().unwrap();
+        assert!(warning.contains("1..=2"), "{warning}");
+        writer(Some(1), Some(2)).finalize().unwrap();
+    }
+
+    #[tokio::test]
+    #[allow(clippy::float_cmp)]
+    async fn compute_header_from_tiles() {
+        use crate::tile_id::tile_id;
+
+        let write = |fname, writer: PmTilesWriter| {
+            let mut writer = writer.create(File::create(fname).unwrap()).unwrap();
+            // The north-eastern quarter of the world
+            let mut ids = [(1, 1, 0), (2, 2, 0), (2, 3, 0), (2, 2, 1), (2, 3, 1)]
+                .map(|(z, x, y)| tile_id(z, x, y));
+            ids.sort_unstable();
+            for id in ids {
+                writer.add_tile(id, &id.to_le_bytes()).unwrap();
+            }
+            writer.finalize().unwrap();
+        };
+        let read_header = |fname| async move {
+            let backend = MmapBackend::try_from(fname).await.unwrap();
+            let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+            let h = tiles.get_header();
+            (h.min_zoom, h.max_zoom, h.bounds(), h.center())
+        };
+
+        let fname = get_temp_file_path("writer-extent.pmtiles");
+        write(&fname, PmTilesWriter::new(TileType::Png));
+        let (min_zoom, max_zoom, bounds, center) = read_header(&fname).await;
+        assert_eq!((min_zoom, max_zoom), (1, 2));
+        // The header stores coordinates as `f32`
+        assert_eq!(bounds, [0.0, 0.0, 180.0, 85.051_132_2]);
+        let (lon, lat, zoom) = center;
+        assert_eq!((lon, zoom), (90.0, 2));
+        assert!((lat - 66.513_26).abs() < 1e-5, "{lat}");
+
+        write(
+            &fname,
+            PmTilesWriter::new(TileType::Png)
+                .with_min_zoom(0)
+                .with_bounds(1.0, 2.0, 3.0, 4.0)
+                .with_center(2.0, 3.0, 1),
+        );
+        let (min_zoom, max_zoom, bounds, center) = read_header(&fname).await;
+        assert_eq!((min_zoom, max_zoom), (0, 2));
+        assert_eq!(bounds, [1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(center, (2.0, 3.0, 1));
+
+        std::fs::remove_file(fname).unwrap();
+    }
+
+    #[test]
+    fn reject_tiles_out_of_order() {
+        let fname = get_temp_file_path("writer-order.pmtiles");
+        let file = File::create(&fname).unwrap();
+        let mut writer = PmTilesWriter::new(TileType::Png).create(file).unwrap();
+        writer.add_tile(0, &[1]).unwrap();
+        writer.add_tile(2, &[]).unwrap();
+
+        for tile_id in [1, 2] {
+            assert!(matches!(
+                writer.add_tile(tile_id, &[2]),
+                Err(PmtError::TilesOutOfOrder {
+                    expected_min: 3,
+                    got
+                }) if got == tile_id
+            ));
+        }
+        writer.add_tile(3, &[3]).unwrap();
+        writer.finalize().unwrap();
+        std::fs::remove_file(fname).unwrap();
+    }
+
+    #[tokio::test]
+    async fn write_avif() {
+        let path = get_temp_file_path("writer-avif.pmtiles");
+        let file = File::create(&path).unwrap();
+        let mut writer = PmTilesWriter::new(TileType::Unknown)
+            .with_tile_type(TileType::Avif)
+            .create(file)
+            .unwrap();
+        writer.add_tile(0, b"avif").unwrap();
+        writer.finalize().unwrap();
+
+        let backend = MmapBackend::try_from(&path).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let header = tiles.get_header();
+        assert_eq!(header.tile_type, TileType::Avif);
+        assert_eq!(header.tile_type.content_type(), "image/avif");
+        assert_eq!(header.tile_compression, Compression::None);
+        assert_eq!(
+            tiles.get_tile(0, 0, 0).await.unwrap().unwrap(),
+            &b"avif"[..]
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn write_precompressed() {
+        let path = get_temp_file_path("writer-precompressed.pmtiles");
+        let file = File::create(&path).unwrap();
+        let gzipped = super::compress(Compression::Gzip, b"tile").unwrap();
+
+        let mut writer = PmTilesWriter::new(TileType::Mvt)
+            .with_tile_compression(Compression::None)
+            .create(file)
+            .unwrap();
+        writer
+            .add_tile_precompressed(0, &gzipped, Compression::Gzip)
+            .unwrap();
+        assert!(matches!(
+            writer.add_tile_precompressed(1, b"tile", Compression::None),
+            Err(PmtError::CompressionMismatch(
+                Compression::Gzip,
+                Compression::None
+            ))
+        ));
+        writer
+            .add_tile_precompressed(2, &gzipped, Compression::Gzip)
+            .unwrap();
+        // Regular tiles are compressed to match
+        writer.add_tile(3, b"other").unwrap();
+        writer.finalize().unwrap();
+
+        let backend = MmapBackend::try_from(&path).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        assert_eq!(tiles.get_header().tile_compression, Compression::Gzip);
+        assert_eq!(tiles.get_header().n_addressed_tiles, NonZeroU64::new(3));
+        assert_eq!(tiles.get_tile(0, 0, 0).await.unwrap().unwrap(), gzipped);
+        assert!(tiles.get_tile(1, 0, 0).await.unwrap().is_none());
+        let (z, x, y) = tile_coord(3);
+        let tile = tiles.get_tile_zxy(z, x, y).await.unwrap().unwrap();
+        assert_eq!(tile, &b"other"[..]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn independent_compression() {
+        let path = get_temp_file_path("writer-compression.pmtiles");
+
+        for (tile_compression, internal_compression) in [
+            (Compression::Gzip, Compression::Gzip),
+            (Compression::None, Compression::Gzip),
+            (Compression::Gzip, Compression::None),
+        ] {
+            let file = File::create(&path).unwrap();
+            let mut builder = PmTilesWriter::new(TileType::Mvt);
+            // Vector tiles default to gzip
+            if tile_compression != Compression::Gzip {
+                builder = builder.with_tile_compression(tile_compression);
+            }
+            let mut writer = builder
+                .with_internal_compression(internal_compression)
+                .with_raw_metadata(r#"{"name":"test"}"#)
+                .create(file)
+                .unwrap();
+            writer.add_tile(0, b"tile").unwrap();
+            writer.finalize().unwrap();
+
+            let backend = MmapBackend::try_from(&path).await.unwrap();
+            let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+            let header = tiles.get_header();
+            assert_eq!(header.tile_compression, tile_compression);
+            assert_eq!(header.internal_compression, internal_compression);
+            assert_eq!(tiles.get_metadata().await.unwrap(), r#"{"name":"test"}"#);
+
+            let stored = tiles.get_tile(0, 0, 0).await.unwrap().unwrap();
+            assert_eq!(stored == b"tile"[..], tile_compression == Compression::None);
+            let tile = tiles.get_tile_zxy(0, 0, 0).await.unwrap().unwrap();
+            assert_eq!(tile, &b"tile"[..]);
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "zstd")]
+    async fn roundtrip_zstd() {
+        let path = get_temp_file_path("writer-zstd.pmtiles");
+        let file = File::create(&path).unwrap();
+        let mut writer = PmTilesWriter::new(TileType::Mvt)
+            .with_internal_compression(Compression::Zstd)
+            .with_tile_compression(Compression::Zstd)
+            .with_raw_metadata(r#"{"name":"zstd"}"#)
+            .create(file)
+            .unwrap();
+        for id in 0..85 {
+            writer
+                .add_tile(id, format!("tile {id}").as_bytes())
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let backend = MmapBackend::try_from(&path).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let header = tiles.get_header();
+        assert_eq!(header.internal_compression, Compression::Zstd);
+        assert_eq!(header.tile_compression, Compression::Zstd);
+        assert_eq!(tiles.get_metadata().await.unwrap(), r#"{"name":"zstd"}"#);
+
+        for id in 0..85 {
+            let (z, x, y) = tile_coord(id);
+            let tile = tiles.get_tile_zxy(z, x, y).await.unwrap().unwrap();
+            assert_eq!(tile, format!("tile {id}").as_bytes());
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "brotli")]
+    async fn roundtrip_brotli() {
+        let path = get_temp_file_path("writer-brotli.pmtiles");
+        let file = File::create(&path).unwrap();
+        let mut writer = PmTilesWriter::new(TileType::Mvt)
+            .with_tile_compression(Compression::Brotli)
+            .with_raw_metadata(r#"{"name":"brotli"}"#)
+            .create(file)
+            .unwrap();
+        for id in 0..85 {
+            writer
+                .add_tile(id, format!("tile {id}").as_bytes())
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let backend = MmapBackend::try_from(&path).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let header = tiles.get_header();
+        assert_eq!(header.internal_compression, Compression::Gzip);
+        assert_eq!(header.tile_compression, Compression::Brotli);
+        assert_eq!(tiles.get_metadata().await.unwrap(), r#"{"name":"brotli"}"#);
+
+        for id in 0..85 {
+            let (z, x, y) = tile_coord(id);
+            let tile = tiles.get_tile_zxy(z, x, y).await.unwrap().unwrap();
+            assert_eq!(tile, format!("tile {id}").as_bytes());
+        }
+
+        std::fs::remove_file(&path).unwrap();
+
+        // Brotli directories and metadata with uncompressed tiles
+        let file = File::create(&path).unwrap();
+        let mut writer = PmTilesWriter::new(TileType::Png)
+            .with_internal_compression(Compression::Brotli)
+            .with_raw_metadata("{}")
+            .create(file)
+            .unwrap();
+        writer.add_tile(0, b"png").unwrap();
+        writer.finalize().unwrap();
+
+        let backend = MmapBackend::try_from(&path).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        assert_eq!(tiles.get_header().internal_compression, Compression::Brotli);
+        assert_eq!(tiles.get_metadata().await.unwrap(), "{}");
+        assert_eq!(tiles.get_tile(0, 0, 0).await.unwrap().unwrap(), &b"png"[..]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn write_deduplicated() {
+        let path = get_temp_file_path("writer-dedup.pmtiles");
+        let file = File::create(&path).unwrap();
+        let mut writer = PmTilesWriter::new(TileType::Png)
+            .dedup(true)
+            .create(file)
+            .unwrap();
+        // Tiles alternating between two contents can't be run-length encoded
+        for id in 0..85 {
+            writer.add_tile(id, &[u8::from(id % 2 == 0); 100]).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let backend = MmapBackend::try_from(&path).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let header = tiles.get_header();
+        assert_eq!(header.n_addressed_tiles, NonZeroU64::new(85));
+        assert_eq!(header.n_tile_entries, NonZeroU64::new(85));
+        assert_eq!(header.n_tile_contents, NonZeroU64::new(2));
+        assert_eq!(header.data_length, 200);
+
+        for id in 0..85 {
+            let (z, x, y) = tile_coord(id);
+            let tile = tiles.get_tile(z, x, y).await.unwrap().unwrap();
+            assert_eq!(tile, vec![u8::from(id % 2 == 0); 100]);
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn write_from_header() {
+        let header = HeaderBuilder::new(TileType::Png)
+            .internal_compression(Compression::None)
+            .zoom_range(0, 5)
+            .bounds(-10.0, -10.0, 10.0, 10.0)
+            .center(1.0, 1.0, 3)
+            .build()
+            .unwrap();
+        let path = get_temp_file_path("writer-from-header.pmtiles");
+        let mut writer = PmTilesWriter::from_header(&header)
+            .create(File::create(&path).unwrap())
+            .unwrap();
+        writer.add_tile(0, b"tile").unwrap();
+        writer.finalize().unwrap();
+
+        // The explicit fields are kept even though the tiles only cover zoom 0
+        let backend = MmapBackend::try_from(&path).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let written = tiles.get_header();
+        assert_eq!(written.internal_compression, Compression::None);
+        assert_eq!((written.min_zoom, written.max_zoom), (0, 5));
+        let e7 = |h: &Header| {
+            let bounds = [
+                h.min_lon_e7(),
+                h.min_lat_e7(),
+                h.max_lon_e7(),
+                h.max_lat_e7(),
+            ];
+            (bounds, h.center_lon_e7(), h.center_lat_e7(), h.center_zoom)
+        };
+        assert_eq!(e7(written), e7(&header));
+        assert_eq!(written.n_addressed_tiles, NonZeroU64::new(1));
+        assert!(written.is_clustered());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn write_aligned() {
+        let path = get_temp_file_path("writer-aligned.pmtiles");
+        let mut writer = PmTilesWriter::new(TileType::Png)
+            .align_tiles(4096)
+            .create(File::create(&path).unwrap())
+            .unwrap();
+        // Tiles of different lengths, none a multiple of the alignment
+        let data = |id: u64| vec![7; 100 * usize::try_from(id).unwrap() + 1];
+        for id in 0..5 {
+            writer.add_tile(id, &data(id)).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let backend = MmapBackend::try_from(&path).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let header = tiles.get_header();
+        assert_eq!(header.data_offset % 4096, 0);
+        assert!(!header.clustered);
+        for id in 0..5 {
+            let (tile, entry) = tiles.get_tile_with_entry(id).await.unwrap().unwrap();
+            assert_eq!(entry.offset, id * 4096);
+            assert_eq!(tile, data(id));
+        }
+        assert!(tiles.validate().await.unwrap().is_valid());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// Hasher mapping all contents to the same hash.
+    #[derive(Default)]
+    struct CollidingHasher;
+
+    impl std::hash::Hasher for CollidingHasher {
+        fn finish(&self) -> u64 {
+            0
+        }
+
+        fn write(&mut self, _: &[u8]) {}
+    }
+
+    #[test]
+    fn dedup_hash_collisions() {
+        let hasher = std::hash::BuildHasherDefault::<CollidingHasher>::default();
+        let mut writers = vec![PmTilesWriter::new(TileType::Png).dedup_with_hasher(hasher)];
+        #[cfg(feature = "sha256")]
+        writers.push(PmTilesWriter::new(TileType::Png).dedup_sha256());
+        for writer in writers {
+            let mut writer = writer.create(std::io::Cursor::new(Vec::new())).unwrap();
+            for id in 0..30 {
+                writer.add_tile(id, &[(id % 3) as u8; 10]).unwrap();
+            }
+            // Tiles of the same length and hash, but different contents are kept apart
+            assert_eq!(writer.n_tile_contents, 3);
+            assert_eq!(writer.entries.len(), 30);
+            for entry in &writer.entries {
+                assert_eq!(entry.offset, (entry.tile_id % 3) * 10);
+            }
+            writer.finalize().unwrap();
+        }
+    }
+
+    #[test]
+    fn dedup_contents() {
+        let tile = vec![1; 100_000];
+        let mut writers = vec![(PmTilesWriter::new(TileType::Png).dedup(true), true)];
+        #[cfg(feature = "sha256")]
+        writers.push((PmTilesWriter::new(TileType::Png).dedup_sha256(), false));
+        for (writer, keeps_contents) in writers {
+            let mut writer = writer.create(std::io::Cursor::new(Vec::new())).unwrap();
+            writer.add_tile(0, &tile).unwrap();
+            writer.add_tile(2, &tile).unwrap();
+            assert_eq!(writer.n_tile_contents, 1);
+
+            // Hashed tiles keep their contents to compare them, SHA-256 digests do not
+            let tiles = writer.written_tiles.as_ref().unwrap();
+            let written = &tiles.by_key[&tiles.key(&tile)];
+            assert_eq!(written.len(), 1);
+            assert_eq!((written[0].offset, written[0].length), (0, 100_000));
+            assert_eq!(written[0].data.is_some(), keeps_contents);
+        }
+    }
+
+    /// Output keeping track of writes after the last flush.
+    #[derive(Default)]
+    struct FlushTracker {
+        out: std::io::Cursor<Vec<u8>>,
+        unflushed: bool,
+    }
+
+    impl std::io::Write for FlushTracker {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.unflushed = true;
+            self.out.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.unflushed = false;
+            Ok(())
+        }
+    }
+
+    impl std::io::Seek for FlushTracker {
+        fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+            self.out.seek(pos)
+        }
+    }
+
+    /// Output which can only be written to.
+    struct Pipe(Vec<u8>);
+
+    impl std::io::Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn write_seekless() {
+        let temp_path = get_temp_file_path("writer-seekless.tmp");
+        let mut out = Pipe(Vec::new());
+        let mut writer = PmTilesWriter::new(TileType::Png)
+            .create_seekless(&mut out, &temp_path)
+            .unwrap();
+        for id in 0..100 {
+            writer.add_tile(id, &id.to_le_bytes()).unwrap();
+        }
+        writer.finalize().unwrap();
+        assert!(!temp_path.exists());
+
+        let tiles = AsyncPmTilesReader::from_bytes(Bytes::from(out.0))
+            .await
+            .unwrap();
+        assert_eq!(tiles.get_header().n_addressed_tiles, NonZeroU64::new(100));
+        for id in 0..100 {
+            let (z, x, y) = tile_coord(id);
+            let tile = tiles.get_tile(z, x, y).await.unwrap().unwrap();
+            assert_eq!(tile, &id.to_le_bytes()[..]);
+        }
+    }
+
+    #[test]
+    fn finalize_flushes() {
+        let mut out = FlushTracker::default();
+        let mut writer = PmTilesWriter::new(TileType::Png).create(&mut out).unwrap();
+        writer.add_tile(0, b"tile").unwrap();
+        writer.finalize().unwrap();
+
+        assert!(!out.unflushed);
+        assert!(out.out.get_ref().starts_with(b"PMTiles"));
+    }
+
+    #[tokio::test]
+    #[allow(clippy::cast_possible_truncation)]
+    async fn write_unsorted() {
+        let path = get_temp_file_path("writer-unsorted.pmtiles");
+        let temp_path = get_temp_file_path("writer-unsorted.spill");
+        let file = File::create(&path).unwrap();
+        let mut writer = PmTilesWriter::new(TileType::Png)
+            .create_unsorted(file, &temp_path)
+            .unwrap();
+        assert!(temp_path.exists());
+        // Tiles 10 to 19 share the same data and become a single run
+        for id in (0..30).rev() {
+            let data = if (10..20).contains(&id) { 10 } else { id };
+            writer.add_tile(id, &[data as u8; 10]).unwrap();
+        }
+        writer.finalize().unwrap();
+        assert!(!temp_path.exists());
+
+        let backend = MmapBackend::try_from(&path).await.unwrap();
+        let tiles = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let header = tiles.get_header();
+        assert_eq!(header.n_addressed_tiles, NonZeroU64::new(30));
+        assert_eq!(header.n_tile_entries, NonZeroU64::new(21));
+        assert!(tiles.is_clustered().await.unwrap());
+        for id in 0..30 {
+            let (z, x, y) = tile_coord(id);
+            let tile = tiles.get_tile(z, x, y).await.unwrap().unwrap();
+            let data = if (10..20).contains(&id) { 10 } else { id };
+            assert_eq!(tile, vec![data as u8; 10]);
+        }
+
+        let file = File::create(&path).unwrap();
+        let mut writer = PmTilesWriter::new(TileType::Png)
+            .create_unsorted(file, &temp_path)
+            .unwrap();
+        writer.add_tile(3, b"a").unwrap();
+        writer.add_tile(1, b"b").unwrap();
+        writer.add_tile(3, b"c").unwrap();
+        assert!(matches!(writer.finalize(), Err(PmtError::DuplicateTile(3))));
+        assert!(!temp_path.exists());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    #[allow(clippy::cast_possible_truncation)]
+    async fn write_leaf_directories() {
+        let path = get_temp_file_path("writer-leaves.pmtiles");
+        let file = File::create(&path).unwrap();
+        let mut writer = PmTilesWriter::new(TileType::Png).create(file).unwrap();
+        // Tiles of pseudo-random size with gaps between their IDs, enough to overflow the root directory
+        let mut tiles = Vec::new();
+        let (mut id, mut state) = (0_u64, 1_u64);
+        for _ in 0..50_000 {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1);
+            id += 1 + (state >> 58);
+            tiles.push((id, state.to_le_bytes().repeat(1 + (state >> 60) as usize)));
+        }
+        for (id, data) in &tiles {
+            writer.add_tile(*id, data).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let backend = MmapBackend::try_from(&path).await.unwrap();
+        let tiles_out = AsyncPmTilesReader::try_from_source(backend).await.unwrap();
+        let header = tiles_out.get_header();
+        assert_ne!(header.leaf_length, 0);
+
+        // Every leaf is gzip-compressed on its own, at the offset and length of its root entry
+        let gunzip = |data: &[u8]| {
+            let mut out = Vec::new();
+            flate2::read::GzDecoder::new(data)
+                .read_to_end(&mut out)
+                .unwrap();
+            Directory::try_from(Bytes::from(out)).unwrap()
+        };
+        let archive = std::fs::read(&path).unwrap();
+        let section =
+            |offset: u64, length: u64| &archive[offset as usize..(offset + length) as usize];
+        let root = gunzip(section(header.root_offset, header.root_length));
+        let leaf_section = section(header.leaf_offset, header.leaf_length);
+        let mut leaf_entries = 0;
+        for leaf in root.iter() {
+            assert!(leaf.is_leaf());
+            let offset = leaf.offset as usize;
+            let stored = &leaf_section[offset..offset + leaf.length as usize];
+            assert_eq!(stored[..2], [0x1f, 0x8b]);
+            leaf_entries += gunzip(stored).iter().count();
+        }
+        assert_eq!(leaf_entries, tiles.len());
+
+        for (id, data) in tiles.iter().step_by(997) {
+            let (z, x, y) = tile_coord(*id);
+            let tile = tiles_out.get_tile(z, x, y).await.unwrap().unwrap();
+            assert_eq!(&tile[..], data);
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+}